@@ -7,37 +7,146 @@ struct Params {
   resolution_y: u32,
   random_seed: u32,
   zoom: f32,
+  birth_mask: u32,
+  survival_mask: u32,
+  state_count: u32,
+  bits_per_cell: u32,
+  range: u32,
+  von_neumann: u32,
+  include_center: u32,
+  birth_min: u32,
+  birth_max: u32,
+  survival_min: u32,
+  survival_max: u32,
+  cell_count_x: u32,
+  topology: u32,
+  tile_display: u32,
+  ghost_x: i32,
+  ghost_y: i32,
+  ghost_width: u32,
+  ghost_height: u32,
+  ghost_replace: u32,
+  selection_x: u32,
+  selection_y: u32,
+  selection_width: u32,
+  selection_height: u32,
+  coloring: u32,
+}
+
+// must match `RegionOp`
+struct Region {
+  kind: u32,
+  x: u32,
+  y: u32,
+  width: u32,
+  height: u32,
+  target_x: i32,
+  target_y: i32,
+  threshold: u32,
+  seed: u32,
 }
 
 @group(0) @binding(0) var<uniform> params: Params;
 @group(0) @binding(1) var main_image: texture_storage_2d<rgba8unorm, read_write>;
-@group(0) @binding(2) var<storage, read_write> buffer: array<u32>;
+@group(0) @binding(2) var<storage, read> current: array<u32>;
+@group(0) @binding(3) var<storage, read_write> next: array<u32>;
+@group(0) @binding(4) var<storage, read> cell_edits: CellEdits;
+// the pattern attached to the cursor, `ghost_width` cells per row
+@group(0) @binding(5) var<storage, read> ghost: array<u32>;
+@group(0) @binding(6) var<uniform> region: Region;
+// cells copied from or pasted into the universe, `region.width` cells per row
+@group(0) @binding(7) var<storage, read_write> clipboard: array<u32>;
+// live cells counted by `count_population`, cleared by the node after every count
+@group(0) @binding(8) var<storage, read_write> population: atomic<u32>;
+// low and high half of the fingerprint summed up by `hash_generation`, cleared the same way
+@group(0) @binding(9) var<storage, read_write> fingerprint: array<atomic<u32>, 2>;
+// generations every cell has been alive for, 4 cells per word with the leftmost in the
+// lowest byte, in rows as wide as the padded rows of the universe; a single placeholder
+// word while no coloring needs them
+@group(0) @binding(10) var<storage, read_write> ages: array<u32>;
+
+// must match `CellEdit`
+struct CellEdit {
+  index: u32,
+  mask: u32,
+  bits: u32,
+}
+
+struct CellEdits {
+  count: u32,
+  edits: array<CellEdit>,
+}
 
 const COMPUTE_WG_SIZE: u32 = 1024;
 const DISPLAY_WG_SIZE: u32 = 32;
+const GHOST_COLOR: vec4<f32> = vec4<f32>(0.2, 0.8, 1.0, 1.0);
+const SELECTION_COLOR: vec4<f32> = vec4<f32>(0.3, 0.5, 1.0, 1.0);
+
+// must match the kinds of `RegionOp`
+const REGION_CLEAR: u32 = 1;
+const REGION_CLEAR_OUTSIDE: u32 = 2;
+const REGION_FILL: u32 = 3;
+const REGION_COPY: u32 = 4;
+const REGION_CUT: u32 = 5;
+const REGION_PASTE: u32 = 6;
+
+// must match `CellColoring::shader_id`
+const COLORING_PLAIN: u32 = 0;
+const COLORING_HEAT: u32 = 1;
+const COLORING_VIRIDIS: u32 = 2;
+const COLORING_ICE: u32 = 3;
+// the most cells along each axis a zoomed out pixel averages the ages of, larger areas
+// are sampled
+const MAX_AVERAGED_CELLS: u32 = 16;
+
+// must match `Topology::shader_id`
+const TOPOLOGY_DEAD_EDGES: u32 = 0;
+const TOPOLOGY_ALIVE_EDGES: u32 = 1;
+const TOPOLOGY_TORUS: u32 = 2;
+const TOPOLOGY_KLEIN_HORIZONTAL: u32 = 3;
+const TOPOLOGY_KLEIN_VERTICAL: u32 = 4;
+const TOPOLOGY_CROSS_SURFACE: u32 = 5;
+const TOPOLOGY_SPHERE: u32 = 6;
+
+// larger than life tiles, padded by an apron of the largest range on every side
+const LTL_TILE_X: u32 = 32;
+const LTL_TILE_Y: u32 = 8;
+const MAX_RANGE: u32 = 10;
+// prefix sums carry a leading zero row and column
+const SAT_X: u32 = LTL_TILE_X + 2 * MAX_RANGE + 1;
+const SAT_Y: u32 = LTL_TILE_Y + 2 * MAX_RANGE + 1;
+// one row of a tile spans at most 8 words, in the 8-bit layout
+const LTL_TILE_WORDS: u32 = LTL_TILE_Y * 8;
+
+var<workgroup> row_sums: array<u32, SAT_X * SAT_Y>;
+var<workgroup> box_sums: array<u32, SAT_X * SAT_Y>;
+var<workgroup> tile_words: array<atomic<u32>, LTL_TILE_WORDS>;
+var<workgroup> population_sums: array<u32, COMPUTE_WG_SIZE>;
+var<workgroup> fingerprint_sums: array<vec2<u32>, COMPUTE_WG_SIZE>;
 
 @compute @workgroup_size(COMPUTE_WG_SIZE)
 fn update(
   @builtin(global_invocation_id) id: vec3<u32>,
 ) {
-  let left_check = id.x % params.buffer_size_x > 0;
-  let top_check = id.x / params.buffer_size_x > 0;
-  let right_check = id.x % params.buffer_size_x < params.buffer_size_x - 1u;
-  let bottom_check = id.x / params.buffer_size_x < params.buffer_size_y - 1u;
-
-  let y_offset = params.buffer_size_x;
-  let me = buffer[id.x];
-  let left = ternary(left_check, buffer[id.x - 1], 0u);
-  let top = ternary(top_check, buffer[id.x - y_offset], 0u);
-  let right = ternary(right_check, buffer[id.x + 1], 0u);
-  let bottom = ternary(bottom_check, buffer[id.x + y_offset], 0u);
-  let top_left = ternary(top_check && left_check, buffer[id.x - y_offset - 1], 0u);
-  let top_right = ternary(top_check && right_check, buffer[id.x - y_offset + 1], 0u);
-  let bottom_left = ternary(bottom_check && left_check, buffer[id.x + y_offset - 1], 0u);
-  let bottom_right = ternary(bottom_check && right_check, buffer[id.x + y_offset + 1], 0u);
-
-  storageBarrier();
+  if (id.x >= params.buffer_size_x * params.buffer_size_y) {
+    return;
+  }
+
+  let x = i32((id.x % params.buffer_size_x) * 32u);
+  let y = i32(id.x / params.buffer_size_x);
+
+  // only bit 0 of the left words and bit 31 of the right words are ever read
+  let me = row_word(x, y);
+  let left = cell_state(x - 1, y);
+  let top = row_word(x, y - 1);
+  let right = cell_state(x + 32, y) << 31u;
+  let bottom = row_word(x, y + 1);
+  let top_left = cell_state(x - 1, y - 1);
+  let top_right = cell_state(x + 32, y - 1) << 31u;
+  let bottom_left = cell_state(x - 1, y + 1);
+  let bottom_right = cell_state(x + 32, y + 1) << 31u;
 
+  var result = 0u;
   for (var i = 0u; i < 32u; i++) {
     let mask = 1u << i;
     let left_mask = 1u << ((i + 1) % 32u);
@@ -57,19 +166,388 @@ fn update(
     count += u32((ternary(i == 0u, bottom_right, bottom) & right_mask) > 0);
 
 
-    if (count < 2 || count > 3) {
-      buffer[id.x] &= ~mask;
-    } else if count == 3 {
-      buffer[id.x] |= mask;
+    let rule_mask = ternary((me & mask) > 0, params.survival_mask, params.birth_mask);
+    if ((rule_mask >> count) & 1u) > 0 {
+      result |= mask;
+    } else {
+      result &= ~mask;
+    }
+  }
+
+  next[id.x] = result & cell_bits(id.x % params.buffer_size_x);
+}
+
+// Generations rules store 8 bits per cell, 4 cells per word with the leftmost cell in the
+// most significant byte. State 0 is dead, 1 is alive and the rest are dying states.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn update_generations(
+  @builtin(global_invocation_id) id: vec3<u32>,
+) {
+  if (id.x >= params.buffer_size_x * params.buffer_size_y) {
+    return;
+  }
+
+  let first_x = i32((id.x % params.buffer_size_x) * 4u);
+  let y = i32(id.x / params.buffer_size_x);
+
+  var result = 0u;
+  for (var i = 0; i < 4; i++) {
+    let x = first_x + i;
+    var count = 0u;
+    for (var dy = -1; dy <= 1; dy++) {
+      for (var dx = -1; dx <= 1; dx++) {
+        if (dx != 0 || dy != 0) {
+          count += u32(cell_state(x + dx, y + dy) == 1u);
+        }
+      }
+    }
+
+    let state = cell_state(x, y);
+    var next_state = 0u;
+    if (state == 0u) {
+      next_state = (params.birth_mask >> count) & 1u;
+    } else if (state == 1u && ((params.survival_mask >> count) & 1u) > 0) {
+      next_state = 1u;
+    } else {
+      next_state = (state + 1u) % params.state_count;
+    }
+
+    result |= next_state << ((3u - u32(i)) * 8u);
+  }
+
+  next[id.x] = result & cell_bits(id.x % params.buffer_size_x);
+}
+
+// Larger than Life counts live cells through summed-area tables of the tile and its apron,
+// kept in workgroup memory. Works on either cell layout.
+@compute @workgroup_size(LTL_TILE_X, LTL_TILE_Y)
+fn update_larger_than_life(
+  @builtin(workgroup_id) workgroup_id: vec3<u32>,
+  @builtin(local_invocation_id) local_id: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  let origin_x = i32(workgroup_id.x * LTL_TILE_X) - i32(MAX_RANGE);
+  let origin_y = i32(workgroup_id.y * LTL_TILE_Y) - i32(MAX_RANGE);
+
+  for (var i = local_index; i < SAT_X * SAT_Y; i += LTL_TILE_X * LTL_TILE_Y) {
+    let sat_x = i % SAT_X;
+    let sat_y = i / SAT_X;
+    var alive = 0u;
+    if (sat_x > 0u && sat_y > 0u) {
+      alive = u32(cell_state(origin_x + i32(sat_x) - 1, origin_y + i32(sat_y) - 1) == 1u);
+    }
+    row_sums[i] = alive;
+  }
+  if (local_index < LTL_TILE_WORDS) {
+    atomicStore(&tile_words[local_index], 0u);
+  }
+  workgroupBarrier();
+
+  if (local_index < SAT_Y) {
+    let row = local_index * SAT_X;
+    for (var x = 1u; x < SAT_X; x++) {
+      row_sums[row + x] += row_sums[row + x - 1u];
+    }
+  }
+  workgroupBarrier();
+
+  if (local_index < SAT_X) {
+    var sum = 0u;
+    for (var y = 0u; y < SAT_Y; y++) {
+      sum += row_sums[y * SAT_X + local_index];
+      box_sums[y * SAT_X + local_index] = sum;
+    }
+  }
+  workgroupBarrier();
+
+  // position of this invocation's cell in the prefix sums
+  let sat_x = local_id.x + MAX_RANGE + 1u;
+  let sat_y = local_id.y + MAX_RANGE + 1u;
+  let r = params.range;
+
+  var count = 0u;
+  if (params.von_neumann == 0u) {
+    let x0 = sat_x - r - 1u;
+    let x1 = sat_x + r;
+    let y0 = sat_y - r - 1u;
+    let y1 = sat_y + r;
+    count = box_sums[y1 * SAT_X + x1] + box_sums[y0 * SAT_X + x0]
+      - box_sums[y0 * SAT_X + x1] - box_sums[y1 * SAT_X + x0];
+  } else {
+    for (var dy = -i32(r); dy <= i32(r); dy++) {
+      let row = u32(i32(sat_y) + dy) * SAT_X;
+      let half_width = r - u32(abs(dy));
+      count += row_sums[row + sat_x + half_width] - row_sums[row + sat_x - half_width - 1u];
+    }
+  }
+
+  let x = i32(workgroup_id.x * LTL_TILE_X + local_id.x);
+  let y = i32(workgroup_id.y * LTL_TILE_Y + local_id.y);
+  let state = cell_state(x, y);
+  if (params.include_center == 0u) {
+    count -= u32(state == 1u);
+  }
+
+  var next_state = 0u;
+  if (state == 0u) {
+    next_state = u32(count >= params.birth_min && count <= params.birth_max);
+  } else if (state == 1u && count >= params.survival_min && count <= params.survival_max) {
+    next_state = 1u;
+  } else {
+    next_state = (state + 1u) % params.state_count;
+  }
+
+  let cells_per_word = 32u / params.bits_per_cell;
+  let shift = (cells_per_word - 1u - local_id.x % cells_per_word) * params.bits_per_cell;
+  let tile_word = local_id.y * 8u + local_id.x / cells_per_word;
+  atomicOr(&tile_words[tile_word], next_state << shift);
+  workgroupBarrier();
+
+  let inside = x < i32(params.cell_count_x) && y < i32(params.buffer_size_y);
+  if (inside && local_id.x % cells_per_word == 0u) {
+    let word_x = u32(x) / cells_per_word;
+    let word = atomicLoad(&tile_words[tile_word]) & cell_bits(word_x);
+    next[word_x + u32(y) * params.buffer_size_x] = word;
+  }
+}
+
+// Ages the cells of four bytes of the age buffer, bound like the update kernels that just
+// computed `next` from `current`. Cells born this generation are 1, dead ones 0.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn update_ages(
+  @builtin(global_invocation_id) id: vec3<u32>,
+) {
+  let cells_per_word = 32u / params.bits_per_cell;
+  let row_cells = params.buffer_size_x * cells_per_word;
+  if (id.x >= row_cells / 4u * params.buffer_size_y) {
+    return;
+  }
+
+  let first_x = (id.x * 4u) % row_cells;
+  let y = (id.x * 4u) / row_cells;
+  let old_ages = ages[id.x];
+  var word = 0u;
+  for (var i = 0u; i < 4u; i++) {
+    let x = first_x + i;
+    let index = x / cells_per_word + y * params.buffer_size_x;
+    let was_alive = packed_state(current[index], x) == 1u;
+    let alive = packed_state(next[index], x) == 1u;
+
+    let age = (old_ages >> (i * 8u)) & 0xFFu;
+    var next_age = 0u;
+    if (alive) {
+      next_age = ternary(was_alive, min(age + 1u, 255u), 1u);
     }
+    word |= next_age << (i * 8u);
   }
+  ages[id.x] = word;
 }
 
 @compute @workgroup_size(COMPUTE_WG_SIZE)
 fn randomize(
   @builtin(global_invocation_id) id: vec3<u32>,
 ) {
-  buffer[id.x] = random_u32(id);
+  if (id.x >= params.buffer_size_x * params.buffer_size_y) {
+    return;
+  }
+
+  // multi-state layouts start with live and dead cells only
+  let alive_mask = ternary(params.bits_per_cell == 8u, 0x01010101u, 0xFFFFFFFFu);
+  next[id.x] = random_u32(params.random_seed, id.x) & alive_mask & cell_bits(id.x % params.buffer_size_x);
+}
+
+// cells painted with the mouse, bound so that `next` is the buffer holding the latest
+// generation; every edit covers a different word
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn apply_edits(
+  @builtin(global_invocation_id) id: vec3<u32>,
+) {
+  if (id.x >= cell_edits.count) {
+    return;
+  }
+
+  let edit = cell_edits.edits[id.x];
+  next[edit.index] = (next[edit.index] & ~edit.mask) | edit.bits;
+}
+
+// Edits of a rectangle of the latest generation, bound like `apply_edits`. Every invocation
+// rewrites the cells of one word, so the whole universe is covered at once.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn edit_region(
+  @builtin(global_invocation_id) id: vec3<u32>,
+) {
+  if (id.x >= params.buffer_size_x * params.buffer_size_y) {
+    return;
+  }
+
+  let cells_per_word = 32u / params.bits_per_cell;
+  let state_mask = (1u << params.bits_per_cell) - 1u;
+  let first_x = (id.x % params.buffer_size_x) * cells_per_word;
+  let y = id.x / params.buffer_size_x;
+
+  var word = next[id.x];
+  for (var i = 0u; i < cells_per_word; i++) {
+    let x = first_x + i;
+    let shift = (cells_per_word - 1u - i) * params.bits_per_cell;
+    let inside = x >= region.x && x < region.x + region.width
+      && y >= region.y && y < region.y + region.height;
+
+    var state = (word >> shift) & state_mask;
+    switch region.kind {
+      case REGION_CLEAR, REGION_CUT: {
+        state = ternary(inside, 0u, state);
+      }
+      case REGION_CLEAR_OUTSIDE: {
+        state = ternary(inside, state, 0u);
+      }
+      case REGION_FILL: {
+        // the top 24 bits of the hash against the density scaled to 2^24
+        let alive = (random_u32(region.seed, x + y * params.cell_count_x) >> 8u) < region.threshold;
+        state = ternary(inside, u32(alive), state);
+      }
+      case REGION_PASTE: {
+        let source = vec2<i32>(i32(x), i32(y)) - vec2<i32>(region.target_x, region.target_y);
+        let pasted = all(source >= vec2<i32>(0))
+          && all(source < vec2<i32>(i32(region.width), i32(region.height)));
+        if (pasted) {
+          state = clipboard_cell_state(u32(source.x), u32(source.y));
+        }
+      }
+      default: {}
+    }
+    word = (word & ~(state_mask << shift)) | (state << shift);
+  }
+
+  next[id.x] = word & cell_bits(id.x % params.buffer_size_x);
+}
+
+// Packs the selected rectangle of the latest generation into the clipboard, one clipboard
+// word per invocation.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn copy_region(
+  @builtin(global_invocation_id) id: vec3<u32>,
+) {
+  let cells_per_word = 32u / params.bits_per_cell;
+  let state_mask = (1u << params.bits_per_cell) - 1u;
+  let row_words = (region.width + cells_per_word - 1u) / cells_per_word;
+  if (id.x >= row_words * region.height) {
+    return;
+  }
+
+  let first_x = (id.x % row_words) * cells_per_word;
+  let y = region.y + id.x / row_words;
+  var word = 0u;
+  for (var i = 0u; i < cells_per_word && first_x + i < region.width; i++) {
+    let x = region.x + first_x + i;
+    let source = next[x / cells_per_word + y * params.buffer_size_x];
+    let state = (source >> ((cells_per_word - 1u - x % cells_per_word) * params.bits_per_cell))
+      & state_mask;
+    word |= state << ((cells_per_word - 1u - i) * params.bits_per_cell);
+  }
+  clipboard[id.x] = word;
+}
+
+// Copies the cells of a universe that was resized from `current`, laid out `region.width`
+// cells by `region.height` rows, into `next`, moving them by `region.target_x` and
+// `region.target_y`. Cells that end up outside are dropped and new ones start dead.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn resize(
+  @builtin(global_invocation_id) id: vec3<u32>,
+) {
+  if (id.x >= params.buffer_size_x * params.buffer_size_y) {
+    return;
+  }
+
+  let cells_per_word = 32u / params.bits_per_cell;
+  let state_mask = (1u << params.bits_per_cell) - 1u;
+  let old_row_words = (region.width + cells_per_word - 1u) / cells_per_word;
+  let first_x = (id.x % params.buffer_size_x) * cells_per_word;
+  let y = i32(id.x / params.buffer_size_x) - region.target_y;
+
+  var word = 0u;
+  if (y >= 0 && y < i32(region.height)) {
+    for (var i = 0u; i < cells_per_word; i++) {
+      let x = i32(first_x + i) - region.target_x;
+      if (x < 0 || x >= i32(region.width)) {
+        continue;
+      }
+      let old_x = u32(x);
+      let source = current[old_x / cells_per_word + u32(y) * old_row_words];
+      let state = (source >> ((cells_per_word - 1u - old_x % cells_per_word) * params.bits_per_cell))
+        & state_mask;
+      word |= state << ((cells_per_word - 1u - i) * params.bits_per_cell);
+    }
+  }
+  next[id.x] = word & cell_bits(id.x % params.buffer_size_x);
+}
+
+// Counts the live cells of `current`, one word per invocation. The counts of a workgroup
+// are summed up in shared memory, so only one invocation per workgroup touches the atomic.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn count_population(
+  @builtin(global_invocation_id) id: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  var count = 0u;
+  if (id.x < params.buffer_size_x * params.buffer_size_y) {
+    let word = current[id.x];
+    if (params.bits_per_cell == 1u) {
+      count = countOneBits(word);
+    } else {
+      // dying states are not alive
+      for (var shift = 0u; shift < 32u; shift += params.bits_per_cell) {
+        count += u32(((word >> shift) & ((1u << params.bits_per_cell) - 1u)) == 1u);
+      }
+    }
+  }
+  population_sums[local_index] = count;
+  workgroupBarrier();
+
+  for (var stride = COMPUTE_WG_SIZE / 2u; stride > 0u; stride /= 2u) {
+    if (local_index < stride) {
+      population_sums[local_index] += population_sums[local_index + stride];
+    }
+    workgroupBarrier();
+  }
+
+  if (local_index == 0u) {
+    atomicAdd(&population, population_sums[0]);
+  }
+}
+
+// Hashes `current` into a 64-bit fingerprint, one word per invocation. Every word is
+// hashed together with its index into two independent 32-bit halves, which are summed up
+// like the population so the order of the workgroups does not matter. Empty words add
+// nothing, so an empty universe has a fingerprint of 0.
+@compute @workgroup_size(COMPUTE_WG_SIZE)
+fn hash_generation(
+  @builtin(global_invocation_id) id: vec3<u32>,
+  @builtin(local_invocation_index) local_index: u32,
+) {
+  var hash = vec2<u32>(0u);
+  if (id.x < params.buffer_size_x * params.buffer_size_y) {
+    let word = current[id.x];
+    if (word != 0u) {
+      hash = vec2<u32>(
+        random_u32(word, random_u32(0x9E3779B9u, id.x)),
+        random_u32(~word, random_u32(0x85EBCA6Bu, ~id.x)),
+      );
+    }
+  }
+  fingerprint_sums[local_index] = hash;
+  workgroupBarrier();
+
+  for (var stride = COMPUTE_WG_SIZE / 2u; stride > 0u; stride /= 2u) {
+    if (local_index < stride) {
+      fingerprint_sums[local_index] += fingerprint_sums[local_index + stride];
+    }
+    workgroupBarrier();
+  }
+
+  if (local_index == 0u) {
+    atomicAdd(&fingerprint[0], fingerprint_sums[0].x);
+    atomicAdd(&fingerprint[1], fingerprint_sums[0].y);
+  }
 }
 
 @compute @workgroup_size(DISPLAY_WG_SIZE, DISPLAY_WG_SIZE)
@@ -86,21 +564,51 @@ fn display(
   let adjusted_y = params.center_y + (f32(id.y) - f32(params.resolution_y) * 0.5) / params.zoom;
   let outside_bounds = adjusted_x < 0.0 
     || adjusted_y < 0.0 
-    || adjusted_x >= 32.0 * f32(params.buffer_size_x) 
+    || adjusted_x >= f32(params.cell_count_x) 
     || adjusted_y >= f32(params.buffer_size_y);
+  let wrapping = params.topology != TOPOLOGY_DEAD_EDGES
+    && params.topology != TOPOLOGY_ALIVE_EDGES;
+  let tiled = wrapping
+    && params.tile_display != 0u
+    && wrap(vec2<i32>(floor(vec2<f32>(adjusted_x, adjusted_y)))).z != 0;
 
-  let id_x = u32(adjusted_x) / 32;
-  let id_y = u32(adjusted_y);
-  let offset = u32(adjusted_x) % 32;
-  let mask = 1u << (31u - offset);
-  let cell_alive = (buffer[id_x + id_y * params.buffer_size_x] & mask) > 0;
+  let state = cell_state(i32(floor(adjusted_x)), i32(floor(adjusted_y)));
 
   var color = vec4<f32>(0.0, 0.0, 0.0, 1.0);
 
-  if (outside_bounds) {
+  if (outside_bounds && !tiled) {
     color = vec4<f32>(1.0, 0.0, 0.0, 1.0);
-  } else if (cell_alive) {
+  } else if (ages_tracked() && state <= 1u) {
+    let age = average_age(vec2<f32>(adjusted_x, adjusted_y));
+    if (age > 0.0) {
+      color = vec4<f32>(age_color(age), 1.0);
+    }
+  } else if (state == 1u) {
     color = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+  } else if (state > 1u) {
+    // dying states fade from orange to dark purple
+    let t = f32(state - 1u) / f32(params.state_count - 1u);
+    color = vec4<f32>(mix(vec3<f32>(1.0, 0.6, 0.1), vec3<f32>(0.2, 0.0, 0.5), t), 1.0);
+  }
+
+  let cell = vec2<i32>(floor(vec2<f32>(adjusted_x, adjusted_y)));
+  let selection_min = vec2<i32>(i32(params.selection_x), i32(params.selection_y));
+  let selection_size = vec2<i32>(i32(params.selection_width), i32(params.selection_height));
+  if (all(cell >= selection_min) && all(cell < selection_min + selection_size)) {
+    color = mix(color, SELECTION_COLOR, 0.25);
+  }
+
+  // the stamp preview is translucent, dead cells are tinted only where they replace
+  let ghost_cell = cell - vec2<i32>(params.ghost_x, params.ghost_y);
+  let in_ghost = all(ghost_cell >= vec2<i32>(0))
+    && all(ghost_cell < vec2<i32>(i32(params.ghost_width), i32(params.ghost_height)));
+  if (in_ghost) {
+    let ghost_state = ghost_cell_state(u32(ghost_cell.x), u32(ghost_cell.y));
+    if (ghost_state != 0u) {
+      color = mix(color, GHOST_COLOR, 0.6);
+    } else if (params.ghost_replace != 0u) {
+      color = mix(color, GHOST_COLOR, 0.15);
+    }
   }
 
   let location = vec2<i32>(i32(id.x), i32(id.y));
@@ -115,8 +623,190 @@ fn ternary(cond: bool, a: u32, b: u32) -> u32 {
   }
 }
 
-fn random_u32(id: vec3<u32>) -> u32 {
-  var input = params.random_seed + id.x;
+// Maps a cell position onto the cell it is glued to under the current topology. The z
+// component is 0 when the position lands on no cell, e.g. past a dead or alive edge.
+fn wrap(position: vec2<i32>) -> vec3<i32> {
+  let size = vec2<i32>(i32(params.cell_count_x), i32(params.buffer_size_y));
+  var p = position;
+  // how many times the position crossed each pair of edges, negative for left and top
+  let crossings = vec2<i32>(floor(vec2<f32>(p) / vec2<f32>(size)));
+
+  switch params.topology {
+    case TOPOLOGY_TORUS: {}
+    case TOPOLOGY_KLEIN_HORIZONTAL: {
+      // the top and bottom edges are glued with a twist
+      if ((crossings.y & 1) != 0) {
+        p.x = size.x - 1 - p.x;
+      }
+    }
+    case TOPOLOGY_KLEIN_VERTICAL: {
+      if ((crossings.x & 1) != 0) {
+        p.y = size.y - 1 - p.y;
+      }
+    }
+    case TOPOLOGY_CROSS_SURFACE: {
+      if ((crossings.y & 1) != 0) {
+        p.x = size.x - 1 - p.x;
+      }
+      if ((crossings.x & 1) != 0) {
+        p.y = size.y - 1 - p.y;
+      }
+    }
+    case TOPOLOGY_SPHERE: {
+      // the top edge is glued to the left edge and the bottom edge to the right one, which
+      // only lines up for square universes
+      for (var i = 0; i < 2; i++) {
+        if (p.y < 0) {
+          p = vec2<i32>(-p.y - 1, p.x);
+        } else if (p.x < 0) {
+          p = vec2<i32>(p.y, -p.x - 1);
+        } else if (p.y >= size.y) {
+          p = vec2<i32>(size.x + size.y - 1 - p.y, p.x);
+        } else if (p.x >= size.x) {
+          p = vec2<i32>(p.y, size.x + size.y - 1 - p.x);
+        }
+      }
+      let inside = all(p >= vec2<i32>(0)) && all(p < size);
+      return vec3<i32>(p, i32(inside));
+    }
+    default: {
+      return vec3<i32>(p, i32(all(crossings == vec2<i32>(0))));
+    }
+  }
+
+  return vec3<i32>(((p % size) + size) % size, 1);
+}
+
+// state of a cell in either layout, following the topology for cells outside the grid
+fn cell_state(x: i32, y: i32) -> u32 {
+  let wrapped = wrap(vec2<i32>(x, y));
+  if (wrapped.z == 0) {
+    return u32(params.topology == TOPOLOGY_ALIVE_EDGES);
+  }
+
+  let cells_per_word = 32u / params.bits_per_cell;
+  let word = current[u32(wrapped.x) / cells_per_word + u32(wrapped.y) * params.buffer_size_x];
+  let shift = (cells_per_word - 1u - u32(wrapped.x) % cells_per_word) * params.bits_per_cell;
+  return (word >> shift) & ((1u << params.bits_per_cell) - 1u);
+}
+
+// state of the cell in column x of a word of the universe, in either layout
+fn packed_state(word: u32, x: u32) -> u32 {
+  let cells_per_word = 32u / params.bits_per_cell;
+  let shift = (cells_per_word - 1u - x % cells_per_word) * params.bits_per_cell;
+  return (word >> shift) & ((1u << params.bits_per_cell) - 1u);
+}
+
+// whether a coloring asks for the ages and they could be allocated
+fn ages_tracked() -> bool {
+  let cells = params.buffer_size_x * (32u / params.bits_per_cell) * params.buffer_size_y;
+  return params.coloring != COLORING_PLAIN && arrayLength(&ages) * 4u >= cells;
+}
+
+// age of a cell inside the universe
+fn cell_age(x: u32, y: u32) -> u32 {
+  let index = x + y * params.buffer_size_x * (32u / params.bits_per_cell);
+  return (ages[index / 4u] >> ((index % 4u) * 8u)) & 0xFFu;
+}
+
+// The mean age of the live cells a pixel at `position` covers, 0 if there are none. Cells
+// that became alive by an edit or a new soup count as newly born until they age.
+fn average_age(position: vec2<f32>) -> f32 {
+  let span = max(1.0 / params.zoom, 1.0);
+  let samples = min(u32(ceil(span)), MAX_AVERAGED_CELLS);
+  let stride = span / f32(samples);
+
+  var sum = 0u;
+  var live = 0u;
+  for (var sy = 0u; sy < samples; sy++) {
+    for (var sx = 0u; sx < samples; sx++) {
+      let cell = vec2<i32>(floor(position + vec2<f32>(f32(sx), f32(sy)) * stride));
+      let wrapped = wrap(cell);
+      if (wrapped.z == 0 || cell_state(wrapped.x, wrapped.y) != 1u) {
+        continue;
+      }
+      live += 1u;
+      sum += max(cell_age(u32(wrapped.x), u32(wrapped.y)), 1u);
+    }
+  }
+  if (live == 0u) {
+    return 0.0;
+  }
+  return f32(sum) / f32(live);
+}
+
+// the color of the selected gradient for an age of 1 to 255, on a log scale so the first
+// generations of a cell stand out
+fn age_color(age: f32) -> vec3<f32> {
+  let t = clamp(log2(age) / 8.0, 0.0, 1.0);
+  switch params.coloring {
+    case COLORING_VIRIDIS: {
+      return gradient(vec3<f32>(0.99, 0.91, 0.14), vec3<f32>(0.13, 0.57, 0.55), vec3<f32>(0.27, 0.0, 0.33), t);
+    }
+    case COLORING_ICE: {
+      return gradient(vec3<f32>(1.0, 1.0, 1.0), vec3<f32>(0.2, 0.8, 1.0), vec3<f32>(0.0, 0.1, 0.5), t);
+    }
+    case COLORING_HEAT, default: {
+      return gradient(vec3<f32>(1.0, 1.0, 0.6), vec3<f32>(1.0, 0.5, 0.0), vec3<f32>(0.5, 0.0, 0.0), t);
+    }
+  }
+}
+
+fn gradient(start: vec3<f32>, middle: vec3<f32>, end: vec3<f32>, t: f32) -> vec3<f32> {
+  if (t < 0.5) {
+    return mix(start, middle, t * 2.0);
+  }
+  return mix(middle, end, t * 2.0 - 1.0);
+}
+
+// state of a cell of the stamp preview, packed in rows of whole words like the universe
+fn ghost_cell_state(x: u32, y: u32) -> u32 {
+  let cells_per_word = 32u / params.bits_per_cell;
+  let row_words = (params.ghost_width + cells_per_word - 1u) / cells_per_word;
+  let word = ghost[x / cells_per_word + y * row_words];
+  let shift = (cells_per_word - 1u - x % cells_per_word) * params.bits_per_cell;
+  return (word >> shift) & ((1u << params.bits_per_cell) - 1u);
+}
+
+// state of a clipboard cell, packed like the stamp preview
+fn clipboard_cell_state(x: u32, y: u32) -> u32 {
+  let cells_per_word = 32u / params.bits_per_cell;
+  let row_words = (region.width + cells_per_word - 1u) / cells_per_word;
+  let word = clipboard[x / cells_per_word + y * row_words];
+  let shift = (cells_per_word - 1u - x % cells_per_word) * params.bits_per_cell;
+  return (word >> shift) & ((1u << params.bits_per_cell) - 1u);
+}
+
+// the 32 cells of the 1-bit layout starting at column x, reading straight from the buffer
+// unless the word reaches past the edges of the universe
+fn row_word(x: i32, y: i32) -> u32 {
+  if (y >= 0 && y < i32(params.buffer_size_y) && x + 31 < i32(params.cell_count_x)) {
+    return current[u32(x) / 32u + u32(y) * params.buffer_size_x];
+  }
+
+  var word = 0u;
+  for (var i = 0; i < 32; i++) {
+    word |= cell_state(x + i, y) << u32(31 - i);
+  }
+  return word;
+}
+
+// bits of a word in the given buffer column that hold cells inside the logical width, so
+// the padding past `cell_count_x` stays dead
+fn cell_bits(word_x: u32) -> u32 {
+  let cells_per_word = 32u / params.bits_per_cell;
+  let first_cell = word_x * cells_per_word;
+  if (first_cell + cells_per_word <= params.cell_count_x) {
+    return 0xFFFFFFFFu;
+  }
+
+  // cells are stored from the most significant bit down
+  let padding_bits = (first_cell + cells_per_word - params.cell_count_x) * params.bits_per_cell;
+  return ~((1u << padding_bits) - 1u);
+}
+
+fn random_u32(seed: u32, index: u32) -> u32 {
+  var input = seed + index;
   input ^= 2747636419u;
   input *= 2654435769u;
   input ^= (input >> 16u);
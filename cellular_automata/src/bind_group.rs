@@ -37,7 +37,7 @@ pub fn prepare_bind_group(
 
     let values_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
       label: None,
-      contents: bytemuck::cast_slice(&*ca_values.0),
+      contents: bytemuck::cast_slice(&ca_values.0),
       usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
     });
 
@@ -9,12 +9,21 @@ use bytemuck::{Pod, Zeroable};
 #[derive(Resource, ExtractResource, Clone)]
 pub struct MainImage(pub Handle<Image>);
 
-#[repr(C)]
-#[derive(Resource, Clone, Copy, ShaderType, Pod, Zeroable, ExtractResource)]
-pub struct Params {
-  pub cell_size: u32,
-  pub count_x: u32,
-  pub count_y: u32,
+pub use params::Params;
+
+// the `ShaderType` derive emits per-field `check` functions that are never called, and an
+// allow only reaches them from an enclosing module
+#[allow(dead_code)]
+mod params {
+  use super::*;
+
+  #[repr(C)]
+  #[derive(Resource, Clone, Copy, ShaderType, Pod, Zeroable, ExtractResource)]
+  pub struct Params {
+    pub cell_size: u32,
+    pub count_x: u32,
+    pub count_y: u32,
+  }
 }
 
 #[repr(C)]
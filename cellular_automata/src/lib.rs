@@ -6,16 +6,17 @@ use bind_group::prepare_bind_group;
 use data_structs::{CellSize, CellValues, MainImage, Params, Resolution};
 
 use bevy::{
-  app::{Plugin, Startup},
+  app::{Plugin, Startup, Update},
   asset::{Assets, RenderAssetUsages},
   core_pipeline::core_2d::Camera2d,
   ecs::{
+    event::EventReader,
     schedule::IntoScheduleConfigs,
-    system::{Commands, Query, Res, ResMut, Single},
+    system::{Commands, Res, ResMut, Single},
   },
   image::Image,
+  input::{ButtonInput, keyboard::KeyCode},
   log::info,
-  math::Vec2,
   render::{
     Render, RenderApp, RenderSet,
     extract_resource::ExtractResourcePlugin,
@@ -24,7 +25,7 @@ use bevy::{
   },
   sprite::Sprite,
   utils::default,
-  window::{Monitor, Window},
+  window::{MonitorSelection, Window, WindowMode, WindowResized, WindowScaleFactorChanged},
 };
 use pipeline::CAPipeline;
 use render_graph::{CANode, CANodeLabel};
@@ -37,6 +38,7 @@ impl Plugin for CellularAutomataPlugin {
 
     app.insert_resource(CellSize(self.0));
     app.add_systems(Startup, setup);
+    app.add_systems(Update, (handle_window_resize, toggle_fullscreen));
     app.world_mut().commands().spawn(Camera2d);
 
     app.add_plugins(ExtractResourcePlugin::<Params>::default());
@@ -54,7 +56,7 @@ impl Plugin for CellularAutomataPlugin {
 
     info!("Preparing render graph node");
     let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
-    render_graph.add_node(CANodeLabel, CANode::default());
+    render_graph.add_node(CANodeLabel, CANode);
     render_graph.add_node_edge(CANodeLabel, bevy::render::graph::CameraDriverLabel);
 
     info!("Building pipeline done");
@@ -86,10 +88,23 @@ fn setup(
   // }
   let window_width = window.physical_width();
   let window_height = window.physical_height();
+  insert_grid(&mut commands, square_size.0, window_width, window_height);
 
-  let cell_size = square_size.0;
-  let count_x = (window_width + cell_size - 1) / cell_size;
-  let count_y = (window_height + cell_size - 1) / cell_size;
+  let image_handle = image_assets.add(display_image(window_width, window_height));
+
+  // the texture has a texel per physical pixel, stretched over the window's logical size
+  commands.spawn((Sprite {
+    image: image_handle.clone(),
+    custom_size: Some(window.size()),
+    ..default()
+  },));
+  commands.insert_resource(MainImage(image_handle));
+}
+
+/// The cells covering a window of `width` by `height` physical pixels, all of them empty.
+fn insert_grid(commands: &mut Commands, cell_size: u32, width: u32, height: u32) {
+  let count_x = width.div_ceil(cell_size);
+  let count_y = height.div_ceil(cell_size);
 
   commands.insert_resource(Params {
     cell_size,
@@ -97,13 +112,15 @@ fn setup(
     count_y,
   });
 
-  commands.insert_resource(Resolution(window_width, window_height));
+  commands.insert_resource(Resolution(width, height));
   commands.insert_resource(CellValues(vec![0; (count_x * count_y) as usize]));
+}
 
+fn display_image(width: u32, height: u32) -> Image {
   let mut image = Image::new_fill(
     Extent3d {
-      width: window_width as u32,
-      height: window_height as u32,
+      width,
+      height,
       depth_or_array_layers: 1,
     },
     TextureDimension::D2,
@@ -113,16 +130,44 @@ fn setup(
   );
   image.texture_descriptor.usage =
     TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+  image
+}
+
+/// Reallocates the display texture and the cells for the new physical size of the window.
+/// The bind group is prepared every frame, so it picks both up on its own.
+fn handle_window_resize(
+  mut commands: Commands,
+  window: Single<&Window>,
+  mut resized: EventReader<WindowResized>,
+  mut rescaled: EventReader<WindowScaleFactorChanged>,
+  resolution: Res<Resolution>,
+  cell_size: Res<CellSize>,
+  mut image_assets: ResMut<Assets<Image>>,
+  mut sprite: Single<&mut Sprite>,
+) {
+  if resized.read().count() + rescaled.read().count() == 0 {
+    return;
+  }
+  // a new scale factor changes the logical size without touching the physical one
+  sprite.custom_size = Some(window.size());
+  let (width, height) = (window.physical_width(), window.physical_height());
+  // a minimized window has no pixels to draw into
+  if (width, height) == (resolution.0, resolution.1) || width == 0 || height == 0 {
+    return;
+  }
 
-  let image_handle = image_assets.add(image.clone());
+  info!("Resizing the display to {width}x{height}");
+  insert_grid(&mut commands, cell_size.0, width, height);
+  let image_handle = image_assets.add(display_image(width, height));
+  commands.insert_resource(MainImage(image_handle.clone()));
+  sprite.image = image_handle;
+}
 
-  commands.spawn((Sprite {
-    image: image_handle.clone(),
-    custom_size: Some(Vec2 {
-      x: window_width as f32,
-      y: window_height as f32,
-    }),
-    ..default()
-  },));
-  commands.insert_resource(MainImage(image_handle));
+fn toggle_fullscreen(keys: Res<ButtonInput<KeyCode>>, mut window: Single<&mut Window>) {
+  if keys.just_pressed(KeyCode::F11) {
+    window.mode = match window.mode {
+      WindowMode::Windowed => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+      _ => WindowMode::Windowed,
+    };
+  }
 }
@@ -41,8 +41,8 @@ impl render_graph::Node for CANode {
       pass.set_pipeline(compute_pipeline);
 
       if let Some(params) = world.get_resource::<Params>() {
-        let wg_x = (params.count_x + WORKGROUP_SIZE - 1) / (WORKGROUP_SIZE);
-        let wg_y = (params.count_y + WORKGROUP_SIZE - 1) / (WORKGROUP_SIZE);
+        let wg_x = params.count_x.div_ceil(WORKGROUP_SIZE);
+        let wg_y = params.count_y.div_ceil(WORKGROUP_SIZE);
         pass.dispatch_workgroups(wg_x, wg_y, 1);
       }
     }
@@ -56,8 +56,8 @@ impl render_graph::Node for CANode {
       pass.set_pipeline(display_pipeline);
 
       if let Some(res) = world.get_resource::<Resolution>() {
-        let wg_x = (res.0 + WORKGROUP_SIZE - 1) / (WORKGROUP_SIZE);
-        let wg_y = (res.1 + WORKGROUP_SIZE - 1) / (WORKGROUP_SIZE);
+        let wg_x = res.0.div_ceil(WORKGROUP_SIZE);
+        let wg_y = res.1.div_ceil(WORKGROUP_SIZE);
         pass.dispatch_workgroups(wg_x, wg_y, 1);
       }
     }
@@ -1,24 +1,137 @@
 use bevy::{
   ecs::{
     resource::Resource,
-    system::{Commands, Res},
+    system::{Commands, Res, ResMut},
   },
+  log::warn,
   render::{
     render_asset::RenderAssets,
-    render_resource::{BindGroup, BindGroupEntries, BufferInitDescriptor, BufferUsages},
+    render_resource::{
+      BindGroup, BindGroupEntries, Buffer, BufferDescriptor, BufferInitDescriptor, BufferUsages,
+      TextureViewId,
+    },
     renderer::{RenderDevice, RenderQueue},
     texture::GpuImage,
   },
 };
 
 use crate::{
-  data_structs::{GpuParamsHandle, MainImage, Params},
+  data_structs::{ComputeState, GpuParamsHandle, MainImage, Params},
+  paint::GLEdits,
   pipeline::GLPipeline,
+  population::GLPopulation,
+  render_graph::COMPUTE_WG_SIZE,
+  resize::PendingResize,
+  selection::{GLRegion, RegionOp},
+  stamp::GLGhost,
 };
 
+/// Two bind groups over the same pair of storage buffers with the roles of
+/// `current` and `next` swapped, so every generation reads only the previous one.
 #[derive(Resource)]
-pub struct GLBindGroup(pub BindGroup);
+pub struct GLBindGroup(pub [BindGroup; 2]);
 
+/// The display texture bound by `GLBindGroup`, which is replaced when the window is resized.
+#[derive(Resource)]
+pub struct GLBoundImage(pub TextureViewId);
+
+/// The generation storage buffers behind `GLBindGroup`.
+#[derive(Resource)]
+pub struct GLBuffers(pub [Buffer; 2]);
+
+/// Bind groups for the `resize` kernel, binding the storage buffers of the previous size as
+/// `current` and the new ones as `next`, until the node has copied the cells over.
+#[derive(Resource)]
+pub struct GLResize {
+  pub bind_groups: [BindGroup; 2],
+  /// Whether the node dispatched the copy, set in the frame it does.
+  pub copied: bool,
+}
+
+/// How many generations every cell has been alive for, 8 bits per cell saturating at 255,
+/// in rows as wide as the padded rows of the universe. Only allocated while a coloring
+/// needs them, a placeholder is bound otherwise.
+#[derive(Resource)]
+pub struct GLAges {
+  pub buffer: Buffer,
+  pub tracked: bool,
+  /// Workgroups of the `update_ages` dispatch, one invocation per word of four ages.
+  pub workgroups: u32,
+}
+
+impl GLAges {
+  fn new(device: &RenderDevice, params: &Params) -> Self {
+    let bytes =
+      u64::from(params.buffer_size_x * params.cells_per_word()) * u64::from(params.buffer_size_y);
+    let workgroups = (bytes / 4).div_ceil(u64::from(COMPUTE_WG_SIZE));
+    let limits = device.limits();
+    let max_bytes = u64::from(limits.max_storage_buffer_binding_size);
+    let max_workgroups = u64::from(limits.max_compute_workgroups_per_dimension);
+    let tracked = params.coloring != 0 && bytes <= max_bytes && workgroups <= max_workgroups;
+    if params.coloring != 0 && bytes > max_bytes {
+      warn!(
+        "Cell ages take {bytes} bytes, more than the {max_bytes} bytes of \
+         max_storage_buffer_binding_size, so cells are not colored by age"
+      );
+    } else if params.coloring != 0 && !tracked {
+      warn!(
+        "Aging the cells takes {workgroups} workgroups, more than the {max_workgroups} of \
+         max_compute_workgroups_per_dimension, so cells are not colored by age"
+      );
+    }
+    let buffer = device.create_buffer(&BufferDescriptor {
+      label: Some("ages"),
+      size: if tracked { bytes } else { 4 },
+      usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+      mapped_at_creation: false,
+    });
+    Self {
+      buffer,
+      tracked,
+      workgroups: if tracked { workgroups as u32 } else { 0 },
+    }
+  }
+}
+
+/// The `Params` cell layout the generation storage buffers were allocated for.
+#[derive(Resource)]
+pub struct GLBufferLayout {
+  pub buffer_size_x: u32,
+  pub buffer_size_y: u32,
+  pub bits_per_cell: u32,
+  pub cell_count_x: u32,
+  /// Whether the ages were asked for, even if they did not fit.
+  pub ages: bool,
+}
+
+/// Whether the storage buffers are missing or no longer match the cell layout in `Params`,
+/// e.g. after switching between a Life-like and a Generations rule or resizing the universe,
+/// or the bind groups still bind a display texture that was replaced or miss the ages a
+/// coloring asks for.
+pub fn bind_group_outdated(
+  params: Res<Params>,
+  layout: Option<Res<GLBufferLayout>>,
+  main_image: Res<MainImage>,
+  gpu_images: Res<RenderAssets<GpuImage>>,
+  bound: Option<Res<GLBoundImage>>,
+) -> bool {
+  let image_replaced = gpu_images
+    .get(&main_image.0)
+    .is_some_and(|image| bound.is_none_or(|bound| bound.0 != image.texture_view.id()));
+  layout.is_none_or(|layout| !layout.matches(&params) || layout.ages != (params.coloring != 0))
+    || image_replaced
+}
+
+impl GLBufferLayout {
+  pub fn matches(&self, params: &Params) -> bool {
+    self.buffer_size_x == params.buffer_size_x
+      && self.buffer_size_y == params.buffer_size_y
+      && self.bits_per_cell == params.bits_per_cell
+      && self.cell_count_x == params.cell_count_x
+  }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn prepare_bind_group(
   mut commands: Commands,
   pipeline: Res<GLPipeline>,
@@ -26,35 +139,129 @@ pub fn prepare_bind_group(
   device: Res<RenderDevice>,
   params: Res<Params>,
   main_image: Res<MainImage>,
+  edits: Res<GLEdits>,
+  ghost: Res<GLGhost>,
+  region: Res<GLRegion>,
+  population: Res<GLPopulation>,
+  old_buffers: Option<Res<GLBuffers>>,
+  old_layout: Option<Res<GLBufferLayout>>,
+  old_params: Option<Res<GpuParamsHandle>>,
+  old_ages: Option<Res<GLAges>>,
+  state: Option<Res<ComputeState>>,
+  mut resize: ResMut<PendingResize>,
 ) {
   if let Some(main_image) = gpu_images.get(&main_image.0) {
-    let params_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
-      label: None,
-      contents: bytemuck::bytes_of(&*params),
-      usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
-    });
+    // a new display texture or coloring alone, e.g. after the window was resized, keeps the
+    // universe
+    let retained = old_layout
+      .as_ref()
+      .is_some_and(|layout| layout.matches(&params));
+    let ages_retained = retained
+      && old_layout
+        .as_ref()
+        .is_some_and(|layout| layout.ages == (params.coloring != 0));
+    let ages = match &old_ages {
+      Some(old_ages) if ages_retained => GLAges {
+        buffer: old_ages.buffer.clone(),
+        tracked: old_ages.tracked,
+        workgroups: old_ages.workgroups,
+      },
+      _ => GLAges::new(&device, &params),
+    };
+    let (params_buffer, buffers) = match (&old_params, &old_buffers) {
+      (Some(old_params), Some(old_buffers)) if retained => {
+        (old_params.0.clone(), old_buffers.0.clone())
+      }
+      _ => {
+        let params_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
+          label: None,
+          contents: bytemuck::bytes_of(&*params),
+          usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+        });
 
-    let buffer_size = params.buffer_size_x * params.buffer_size_y;
-    let data_buffer = vec![0u32; buffer_size as usize];
+        let buffer_size = params.buffer_size_x * params.buffer_size_y;
+        let data_buffer = vec![0u32; buffer_size as usize];
 
-    let buffer = device.create_buffer_with_data(&BufferInitDescriptor {
-      label: None,
-      contents: bytemuck::cast_slice(&data_buffer.clone()),
-      usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
-    });
+        let buffers = [0, 1].map(|_| {
+          device.create_buffer_with_data(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&data_buffer),
+            usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+          })
+        });
+        (params_buffer, buffers)
+      }
+    };
+
+    let region_clipboard = &region.clipboard;
+    let create_bind_groups = |current: &[Buffer; 2], next: &[Buffer; 2], region: &Buffer| {
+      [0, 1].map(|i| {
+        device.create_bind_group(
+          None,
+          &pipeline.layout,
+          &BindGroupEntries::sequential((
+            params_buffer.as_entire_binding(),
+            &main_image.texture_view,
+            current[i].as_entire_binding(),
+            next[1 - i].as_entire_binding(),
+            edits.buffer.as_entire_binding(),
+            ghost.buffer.as_entire_binding(),
+            region.as_entire_binding(),
+            region_clipboard.as_entire_binding(),
+            population.counter.as_entire_binding(),
+            population.fingerprint.as_entire_binding(),
+            ages.buffer.as_entire_binding(),
+          )),
+        )
+      })
+    };
+    let bind_groups = create_bind_groups(&buffers, &buffers, &region.uniform);
+    commands.insert_resource(GLBindGroup(bind_groups));
+    commands.insert_resource(GLBoundImage(main_image.texture_view.id()));
 
-    let bind_group = device.create_bind_group(
-      None,
-      &pipeline.layout,
-      &BindGroupEntries::sequential((
-        params_buffer.as_entire_binding(),
-        &main_image.texture_view,
-        buffer.as_entire_binding(),
-      )),
+    // a universe that only changed size keeps its cells, which the node copies over from
+    // the latest generation once it finished the step it is in
+    let offset = resize.0.take().filter(|_| !retained);
+    let resizable = matches!(
+      state.as_deref(),
+      Some(ComputeState::STEP | ComputeState::WAIT | ComputeState::REWIND | ComputeState::RESIZE)
     );
+    if let (Some(offset), Some(old_buffers), Some(old_layout), true) =
+      (offset, old_buffers, old_layout, resizable)
+      && old_layout.bits_per_cell == params.bits_per_cell
+    {
+      let op = RegionOp {
+        width: old_layout.cell_count_x,
+        height: old_layout.buffer_size_y,
+        target_x: offset.x,
+        target_y: offset.y,
+        ..Default::default()
+      };
+      let uniform = device.create_buffer_with_data(&BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::bytes_of(&op),
+        usage: BufferUsages::UNIFORM,
+      });
+      commands.insert_resource(GLResize {
+        bind_groups: create_bind_groups(&old_buffers.0, &buffers, &uniform),
+        copied: false,
+      });
+    } else if !retained {
+      // fresh buffers are empty, so start over with a random soup in the new layout
+      commands.remove_resource::<GLResize>();
+      commands.insert_resource(ComputeState::INITIAL);
+    }
 
-    commands.insert_resource(GLBindGroup(bind_group));
+    commands.insert_resource(GLBuffers(buffers));
+    commands.insert_resource(GLBufferLayout {
+      buffer_size_x: params.buffer_size_x,
+      buffer_size_y: params.buffer_size_y,
+      bits_per_cell: params.bits_per_cell,
+      cell_count_x: params.cell_count_x,
+      ages: params.coloring != 0,
+    });
     commands.insert_resource(GpuParamsHandle(params_buffer));
+    commands.insert_resource(ages);
   }
 }
 
@@ -0,0 +1,483 @@
+use std::{
+  collections::BTreeMap,
+  fmt::{self, Write as _},
+  fs,
+  ops::Range,
+  path::PathBuf,
+};
+
+use bevy::{
+  app::AppExit,
+  ecs::{event::EventWriter, resource::Resource, system::Res},
+  log::{error, info},
+  math::{IVec2, URect, UVec2},
+};
+
+use crate::{cpu::CpuUniverse, data_structs::Topology, pattern::Pattern, rule::Rule};
+
+/// Longest period an object or the population of a soup is checked for.
+pub const MAX_PERIOD: u32 = 64;
+
+/// Generations the population has to repeat over before a soup counts as settled.
+const SETTLE_SPAN: usize = 3 * MAX_PERIOD as usize;
+
+/// Characters of the extended Wechsler format, a column of a strip or the length of a run
+/// of empty columns.
+const WECHSLER_DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// A batch job in the spirit of apgsearch: random soups are run on `CpuUniverse` until
+/// their population repeats, then split into objects which are classified on their own and
+/// counted under apgcode-like names in `report`.
+#[derive(Resource, Clone, Debug)]
+pub struct CensusRun {
+  /// Seeds of the soups, each filled in by the `randomize` pass for its seed.
+  pub seeds: Range<u32>,
+  /// Side of the square soup in the middle of the universe, in cells.
+  pub soup_size: u32,
+  /// Size of the universe in cells. Its edges are dead, so spaceships reaching them before
+  /// the soup settles crash into them.
+  pub size: UVec2,
+  /// Soups still changing after this many generations are left out of the census.
+  pub max_generations: u64,
+  pub report: PathBuf,
+}
+
+impl Default for CensusRun {
+  fn default() -> Self {
+    Self {
+      seeds: 0..100,
+      soup_size: 16,
+      size: UVec2::splat(256),
+      max_generations: 10000,
+      report: "census.csv".into(),
+    }
+  }
+}
+
+/// What an object turned out to be when run on its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ObjectKind {
+  StillLife,
+  Oscillator {
+    period: u32,
+  },
+  /// Moves `dx` and `dy` cells every `period` generations, with `dx >= dy` since objects
+  /// are counted in every orientation.
+  Spaceship {
+    period: u32,
+    dx: u32,
+    dy: u32,
+  },
+  /// Did not repeat within `MAX_PERIOD` generations.
+  Unknown,
+}
+
+impl fmt::Display for ObjectKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match *self {
+      ObjectKind::StillLife => write!(f, "still life"),
+      ObjectKind::Oscillator { period } => write!(f, "oscillator p{period}"),
+      ObjectKind::Spaceship { period, dx, dy } => {
+        let direction = match (dx, dy) {
+          (_, 0) => "orthogonal",
+          _ if dx == dy => "diagonal",
+          _ => "oblique",
+        };
+        if direction == "oblique" {
+          return write!(f, "spaceship ({dx},{dy})c/{period} {direction}");
+        }
+        // c/4 rather than 2c/8
+        let divisor = gcd(dx, period);
+        match (dx / divisor, period / divisor) {
+          (1, period) => write!(f, "spaceship c/{period} {direction}"),
+          (cells, period) => write!(f, "spaceship {cells}c/{period} {direction}"),
+        }
+      }
+      ObjectKind::Unknown => write!(f, "unknown"),
+    }
+  }
+}
+
+/// A classified object with its canonical name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CensusObject {
+  pub apgcode: String,
+  pub kind: ObjectKind,
+}
+
+/// Runs `pattern` on its own under `rule` until it repeats, and names it after the
+/// smallest extended Wechsler encoding of its phases in any orientation, prefixed with
+/// `xs<population>`, `xp<period>` or `xq<period>`.
+pub fn classify(pattern: &Pattern, rule: &Rule) -> CensusObject {
+  let unknown = CensusObject {
+    apgcode: "zz_UNKNOWN".to_string(),
+    kind: ObjectKind::Unknown,
+  };
+  let Some(first) = trimmed(pattern) else {
+    return unknown;
+  };
+
+  // far enough from the dead edges that nothing moving at the speed of light reaches them
+  let margin = (MAX_PERIOD + 1) * reach(rule);
+  let size = UVec2::new(first.width, first.height) + 2 * margin;
+  let mut universe = CpuUniverse::new(size, rule, Topology::DeadEdges);
+  universe.load(&first, UVec2::splat(margin));
+
+  let mut phases = vec![first.clone()];
+  let mut kind = ObjectKind::Unknown;
+  for generation in 1..=MAX_PERIOD {
+    universe.step();
+    let Some((phase, offset)) = live_pattern(&universe) else {
+      return unknown;
+    };
+    if same_cells(&phase, &first) {
+      let moved = (offset.as_ivec2() - IVec2::splat(margin as i32)).abs();
+      kind = match (moved.max_element() as u32, moved.min_element() as u32) {
+        (0, _) if generation == 1 => ObjectKind::StillLife,
+        (0, _) => ObjectKind::Oscillator { period: generation },
+        (dx, dy) => ObjectKind::Spaceship {
+          period: generation,
+          dx,
+          dy,
+        },
+      };
+      break;
+    }
+    phases.push(phase);
+  }
+
+  let prefix = match kind {
+    ObjectKind::StillLife => format!("xs{}", live_cells(&first)),
+    ObjectKind::Oscillator { period } => format!("xp{period}"),
+    ObjectKind::Spaceship { period, .. } => format!("xq{period}"),
+    ObjectKind::Unknown => return unknown,
+  };
+  let code = phases
+    .iter()
+    .flat_map(orientations)
+    .map(|pattern| wechsler(&pattern))
+    .min_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)))
+    .unwrap_or_default();
+  CensusObject {
+    apgcode: format!("{prefix}_{code}"),
+    kind,
+  }
+}
+
+/// Encodes the non-dead cells of `pattern` in the extended Wechsler format: strips of five
+/// rows separated by `z`, every column of a strip a digit with the top cell as its lowest
+/// bit, runs of empty columns shortened to `w`, `x` and `y<n>`.
+pub fn wechsler(pattern: &Pattern) -> String {
+  let mut code = String::new();
+  for strip in 0..pattern.height.div_ceil(5) {
+    if strip > 0 {
+      code.push('z');
+    }
+    let columns: Vec<usize> = (0..pattern.width)
+      .map(|x| {
+        (0..5)
+          .filter(|&row| strip * 5 + row < pattern.height)
+          .filter(|&row| pattern.get(x, strip * 5 + row) != 0)
+          .map(|row| 1usize << row)
+          .sum()
+      })
+      .collect();
+    let end = columns
+      .iter()
+      .rposition(|&column| column != 0)
+      .map_or(0, |last| last + 1);
+
+    let mut empty = 0;
+    for &column in &columns[..end] {
+      if column == 0 {
+        empty += 1;
+        continue;
+      }
+      push_empty_columns(&mut code, empty);
+      empty = 0;
+      code.push(WECHSLER_DIGITS[column] as char);
+    }
+  }
+  code
+}
+
+fn push_empty_columns(code: &mut String, mut empty: usize) {
+  while empty > 0 {
+    let run = empty.min(39);
+    match run {
+      1 => code.push('0'),
+      2 => code.push('w'),
+      3 => code.push('x'),
+      _ => {
+        code.push('y');
+        code.push(WECHSLER_DIGITS[run - 4] as char);
+      }
+    }
+    empty -= run;
+  }
+}
+
+/// The objects of the settled soups by apgcode, and the soups that did not settle.
+#[derive(Clone, Debug, Default)]
+pub struct Census {
+  pub objects: BTreeMap<String, (ObjectKind, u64)>,
+  pub soups: u64,
+  pub unsettled: u64,
+}
+
+impl Census {
+  /// Runs the soup of `seed` until its population repeats and counts its objects.
+  pub fn add_soup(&mut self, run: &CensusRun, rule: &Rule, seed: u32) {
+    self.soups += 1;
+    let Some(objects) = settle_soup(run, rule, seed) else {
+      self.unsettled += 1;
+      return;
+    };
+    for object in objects {
+      self
+        .objects
+        .entry(object.apgcode)
+        .or_insert((object.kind, 0))
+        .1 += 1;
+    }
+  }
+
+  /// The counts as CSV, most common objects first.
+  pub fn report(&self, run: &CensusRun, rule: &Rule) -> String {
+    let mut report = format!(
+      "# {} soups of {size}x{size} cells in a {}x{} universe, seeds {}..{}, rule {rule}\n",
+      self.soups,
+      run.size.x,
+      run.size.y,
+      run.seeds.start,
+      run.seeds.end,
+      size = run.soup_size,
+    );
+    writeln!(
+      report,
+      "# {} still changing after {} generations\napgcode,kind,count",
+      self.unsettled, run.max_generations
+    )
+    .unwrap();
+
+    let mut objects: Vec<_> = self.objects.iter().collect();
+    objects.sort_by(|(a, (_, a_count)), (b, (_, b_count))| b_count.cmp(a_count).then(a.cmp(b)));
+    for (apgcode, (kind, count)) in objects {
+      writeln!(report, "{apgcode},{kind},{count}").unwrap();
+    }
+    report
+  }
+}
+
+/// Runs a census on `CpuUniverse` in one go and writes its report.
+pub fn run_census(run: Res<CensusRun>, rule: Res<Rule>, mut exit: EventWriter<AppExit>) {
+  if UVec2::splat(run.soup_size).cmpgt(run.size).any() {
+    error!(
+      "A {0}x{0} soup does not fit into the {1}x{2} universe",
+      run.soup_size, run.size.x, run.size.y
+    );
+    exit.write(AppExit::error());
+    return;
+  }
+
+  let mut census = Census::default();
+  for seed in run.seeds.clone() {
+    census.add_soup(&run, &rule, seed);
+  }
+
+  if let Err(err) = fs::write(&run.report, census.report(&run, &rule)) {
+    error!("Failed to write the census: {err}");
+    exit.write(AppExit::error());
+    return;
+  }
+  info!(
+    "Census of {} soups: {} objects of {} kinds, {} soups unsettled, written to {}",
+    census.soups,
+    census.objects.values().map(|(_, count)| count).sum::<u64>(),
+    census.objects.len(),
+    census.unsettled,
+    run.report.display()
+  );
+  exit.write(AppExit::Success);
+}
+
+/// The classified objects of the soup of `seed` once its population repeats, `None` if it
+/// does not within `max_generations`.
+fn settle_soup(run: &CensusRun, rule: &Rule, seed: u32) -> Option<Vec<CensusObject>> {
+  let mut soup = CpuUniverse::new(UVec2::splat(run.soup_size), rule, Topology::DeadEdges);
+  soup.randomize(seed);
+  let soup = soup.snapshot(0).to_pattern(URect::from_corners(
+    UVec2::ZERO,
+    UVec2::splat(run.soup_size),
+  ));
+
+  let mut universe = CpuUniverse::new(run.size, rule, Topology::DeadEdges);
+  universe.load(&soup, (run.size - UVec2::splat(run.soup_size)) / 2);
+  let mut populations = vec![universe.snapshot(0).population()];
+  let mut generation = 0;
+  let period = loop {
+    if let Some(period) = population_period(&populations) {
+      break period;
+    }
+    if generation >= run.max_generations {
+      return None;
+    }
+    universe.step();
+    generation += 1;
+    populations.push(universe.snapshot(0).population());
+  };
+
+  // the cells of every phase, so the parts of an oscillator and the track of a spaceship
+  // end up in the same object
+  let mut phases = Vec::new();
+  for _ in 0..period {
+    phases.push(universe.clone());
+    universe.step();
+  }
+  let objects = split_objects(&phases, reach(rule) + 1);
+  info!(
+    "Soup {seed} settled at generation {generation} with period {period} into {} objects",
+    objects.len()
+  );
+  Some(
+    objects
+      .iter()
+      .map(|object| classify(object, rule))
+      .collect(),
+  )
+}
+
+/// The smallest period the last `SETTLE_SPAN` populations repeat with.
+fn population_period(populations: &[u64]) -> Option<u32> {
+  let len = populations.len();
+  if len < SETTLE_SPAN + MAX_PERIOD as usize {
+    return None;
+  }
+  (1..=MAX_PERIOD).find(|&period| {
+    (len - SETTLE_SPAN..len).all(|i| populations[i] == populations[i - period as usize])
+  })
+}
+
+/// Groups the cells alive in any of `phases` into objects whose cells are at most
+/// `distance` cells apart, and returns the cells of every object in the first phase.
+fn split_objects(phases: &[CpuUniverse], distance: u32) -> Vec<Pattern> {
+  let Some(first) = phases.first() else {
+    return Vec::new();
+  };
+  let size = first.size();
+  let index = |cell: UVec2| (cell.x + cell.y * size.x) as usize;
+  let mut occupied = vec![false; (size.x * size.y) as usize];
+  for phase in phases {
+    for y in 0..size.y {
+      for x in 0..size.x {
+        occupied[index(UVec2::new(x, y))] |= phase.get(x, y) != 0;
+      }
+    }
+  }
+
+  let distance = distance as i32;
+  let mut objects = Vec::new();
+  let mut seen = vec![false; occupied.len()];
+  for start in (0..size.y).flat_map(|y| (0..size.x).map(move |x| UVec2::new(x, y))) {
+    if !occupied[index(start)] || seen[index(start)] {
+      continue;
+    }
+    seen[index(start)] = true;
+    let mut cells = vec![start];
+    let mut next = 0;
+    while let Some(&cell) = cells.get(next) {
+      next += 1;
+      for dy in -distance..=distance {
+        for dx in -distance..=distance {
+          let neighbor = cell.as_ivec2() + IVec2::new(dx, dy);
+          if neighbor.cmplt(IVec2::ZERO).any() || neighbor.cmpge(size.as_ivec2()).any() {
+            continue;
+          }
+          let neighbor = neighbor.as_uvec2();
+          if occupied[index(neighbor)] && !seen[index(neighbor)] {
+            seen[index(neighbor)] = true;
+            cells.push(neighbor);
+          }
+        }
+      }
+    }
+
+    // cells only alive in later phases belong to the object, but it is cut from the first
+    if cells.iter().all(|cell| first.get(cell.x, cell.y) == 0) {
+      continue;
+    }
+    let (min, max) = cells.iter().fold((size, UVec2::ZERO), |(min, max), &cell| {
+      (min.min(cell), max.max(cell))
+    });
+    let mut object = Pattern::new(max.x - min.x + 1, max.y - min.y + 1);
+    for cell in cells {
+      object.set(
+        cell.x - min.x,
+        cell.y - min.y,
+        first.get(cell.x, cell.y) as u8,
+      );
+    }
+    object.position = Some(min.as_ivec2());
+    objects.push(object);
+  }
+  objects
+}
+
+/// Cells away from a cell that it can affect in one generation.
+fn reach(rule: &Rule) -> u32 {
+  rule.larger_than_life.map_or(1, |ltl| ltl.range.max(1))
+}
+
+/// The non-dead cells of `universe`, with the cell their top left corner is at.
+fn live_pattern(universe: &CpuUniverse) -> Option<(Pattern, UVec2)> {
+  let snapshot = universe.snapshot(0);
+  let bounds = snapshot.live_bounds()?;
+  Some((snapshot.to_pattern(bounds), bounds.min))
+}
+
+/// `pattern` cut down to the bounds of its non-dead cells.
+fn trimmed(pattern: &Pattern) -> Option<Pattern> {
+  let cells = (0..pattern.height)
+    .flat_map(|y| (0..pattern.width).map(move |x| UVec2::new(x, y)))
+    .filter(|cell| pattern.get(cell.x, cell.y) != 0);
+  let (min, max) = cells.fold(None, |bounds: Option<(UVec2, UVec2)>, cell| {
+    Some(bounds.map_or((cell, cell), |(min, max)| (min.min(cell), max.max(cell))))
+  })?;
+
+  let mut trimmed = Pattern::new(max.x - min.x + 1, max.y - min.y + 1);
+  for y in 0..trimmed.height {
+    for x in 0..trimmed.width {
+      trimmed.set(x, y, pattern.get(min.x + x, min.y + y));
+    }
+  }
+  Some(trimmed)
+}
+
+fn same_cells(a: &Pattern, b: &Pattern) -> bool {
+  (a.width, a.height) == (b.width, b.height) && a.cells == b.cells
+}
+
+fn live_cells(pattern: &Pattern) -> usize {
+  pattern.cells.iter().filter(|&&state| state == 1).count()
+}
+
+/// The pattern in all eight orientations of the square.
+fn orientations(pattern: &Pattern) -> [Pattern; 8] {
+  let turned = pattern.rotated_clockwise();
+  let upside_down = turned.rotated_clockwise();
+  let turned_back = pattern.rotated_counterclockwise();
+  [
+    pattern.flipped_horizontally(),
+    turned.flipped_horizontally(),
+    upside_down.flipped_horizontally(),
+    turned_back.flipped_horizontally(),
+    pattern.clone(),
+    turned,
+    upside_down,
+    turned_back,
+  ]
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+  if b == 0 { a } else { gcd(b, a % b) }
+}
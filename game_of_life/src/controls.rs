@@ -0,0 +1,117 @@
+use bevy::{
+  ecs::{
+    event::{Event, EventReader},
+    resource::Resource,
+    system::ResMut,
+  },
+  log::info,
+  render::{Extract, extract_resource::ExtractResource},
+};
+
+use crate::data_structs::{ComputeState, Params};
+
+/// Most generations the render graph node computes in one frame.
+pub const MAX_GENERATIONS_PER_FRAME: u32 = 1024;
+/// Fastest speed the keyboard controls go up to.
+pub const MAX_TARGET_TPS: u32 = 1000;
+
+/// How the render graph node steps the universe, extracted to the render world every
+/// frame so changes apply while the app runs.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, ExtractResource)]
+pub struct SimulationControls {
+  pub paused: bool,
+  /// Steps per second, 0 steps every frame.
+  pub target_tps: u32,
+  /// Generations computed per step, each one a dispatch of the update kernel.
+  pub generations_per_frame: u32,
+  /// Single generations asked for while paused, counted since the start so the node can
+  /// tell which ones it already ran.
+  pub(crate) requested_steps: u64,
+}
+
+impl Default for SimulationControls {
+  fn default() -> Self {
+    Self {
+      paused: false,
+      target_tps: 10,
+      generations_per_frame: 1,
+      requested_steps: 0,
+    }
+  }
+}
+
+impl SimulationControls {
+  /// Asks for one more generation while paused.
+  pub fn step(&mut self) {
+    self.requested_steps += 1;
+  }
+
+  pub fn apply(&mut self, control: SimulationControl) {
+    match control {
+      SimulationControl::Pause => self.paused = true,
+      SimulationControl::Resume => self.paused = false,
+      SimulationControl::TogglePause => self.paused ^= true,
+      SimulationControl::Step => self.step(),
+      SimulationControl::SetTargetTps(tps) => self.target_tps = tps,
+      SimulationControl::SetGenerationsPerFrame(generations) => {
+        self.generations_per_frame = generations.clamp(1, MAX_GENERATIONS_PER_FRAME);
+      }
+    }
+  }
+}
+
+/// Changes the `SimulationControls`.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimulationControl {
+  Pause,
+  Resume,
+  TogglePause,
+  /// Runs a single generation, only while paused.
+  Step,
+  SetTargetTps(u32),
+  /// Clamped to `1..=MAX_GENERATIONS_PER_FRAME`.
+  SetGenerationsPerFrame(u32),
+}
+
+pub fn apply_simulation_controls(
+  mut events: EventReader<SimulationControl>,
+  mut controls: ResMut<SimulationControls>,
+) {
+  for &event in events.read() {
+    controls.apply(event);
+    if event != SimulationControl::Step {
+      info!(
+        "{} at {} steps per second, {} generations per step",
+        if controls.paused { "Paused" } else { "Running" },
+        controls.target_tps,
+        controls.generations_per_frame
+      );
+    }
+  }
+}
+
+/// Replaces the universe with the random soup of `seed`, starting over at generation 0.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reseed {
+  pub seed: u32,
+}
+
+pub fn apply_reseeds(mut reseeds: EventReader<Reseed>, mut params: ResMut<Params>) {
+  if let Some(reseed) = reseeds.read().last() {
+    info!("Reseeding with seed {}", reseed.seed);
+    params.random_seed = reseed.seed;
+  }
+}
+
+/// Sends the render graph node back to its initial state, which randomizes the universe
+/// with the seed extracted alongside the event.
+pub fn extract_reseeds(
+  mut reseeds: Extract<EventReader<Reseed>>,
+  state: Option<ResMut<ComputeState>>,
+) {
+  if reseeds.read().count() > 0
+    && let Some(mut state) = state
+  {
+    *state = ComputeState::INITIAL;
+  }
+}
@@ -0,0 +1,529 @@
+use bevy::math::{IVec2, URect, UVec2, Vec2, Vec3, Vec4};
+use bytemuck::Zeroable;
+
+use crate::{
+  data_structs::{AgeGradient, Axis, CellColoring, Params, Topology},
+  paint::CellEdit,
+  pattern::Pattern,
+  readback::UniverseSnapshot,
+  rule::Rule,
+  selection::{RegionEdit, fill_threshold},
+};
+
+/// A CPU implementation of the compute kernels in `game_of_life.wgsl`, working on the same
+/// storage buffer layout: `buffer_size_x` words per row, each holding 32 cells of 1 bit
+/// (or 4 cells of 8 bits for Generations rules) with the leftmost cell in the most
+/// significant bits.
+///
+/// It is written for clarity rather than speed and serves as the specification the GPU
+/// output is checked against. The cell ages are always kept, as the `update_ages` kernel
+/// keeps them while a coloring needs them.
+#[derive(Clone)]
+pub struct CpuUniverse {
+  rule: Rule,
+  params: Params,
+  topology: Topology,
+  words: Vec<u32>,
+  ages: Vec<u32>,
+}
+
+/// The part of the universe the display kernel draws, as set through `Params`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+  /// Resolution of the texture in pixels.
+  pub resolution: UVec2,
+  /// Cell position shown in the middle of the texture.
+  pub center: Vec2,
+  /// Pixels per cell.
+  pub zoom: f32,
+  /// Whether the copies of a wrapping universe are drawn past its edges.
+  pub tile_display: bool,
+}
+
+const OUT_OF_BOUNDS_COLOR: Vec4 = Vec4::new(1.0, 0.0, 0.0, 1.0);
+const DEAD_COLOR: Vec4 = Vec4::new(0.0, 0.0, 0.0, 1.0);
+const ALIVE_COLOR: Vec4 = Vec4::new(1.0, 1.0, 1.0, 1.0);
+const DYING_START_COLOR: Vec3 = Vec3::new(1.0, 0.6, 0.1);
+const DYING_END_COLOR: Vec3 = Vec3::new(0.2, 0.0, 0.5);
+const MAX_AVERAGED_CELLS: u32 = 16;
+
+impl CpuUniverse {
+  /// An empty universe of `size` cells.
+  pub fn new(size: UVec2, rule: &Rule, topology: Topology) -> Self {
+    let mut params = Params {
+      buffer_size_y: size.y,
+      cell_count_x: size.x,
+      bits_per_cell: 1,
+      ..Zeroable::zeroed()
+    };
+    params.set_rule(rule);
+
+    Self {
+      rule: *rule,
+      words: vec![0; (params.buffer_size_x * params.buffer_size_y) as usize],
+      ages: vec![
+        0;
+        (params.buffer_size_x * params.cells_per_word() / 4 * params.buffer_size_y)
+          as usize
+      ],
+      params,
+      topology,
+    }
+  }
+
+  /// A universe holding the words of a storage buffer laid out for `rule`.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the number of words does not match the layout of `rule`.
+  pub fn from_words(size: UVec2, rule: &Rule, topology: Topology, words: Vec<u32>) -> Self {
+    let mut universe = Self::new(size, rule, topology);
+    assert_eq!(
+      words.len(),
+      universe.words.len(),
+      "buffer does not match the layout"
+    );
+    universe.words = words;
+    universe
+  }
+
+  pub fn size(&self) -> UVec2 {
+    UVec2::new(self.params.cell_count_x, self.params.buffer_size_y)
+  }
+
+  pub fn buffer_size_x(&self) -> u32 {
+    self.params.buffer_size_x
+  }
+
+  pub fn rule(&self) -> Rule {
+    self.rule
+  }
+
+  pub fn bits_per_cell(&self) -> u32 {
+    self.params.bits_per_cell
+  }
+
+  /// The storage buffer contents, as the GPU would hold them.
+  pub fn words(&self) -> &[u32] {
+    &self.words
+  }
+
+  /// A snapshot of the storage buffer as a readback at `generation` would deliver it.
+  pub fn snapshot(&self, generation: u64) -> UniverseSnapshot {
+    UniverseSnapshot::new(
+      generation,
+      self.size(),
+      self.rule,
+      self.buffer_size_x(),
+      self.words.clone(),
+    )
+  }
+
+  /// The age buffer contents: four ages of 8 bits per word, the leftmost cell in the lowest
+  /// byte.
+  pub fn ages(&self) -> &[u32] {
+    &self.ages
+  }
+
+  /// Generations the cell at `(x, y)` has been alive for, up to 255, or 0 for cells that did
+  /// not live through a generation yet.
+  pub fn age(&self, x: u32, y: u32) -> u32 {
+    let index = x + y * self.params.buffer_size_x * self.params.cells_per_word();
+    (self.ages[(index / 4) as usize] >> (index % 4 * 8)) & 0xFF
+  }
+
+  /// Colors the pixels with `coloring` from now on, like `Params::coloring` does.
+  pub fn set_coloring(&mut self, coloring: CellColoring) {
+    self.params.coloring = coloring.shader_id();
+  }
+
+  /// Replaces the cells with the words of a keyframe, the way a rewind copies one back
+  /// before re-simulating from it. No cell of it has aged yet.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the number of words does not match the layout.
+  pub fn restore(&mut self, words: &[u32]) {
+    self.words.copy_from_slice(words);
+    self.ages.fill(0);
+  }
+
+  pub fn get(&self, x: u32, y: u32) -> u32 {
+    self.cell_state(x as i32, y as i32)
+  }
+
+  pub fn set(&mut self, x: u32, y: u32, state: u32) {
+    let cells_per_word = self.params.cells_per_word();
+    let bits_per_cell = self.params.bits_per_cell;
+    let index = (x / cells_per_word + y * self.params.buffer_size_x) as usize;
+    let shift = (cells_per_word - 1 - x % cells_per_word) * bits_per_cell;
+    let state_mask = (1 << bits_per_cell) - 1;
+    self.words[index] =
+      (self.words[index] & !(state_mask << shift)) | (state & state_mask) << shift;
+  }
+
+  /// Writes the cells of `pattern` with its top left corner at `offset`, the way the
+  /// pattern loader writes them into the storage buffer, starting the ages over.
+  pub fn load(&mut self, pattern: &Pattern, offset: UVec2) {
+    self.ages.fill(0);
+    let (first_word, rows) = pattern.pack_rows(offset.x, self.params.bits_per_cell);
+    for (y, row) in rows.iter().enumerate() {
+      let start = (first_word + (offset.y + y as u32) * self.params.buffer_size_x) as usize;
+      self.words[start..start + row.len()].copy_from_slice(row);
+    }
+  }
+
+  /// The `apply_edits` kernel: every edit replaces the masked bits of its word, so painted
+  /// cells land in the buffer like they do on the GPU.
+  pub fn apply_edits(&mut self, edits: &[CellEdit]) {
+    for edit in edits {
+      let word = &mut self.words[edit.index as usize];
+      *word = (*word & !edit.mask) | edit.bits;
+    }
+  }
+
+  /// The `edit_region` and `copy_region` kernels. Copies and cuts return the copied cells,
+  /// pastes write the cells of `clipboard` the way the clipboard buffer holds them.
+  pub fn edit_region(&mut self, edit: &RegionEdit, clipboard: Option<&Pattern>) -> Option<Pattern> {
+    let universe = URect::from_corners(UVec2::ZERO, self.size());
+    let inside =
+      |rect: URect, x, y| x >= rect.min.x && x < rect.max.x && y >= rect.min.y && y < rect.max.y;
+    let cells = (0..universe.max.y).flat_map(|y| (0..universe.max.x).map(move |x| (x, y)));
+
+    match *edit {
+      RegionEdit::Clear(rect) => {
+        for (x, y) in cells.filter(|&(x, y)| inside(rect, x, y)) {
+          self.set(x, y, 0);
+        }
+      }
+      RegionEdit::ClearOutside(rect) => {
+        for (x, y) in cells.filter(|&(x, y)| !inside(rect, x, y)) {
+          self.set(x, y, 0);
+        }
+      }
+      RegionEdit::Fill {
+        rect,
+        density,
+        seed,
+      } => {
+        let threshold = fill_threshold(density);
+        for (x, y) in cells.filter(|&(x, y)| inside(rect, x, y)) {
+          let index = x.wrapping_add(y.wrapping_mul(self.params.cell_count_x));
+          self.set(x, y, u32::from(random_u32(seed, index) >> 8 < threshold));
+        }
+      }
+      RegionEdit::Copy(rect) | RegionEdit::Cut(rect) => {
+        let rect = rect.intersect(universe);
+        if rect.is_empty() {
+          return None;
+        }
+        let mut pattern = Pattern::new(rect.width(), rect.height());
+        for (x, y) in cells.filter(|&(x, y)| inside(rect, x, y)) {
+          pattern.set(x - rect.min.x, y - rect.min.y, self.get(x, y) as u8);
+        }
+        pattern.position = Some(rect.min.as_ivec2());
+
+        if let RegionEdit::Cut(_) = edit {
+          self.edit_region(&RegionEdit::Clear(rect), None);
+        }
+        return Some(pattern);
+      }
+      RegionEdit::Paste(offset) => {
+        let clipboard = clipboard?;
+        let state_mask = (1 << self.params.bits_per_cell) - 1;
+        for y in 0..clipboard.height {
+          for x in 0..clipboard.width {
+            let cell = offset + UVec2::new(x, y).as_ivec2();
+            if cell.cmplt(IVec2::ZERO).any() || cell.cmpge(self.size().as_ivec2()).any() {
+              continue;
+            }
+            let state = match self.params.bits_per_cell {
+              1 => u32::from(clipboard.get(x, y) == 1),
+              _ => u32::from(clipboard.get(x, y)) & state_mask,
+            };
+            self.set(cell.x as u32, cell.y as u32, state);
+          }
+        }
+      }
+    }
+    None
+  }
+
+  /// The `randomize` kernel: every word gets the hash of its index and `seed`, keeping
+  /// only live and dead cells. A new soup starts the ages over.
+  pub fn randomize(&mut self, seed: u32) {
+    self.ages.fill(0);
+    let alive_mask = match self.params.bits_per_cell {
+      8 => 0x01010101,
+      _ => 0xFFFFFFFF,
+    };
+
+    for (index, word) in self.words.iter_mut().enumerate() {
+      let word_x = index as u32 % self.params.buffer_size_x;
+      *word = random_u32(seed, index as u32) & alive_mask & cell_bits(&self.params, word_x);
+    }
+  }
+
+  /// Advances the universe by one generation, like whichever update kernel the render
+  /// graph picks for the rule followed by `update_ages`.
+  pub fn step(&mut self) {
+    let mut next = Self {
+      rule: self.rule,
+      params: self.params,
+      topology: self.topology,
+      words: vec![0; self.words.len()],
+      ages: vec![],
+    };
+
+    for y in 0..self.params.buffer_size_y {
+      for x in 0..self.params.cell_count_x {
+        let state = self.get(x, y);
+        let count = self.live_neighbors(x as i32, y as i32);
+
+        let params = &self.params;
+        let (born, survives) = match params.range {
+          0 => (
+            (params.birth_mask >> count) & 1 != 0,
+            (params.survival_mask >> count) & 1 != 0,
+          ),
+          _ => (
+            (params.birth_min..=params.birth_max).contains(&count),
+            (params.survival_min..=params.survival_max).contains(&count),
+          ),
+        };
+
+        let next_state = match state {
+          0 => u32::from(born),
+          1 if survives => 1,
+          _ => (state + 1) % params.state_count,
+        };
+        next.set(x, y, next_state);
+      }
+    }
+
+    next.ages = std::mem::take(&mut self.ages);
+    let previous = std::mem::replace(self, next);
+    self.update_ages(&previous);
+  }
+
+  /// The `update_ages` kernel: cells alive before and after the generation age by one up to
+  /// 255, newly born cells are 1 and dead ones 0.
+  fn update_ages(&mut self, previous: &CpuUniverse) {
+    // the padding cells past the logical width never live, so their ages stay 0
+    let row_cells = self.params.buffer_size_x * self.params.cells_per_word();
+    for y in 0..self.params.buffer_size_y {
+      for x in 0..self.params.cell_count_x {
+        let index = x + y * row_cells;
+        let age = match (previous.get(x, y) == 1, self.get(x, y) == 1) {
+          (_, false) => 0,
+          (false, true) => 1,
+          (true, true) => (self.age(x, y) + 1).min(255),
+        };
+        let word = &mut self.ages[(index / 4) as usize];
+        let shift = index % 4 * 8;
+        *word = (*word & !(0xFF << shift)) | age << shift;
+      }
+    }
+  }
+
+  /// The `average_age` of the display kernel: the mean age of the live cells a pixel at
+  /// `position` covers at `zoom`, sampling at most 16 cells along each axis, or 0 if none
+  /// of them are alive. Live cells that did not age yet count as 1.
+  pub fn average_age(&self, position: Vec2, zoom: f32) -> f32 {
+    let span = (1.0 / zoom).max(1.0);
+    let samples = (span.ceil() as u32).min(MAX_AVERAGED_CELLS);
+    let stride = span / samples as f32;
+
+    let (mut sum, mut live) = (0, 0);
+    for sy in 0..samples {
+      for sx in 0..samples {
+        let sample = position + Vec2::new(sx as f32, sy as f32) * stride;
+        let cell = sample.floor().as_ivec2();
+        let Some(cell) = wrap(&self.params, self.topology, cell) else {
+          continue;
+        };
+        if self.cell_state(cell.x, cell.y) != 1 {
+          continue;
+        }
+        live += 1;
+        sum += self.age(cell.x as u32, cell.y as u32).max(1);
+      }
+    }
+    if live == 0 {
+      return 0.0;
+    }
+    sum as f32 / live as f32
+  }
+
+  /// Live cells around `(x, y)`: the Moore neighborhood for Life-like and Generations rules,
+  /// the rule's neighborhood for Larger than Life.
+  fn live_neighbors(&self, x: i32, y: i32) -> u32 {
+    let params = &self.params;
+    let range = params.range.max(1) as i32;
+    let von_neumann = params.range > 0 && params.von_neumann != 0;
+    let include_center = params.range > 0 && params.include_center != 0;
+
+    let mut count = 0;
+    for dy in -range..=range {
+      for dx in -range..=range {
+        if von_neumann && dx.abs() + dy.abs() > range {
+          continue;
+        }
+        if dx == 0 && dy == 0 && !include_center {
+          continue;
+        }
+        count += u32::from(self.cell_state(x + dx, y + dy) == 1);
+      }
+    }
+    count
+  }
+
+  /// State of a cell anywhere on the plane, following the topology for cells outside the
+  /// universe like `cell_state` in the shader.
+  pub fn cell_state(&self, x: i32, y: i32) -> u32 {
+    let Some(cell) = wrap(&self.params, self.topology, IVec2::new(x, y)) else {
+      return u32::from(self.topology == Topology::AliveEdges);
+    };
+
+    let cells_per_word = self.params.cells_per_word();
+    let (x, y) = (cell.x as u32, cell.y as u32);
+    let word = self.words[(x / cells_per_word + y * self.params.buffer_size_x) as usize];
+    let shift = (cells_per_word - 1 - x % cells_per_word) * self.params.bits_per_cell;
+    let state_mask = (1 << self.params.bits_per_cell) - 1;
+    (word >> shift) & state_mask
+  }
+
+  /// The cell position a pixel of the display texture shows, before flooring.
+  pub fn pixel_position(viewport: &Viewport, pixel: UVec2) -> Vec2 {
+    viewport.center + (pixel.as_vec2() - viewport.resolution.as_vec2() * 0.5) / viewport.zoom
+  }
+
+  /// The color the `display` kernel writes for `pixel`.
+  pub fn pixel_color(&self, viewport: &Viewport, pixel: UVec2) -> Vec4 {
+    let position = Self::pixel_position(viewport, pixel);
+    let size = self.size().as_vec2();
+    let outside_bounds = position.cmplt(Vec2::ZERO).any() || position.cmpge(size).any();
+
+    let cell = position.floor().as_ivec2();
+    let wrapping = !matches!(self.topology, Topology::DeadEdges | Topology::AliveEdges);
+    let tiled =
+      wrapping && viewport.tile_display && wrap(&self.params, self.topology, cell).is_some();
+
+    let state = self.cell_state(cell.x, cell.y);
+    if outside_bounds && !tiled {
+      OUT_OF_BOUNDS_COLOR
+    } else if self.params.coloring != 0 && state <= 1 {
+      let age = self.average_age(position, viewport.zoom);
+      if age > 0.0 {
+        age_color(self.params.coloring, age).extend(1.0)
+      } else {
+        DEAD_COLOR
+      }
+    } else if state == 1 {
+      ALIVE_COLOR
+    } else if state > 1 {
+      let t = (state - 1) as f32 / (self.params.state_count - 1) as f32;
+      DYING_START_COLOR.lerp(DYING_END_COLOR, t).extend(1.0)
+    } else {
+      DEAD_COLOR
+    }
+  }
+}
+
+/// The `age_color` of the display kernel: the gradient of `coloring` at an age of 1 to 255,
+/// on a log scale.
+fn age_color(coloring: u32, age: f32) -> Vec3 {
+  let t = (age.log2() / 8.0).clamp(0.0, 1.0);
+  let (start, middle, end) = match coloring {
+    id if id == CellColoring::Age(AgeGradient::Viridis).shader_id() => (
+      Vec3::new(0.99, 0.91, 0.14),
+      Vec3::new(0.13, 0.57, 0.55),
+      Vec3::new(0.27, 0.0, 0.33),
+    ),
+    id if id == CellColoring::Age(AgeGradient::Ice).shader_id() => (
+      Vec3::new(1.0, 1.0, 1.0),
+      Vec3::new(0.2, 0.8, 1.0),
+      Vec3::new(0.0, 0.1, 0.5),
+    ),
+    _ => (
+      Vec3::new(1.0, 1.0, 0.6),
+      Vec3::new(1.0, 0.5, 0.0),
+      Vec3::new(0.5, 0.0, 0.0),
+    ),
+  };
+  if t < 0.5 {
+    start.lerp(middle, t * 2.0)
+  } else {
+    middle.lerp(end, t * 2.0 - 1.0)
+  }
+}
+
+/// Counterpart of `wrap` in the shader: the cell a position is glued to under `topology`,
+/// `None` when it lands on no cell.
+fn wrap(params: &Params, topology: Topology, position: IVec2) -> Option<IVec2> {
+  let size = IVec2::new(params.cell_count_x as i32, params.buffer_size_y as i32);
+  let mut p = position;
+  // how many times the position crossed each pair of edges, negative for left and top
+  let crossings = IVec2::new(p.x.div_euclid(size.x), p.y.div_euclid(size.y));
+
+  let (mirror_x, mirror_y) = match topology {
+    Topology::DeadEdges | Topology::AliveEdges => {
+      return (crossings == IVec2::ZERO).then_some(p);
+    }
+    Topology::Sphere => {
+      for _ in 0..2 {
+        if p.y < 0 {
+          p = IVec2::new(-p.y - 1, p.x);
+        } else if p.x < 0 {
+          p = IVec2::new(p.y, -p.x - 1);
+        } else if p.y >= size.y {
+          p = IVec2::new(size.x + size.y - 1 - p.y, p.x);
+        } else if p.x >= size.x {
+          p = IVec2::new(p.y, size.x + size.y - 1 - p.x);
+        }
+      }
+      let inside = p.cmpge(IVec2::ZERO).all() && p.cmplt(size).all();
+      return inside.then_some(p);
+    }
+    Topology::Torus => (false, false),
+    Topology::KleinBottle {
+      twisted: Axis::Horizontal,
+    } => (true, false),
+    Topology::KleinBottle {
+      twisted: Axis::Vertical,
+    } => (false, true),
+    Topology::CrossSurface => (true, true),
+  };
+
+  if mirror_x && crossings.y & 1 != 0 {
+    p.x = size.x - 1 - p.x;
+  }
+  if mirror_y && crossings.x & 1 != 0 {
+    p.y = size.y - 1 - p.y;
+  }
+  Some(IVec2::new(p.x.rem_euclid(size.x), p.y.rem_euclid(size.y)))
+}
+
+/// Bits of a word in buffer column `word_x` that hold cells inside the logical width.
+fn cell_bits(params: &Params, word_x: u32) -> u32 {
+  let cells_per_word = params.cells_per_word();
+  let first_cell = word_x * cells_per_word;
+  if first_cell + cells_per_word <= params.cell_count_x {
+    return u32::MAX;
+  }
+
+  let padding_bits = (first_cell + cells_per_word - params.cell_count_x) * params.bits_per_cell;
+  !((1u32 << padding_bits) - 1)
+}
+
+/// The hash behind the `randomize` kernel, for the word at `index`.
+fn random_u32(seed: u32, index: u32) -> u32 {
+  let mut input = seed.wrapping_add(index);
+  input ^= 2747636419;
+  input = input.wrapping_mul(2654435769);
+  input ^= input >> 16;
+  input = input.wrapping_mul(2654435769);
+  input ^= input >> 16;
+  input = input.wrapping_mul(2654435769);
+  input
+}
@@ -1,4 +1,7 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{
+  Arc,
+  atomic::{AtomicU64, Ordering},
+};
 
 use bevy::{
   asset::Handle,
@@ -12,25 +15,192 @@ use bevy::{
 };
 use bytemuck::{Pod, Zeroable};
 
+use crate::rule::{Neighborhood, Rule};
+
 #[derive(Resource, ExtractResource, Clone)]
 pub struct MainImage(pub Handle<Image>);
 
-#[repr(C)]
-#[derive(Resource, Clone, Copy, ShaderType, Pod, Zeroable, ExtractResource)]
-pub struct Params {
-  pub buffer_size_x: u32,
-  pub buffer_size_y: u32,
-  pub center_x: f32,
-  pub center_y: f32,
-  pub resolution_x: u32,
-  pub resolution_y: u32,
-  pub random_seed: u32,
-  pub zoom: f32,
+pub use params::Params;
+
+// the `ShaderType` derive emits per-field `check` functions that are never called, and an
+// allow only reaches them from an enclosing module
+#[allow(dead_code)]
+mod params {
+  use super::*;
+
+  #[repr(C)]
+  #[derive(Resource, Clone, Copy, ShaderType, Pod, Zeroable, ExtractResource)]
+  pub struct Params {
+    pub buffer_size_x: u32,
+    pub buffer_size_y: u32,
+    pub center_x: f32,
+    pub center_y: f32,
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub random_seed: u32,
+    pub zoom: f32,
+    pub birth_mask: u32,
+    pub survival_mask: u32,
+    pub state_count: u32,
+    pub bits_per_cell: u32,
+    // Larger than Life neighborhood, a range of 0 selects the 8-cell Moore kernel
+    pub range: u32,
+    pub von_neumann: u32,
+    pub include_center: u32,
+    pub birth_min: u32,
+    pub birth_max: u32,
+    pub survival_min: u32,
+    pub survival_max: u32,
+    /// Logical width of the universe in cells. The rightmost word of a row may extend past
+    /// it, the kernels keep those padding cells dead and the display treats them as outside.
+    pub cell_count_x: u32,
+    pub topology: u32,
+    pub tile_display: u32,
+    /// Top left cell of the stamp preview drawn over the universe, which is hidden while
+    /// `ghost_width` is 0. Its cells are in the ghost buffer, packed like the universe.
+    pub ghost_x: i32,
+    pub ghost_y: i32,
+    pub ghost_width: u32,
+    pub ghost_height: u32,
+    /// Whether stamping replaces the cells under the preview instead of adding to them.
+    pub ghost_replace: u32,
+    /// The selected rectangle of cells, none while `selection_width` is 0.
+    pub selection_x: u32,
+    pub selection_y: u32,
+    pub selection_width: u32,
+    pub selection_height: u32,
+    /// `CellColoring::shader_id`, anything but 0 reads the age buffer.
+    pub coloring: u32,
+  }
+}
+
+impl Params {
+  pub fn cells_per_word(&self) -> u32 {
+    32 / self.bits_per_cell
+  }
+
+  /// Copies the rule into the shader parameters, re-laying out the buffer width for the
+  /// number of bits per cell the rule needs.
+  pub fn set_rule(&mut self, rule: &Rule) {
+    self.birth_mask = rule.birth;
+    self.survival_mask = rule.survival;
+    self.state_count = rule.states;
+
+    let ltl = rule.larger_than_life;
+    self.range = ltl.map_or(0, |ltl| ltl.range);
+    self.von_neumann = ltl.map_or(0, |ltl| {
+      (ltl.neighborhood == Neighborhood::VonNeumann).into()
+    });
+    self.include_center = ltl.map_or(0, |ltl| ltl.include_center.into());
+    (self.birth_min, self.birth_max) = ltl.map_or((0, 0), |ltl| ltl.birth);
+    (self.survival_min, self.survival_max) = ltl.map_or((0, 0), |ltl| ltl.survival);
+
+    self.bits_per_cell = rule.bits_per_cell();
+    self.buffer_size_x = self.cell_count_x.div_ceil(self.cells_per_word());
+  }
 }
 
 #[derive(Resource)]
 pub struct GpuParamsHandle(pub Buffer);
 
+/// Which edges of the universe are glued together, and how.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+  /// A bounded plane, everything past the edges is dead.
+  #[default]
+  DeadEdges,
+  /// A bounded plane, everything past the edges is alive.
+  AliveEdges,
+  Torus,
+  /// A torus where the edges along `twisted` are glued with a twist, so crossing them
+  /// mirrors the other coordinate.
+  KleinBottle {
+    twisted: Axis,
+  },
+  /// Both pairs of edges are glued with a twist (the real projective plane).
+  CrossSurface,
+  /// The top edge is glued to the left edge and the bottom edge to the right edge. Only
+  /// square universes line up.
+  Sphere,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+  /// The top and bottom edges.
+  Horizontal,
+  /// The left and right edges.
+  Vertical,
+}
+
+impl Topology {
+  /// Identifier of the topology in `game_of_life.wgsl`.
+  pub fn shader_id(&self) -> u32 {
+    match self {
+      Topology::DeadEdges => 0,
+      Topology::AliveEdges => 1,
+      Topology::Torus => 2,
+      Topology::KleinBottle {
+        twisted: Axis::Horizontal,
+      } => 3,
+      Topology::KleinBottle {
+        twisted: Axis::Vertical,
+      } => 4,
+      Topology::CrossSurface => 5,
+      Topology::Sphere => 6,
+    }
+  }
+}
+
+/// How the display colors live cells.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellColoring {
+  #[default]
+  Plain,
+  /// By the generations a cell has been alive, mapped through a gradient. Needs an age
+  /// counter per cell that every generation keeps up to date. Dying states of Generations
+  /// rules keep their colors.
+  Age(AgeGradient),
+}
+
+/// Color gradients from newly born cells to the oldest ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AgeGradient {
+  /// Yellow through orange to dark red.
+  #[default]
+  Heat,
+  /// Yellow through teal to purple.
+  Viridis,
+  /// White through cyan to deep blue.
+  Ice,
+}
+
+impl CellColoring {
+  /// Identifier of the coloring in `game_of_life.wgsl`.
+  pub fn shader_id(&self) -> u32 {
+    match self {
+      CellColoring::Plain => 0,
+      CellColoring::Age(AgeGradient::Heat) => 1,
+      CellColoring::Age(AgeGradient::Viridis) => 2,
+      CellColoring::Age(AgeGradient::Ice) => 3,
+    }
+  }
+
+  pub fn tracks_ages(&self) -> bool {
+    *self != CellColoring::Plain
+  }
+
+  /// The coloring after this one, cycling through plain and every gradient.
+  pub fn next(&self) -> Self {
+    match self {
+      CellColoring::Plain => CellColoring::Age(AgeGradient::Heat),
+      CellColoring::Age(AgeGradient::Heat) => CellColoring::Age(AgeGradient::Viridis),
+      CellColoring::Age(AgeGradient::Viridis) => CellColoring::Age(AgeGradient::Ice),
+      CellColoring::Age(AgeGradient::Ice) => CellColoring::Plain,
+    }
+  }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Resource, ExtractResource, Clone, Default, PartialEq)]
 pub enum ComputeState {
   #[default]
@@ -38,6 +208,11 @@ pub enum ComputeState {
   RANDOMIZE,
   STEP,
   WAIT,
+  /// Copies a keyframe back from the history and re-simulates up to the generation rewound
+  /// to.
+  REWIND,
+  /// Copies the latest generation into the storage buffers of a resized universe.
+  RESIZE,
 }
 
 #[derive(Resource, Default)]
@@ -50,18 +225,26 @@ pub struct WindowData {
   pub pos: Option<IVec2>,
 }
 
-#[derive(Resource, Clone, ExtractResource)]
-pub struct Telemetry {
-  pub ticks_len: usize,
-  pub ticks: Arc<Mutex<Vec<f32>>>,
-}
+/// Generation the render graph node stops stepping at, so a headless run can read the
+/// universe back at exactly the generations it samples.
+#[derive(Resource, Clone, Copy, ExtractResource, Default)]
+pub struct GenerationLimit(pub u64);
 
-impl Default for Telemetry {
-  fn default() -> Self {
-    let len = 1000;
-    Self {
-      ticks_len: len,
-      ticks: Arc::new(Mutex::new(Vec::<f32>::with_capacity(len))),
-    }
+/// Number of the generation in the storage buffers, counted by the render graph node and
+/// reset when the universe is randomized or a pattern is loaded.
+#[derive(Resource, Clone, ExtractResource, Default)]
+pub struct Generation(pub Arc<AtomicU64>);
+
+impl Generation {
+  pub fn get(&self) -> u64 {
+    self.0.load(Ordering::Relaxed)
+  }
+
+  pub fn set(&self, generation: u64) {
+    self.0.store(generation, Ordering::Relaxed);
+  }
+
+  pub fn advance(&self, generations: u64) {
+    self.0.fetch_add(generations, Ordering::Relaxed);
   }
 }
@@ -0,0 +1,94 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::{error, info, warn},
+};
+
+use crate::{
+  pattern::{Pattern, write_macrocell, write_plaintext, write_rle},
+  readback::{RequestSnapshot, UniverseSnapshot},
+};
+
+/// Which part of the universe an export covers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportBounds {
+  Universe,
+  /// The bounding box of all non-dead cells.
+  #[default]
+  LiveCells,
+}
+
+/// Writes the universe to `path`, as plaintext for `.cells` files, as a macrocell quadtree
+/// for `.mc` files and as RLE otherwise.
+/// The rule, the generation and the position of the exported cells are written along, so
+/// loading the file restores the same state. Macrocell files have no position and are
+/// loaded centered on the view.
+#[derive(Event, Clone, Debug)]
+pub struct ExportPattern {
+  pub path: PathBuf,
+  pub bounds: ExportBounds,
+}
+
+/// Exports waiting for the next snapshot of the universe.
+#[derive(Resource, Default)]
+pub struct PendingExports(Vec<ExportPattern>);
+
+pub fn request_exports(
+  mut exports: EventReader<ExportPattern>,
+  mut pending: ResMut<PendingExports>,
+  mut requests: EventWriter<RequestSnapshot>,
+) {
+  for export in exports.read() {
+    pending.0.push(export.clone());
+    requests.write(RequestSnapshot);
+  }
+}
+
+pub fn write_exports(
+  mut snapshots: EventReader<UniverseSnapshot>,
+  mut pending: ResMut<PendingExports>,
+) {
+  let Some(snapshot) = snapshots.read().last() else {
+    return;
+  };
+
+  for export in pending.0.drain(..) {
+    let pattern = snapshot.export_pattern(export.bounds);
+
+    match write_pattern_file(&export.path, &pattern) {
+      Ok(()) => info!(
+        "Exported generation {} to {}",
+        snapshot.generation,
+        export.path.display()
+      ),
+      Err(err) => error!("Failed to write {}: {err}", export.path.display()),
+    }
+  }
+}
+
+/// Writes `pattern` in the format the extension of `path` asks for.
+pub(crate) fn write_pattern_file(path: &Path, pattern: &Pattern) -> io::Result<()> {
+  let extension = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .map(str::to_ascii_lowercase);
+  let contents = match extension.as_deref() {
+    Some("cells") => {
+      if let Some(rule) = pattern.rule.filter(|rule| rule.states > 2) {
+        warn!("Plaintext cannot hold the dying states of {rule}");
+      }
+      write_plaintext(pattern)
+    }
+    Some("mc") => write_macrocell(pattern),
+    _ => write_rle(pattern),
+  };
+  fs::write(path, contents)
+}
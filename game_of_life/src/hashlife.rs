@@ -0,0 +1,712 @@
+use std::{error::Error, fmt, mem};
+
+use bevy::{
+  math::{I64Vec2, IVec2, UVec2},
+  platform::collections::HashMap,
+};
+
+use crate::{
+  pattern::{Macrocell, MacrocellNode, Pattern},
+  rule::Rule,
+};
+
+/// Index of a node in the arena of a `HashLife` universe.
+type NodeId = u32;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+
+/// Largest power of two a single step can advance by, which keeps the coordinates of the
+/// root inside the range of `i64`.
+pub const MAX_STEP_LOG2: u32 = 58;
+/// Smallest level of the root, a step needs at least two levels below it.
+const MIN_LEVEL: u8 = 3;
+/// Node count above which unreachable nodes and memoized results are dropped after a step.
+const GC_THRESHOLD: usize = 1 << 24;
+
+/// A square of `2^level` cells on a side. Leaves are single cells; every other node is
+/// made of four nodes one level below, so identical squares are stored once.
+#[derive(Clone, Copy)]
+struct Node {
+  /// The north west, north east, south west and south east quadrants.
+  children: [NodeId; 4],
+  level: u8,
+  population: u64,
+}
+
+/// Rules `HashLife` cannot run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnsupportedRule {
+  Generations,
+  LargerThanLife,
+  /// Births on zero neighbors fill the unbounded plane in a single generation.
+  BirthOnZero,
+}
+
+impl fmt::Display for UnsupportedRule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UnsupportedRule::Generations => write!(f, "HashLife does not support Generations rules"),
+      UnsupportedRule::LargerThanLife => {
+        write!(f, "HashLife does not support Larger than Life rules")
+      }
+      UnsupportedRule::BirthOnZero => write!(f, "HashLife does not support B0 rules"),
+    }
+  }
+}
+
+impl Error for UnsupportedRule {}
+
+/// A Life-like universe on the unbounded plane, stored as a hash-consed quadtree. The
+/// future of every node is memoized, so repetitive patterns advance by huge powers of two
+/// in a few steps.
+///
+/// Unlike the GPU universe there are no edges: cells keep going past the size of the
+/// buffer they were imported from.
+pub struct HashLife {
+  birth: u32,
+  survival: u32,
+  nodes: Vec<Node>,
+  ids: HashMap<[NodeId; 4], NodeId>,
+  /// The center of a node advanced by `2^j` generations, keyed by node and `j`.
+  results: HashMap<(NodeId, u8), NodeId>,
+  /// The empty node of every level.
+  empty: Vec<NodeId>,
+  root: NodeId,
+  /// Position of the top left cell of the root.
+  origin: I64Vec2,
+  generation: u64,
+}
+
+impl HashLife {
+  pub fn new(rule: &Rule) -> Result<Self, UnsupportedRule> {
+    if rule.larger_than_life.is_some() {
+      return Err(UnsupportedRule::LargerThanLife);
+    }
+    if rule.states > 2 {
+      return Err(UnsupportedRule::Generations);
+    }
+    if rule.birth & 1 != 0 {
+      return Err(UnsupportedRule::BirthOnZero);
+    }
+
+    let leaf = |population| Node {
+      children: [DEAD; 4],
+      level: 0,
+      population,
+    };
+    let mut life = Self {
+      birth: rule.birth,
+      survival: rule.survival,
+      nodes: vec![leaf(0), leaf(1)],
+      ids: HashMap::default(),
+      results: HashMap::default(),
+      empty: vec![DEAD],
+      root: DEAD,
+      origin: I64Vec2::ZERO,
+      generation: 0,
+    };
+    life.root = life.empty(MIN_LEVEL);
+    Ok(life)
+  }
+
+  /// Imports a universe of `size` cells from the 1-bit storage buffer layout, with
+  /// `buffer_size_x` words per row. Its top left cell ends up at the origin.
+  pub fn from_words(
+    rule: &Rule,
+    size: UVec2,
+    buffer_size_x: u32,
+    words: &[u32],
+  ) -> Result<Self, UnsupportedRule> {
+    let mut life = Self::new(rule)?;
+    let side = size.max_element().next_power_of_two();
+    let level = (side.trailing_zeros() as u8).max(MIN_LEVEL);
+
+    let source = WordSource {
+      size,
+      buffer_size_x,
+      words,
+    };
+    life.root = life.build(&source, level, 0, 0);
+    Ok(life)
+  }
+
+  pub fn generation(&self) -> u64 {
+    self.generation
+  }
+
+  pub fn set_generation(&mut self, generation: u64) {
+    self.generation = generation;
+  }
+
+  /// Number of live cells, saturating at `u64::MAX`.
+  pub fn population(&self) -> u64 {
+    self.nodes[self.root as usize].population
+  }
+
+  pub fn get(&self, position: I64Vec2) -> bool {
+    let level = self.level(self.root);
+    let relative = position - self.origin;
+    if relative.cmplt(I64Vec2::ZERO).any() || relative.cmpge(I64Vec2::splat(1 << level)).any() {
+      return false;
+    }
+
+    let mut node = self.root;
+    for level in (0..level).rev() {
+      let quadrant = ((relative.x >> level) & 1) + 2 * ((relative.y >> level) & 1);
+      node = self.nodes[node as usize].children[quadrant as usize];
+    }
+    node == ALIVE
+  }
+
+  pub fn set(&mut self, position: I64Vec2, alive: bool) {
+    loop {
+      let relative = position - self.origin;
+      let side = 1 << self.level(self.root);
+      if relative.cmpge(I64Vec2::ZERO).all() && relative.cmplt(I64Vec2::splat(side)).all() {
+        break;
+      }
+      self.expand();
+    }
+
+    let relative = position - self.origin;
+    self.root = self.set_in(self.root, relative.x as u64, relative.y as u64, alive);
+  }
+
+  /// Advances the universe by `2^log2` generations.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `log2` is above `MAX_STEP_LOG2`.
+  pub fn step_pow2(&mut self, log2: u32) {
+    assert!(
+      log2 <= MAX_STEP_LOG2,
+      "step of 2^{log2} generations is too large"
+    );
+
+    // the root must contain everything the pattern can reach at the speed of light
+    while u32::from(self.level(self.root)) < log2 + 2 || !self.is_centered(self.root) {
+      self.expand();
+    }
+    self.expand();
+
+    let level = self.level(self.root);
+    self.root = self.successor(self.root, log2 as u8);
+    self.origin += I64Vec2::splat(1 << (level - 2));
+    self.generation = self.generation.wrapping_add(1 << log2);
+
+    while self.level(self.root) > MIN_LEVEL && self.is_centered(self.root) {
+      let level = self.level(self.root);
+      self.root = self.center(self.root);
+      self.origin += I64Vec2::splat(1 << (level - 2));
+    }
+
+    if self.nodes.len() > GC_THRESHOLD {
+      self.collect_garbage();
+    }
+  }
+
+  /// Advances the universe by any number of generations, as a series of power of two steps.
+  pub fn advance(&mut self, generations: u64) {
+    let mut remaining = generations;
+    while remaining > 0 {
+      let log2 = (63 - remaining.leading_zeros()).min(MAX_STEP_LOG2);
+      self.step_pow2(log2);
+      remaining -= 1 << log2;
+    }
+  }
+
+  /// The smallest rectangle holding every live cell, as its top left cell and size.
+  pub fn live_bounds(&self) -> Option<(I64Vec2, UVec2)> {
+    let extent = |axis, max| {
+      let mut memo = HashMap::default();
+      self.extent(self.root, axis, max, &mut memo)
+    };
+    let min = I64Vec2::new(extent(0, false)? as i64, extent(1, false)? as i64);
+    let max = I64Vec2::new(extent(0, true)? as i64, extent(1, true)? as i64);
+    let size = (max - min + 1).clamp(I64Vec2::ZERO, I64Vec2::splat(u32::MAX.into()));
+    Some((self.origin + min, UVec2::new(size.x as u32, size.y as u32)))
+  }
+
+  /// Exports the `size` cells starting at `origin` into the 1-bit storage buffer layout,
+  /// with `size.x.div_ceil(32)` words per row.
+  pub fn to_words(&self, origin: I64Vec2, size: UVec2) -> Vec<u32> {
+    let buffer_size_x = size.x.div_ceil(32);
+    let mut words = vec![0; (buffer_size_x * size.y) as usize];
+    self.for_each_live(origin, size, &mut |x, y| {
+      words[(y * buffer_size_x + x / 32) as usize] |= 1 << (31 - x % 32);
+    });
+    words
+  }
+
+  /// Exports the `size` cells starting at `origin` into a pattern that remembers its
+  /// position and generation.
+  pub fn to_pattern(&self, origin: I64Vec2, size: UVec2) -> Pattern {
+    let mut pattern = Pattern::new(size.x, size.y);
+    self.for_each_live(origin, size, &mut |x, y| pattern.set(x, y, 1));
+    pattern.position = IVec2::try_from(origin).ok();
+    pattern.generation = Some(self.generation);
+    pattern
+  }
+
+  /// Imports a macrocell file with its root centered on the origin, like Golly does. The
+  /// rule of the file is used when it has one.
+  pub fn from_macrocell(macrocell: &Macrocell, rule: &Rule) -> Result<Self, UnsupportedRule> {
+    let mut life = Self::new(&macrocell.rule.unwrap_or(*rule))?;
+    let leaf = |state: u8| match state {
+      0 => Ok(DEAD),
+      1 => Ok(ALIVE),
+      _ => Err(UnsupportedRule::Generations),
+    };
+
+    // children always come before their parents, so one pass builds the whole tree
+    let mut ids = Vec::with_capacity(macrocell.nodes.len());
+    for node in &macrocell.nodes {
+      let id = match *node {
+        MacrocellNode::Leaf(rows) => life.build_leaf(rows, 3, 0, 0),
+        MacrocellNode::Cells(states) => {
+          let children = [
+            leaf(states[0])?,
+            leaf(states[1])?,
+            leaf(states[2])?,
+            leaf(states[3])?,
+          ];
+          life.join(children)
+        }
+        MacrocellNode::Node { level, children } => {
+          let children = children.map(|child| match child {
+            0 => life.empty(level - 1),
+            _ => ids[child as usize - 1],
+          });
+          life.join(children)
+        }
+      };
+      ids.push(id);
+    }
+
+    life.root = *ids.last().unwrap();
+    let level = life.level(life.root);
+    life.origin = -I64Vec2::splat(1 << (level - 1));
+    while life.level(life.root) < MIN_LEVEL {
+      life.expand();
+    }
+    life.generation = macrocell.generation.unwrap_or(0);
+    Ok(life)
+  }
+
+  /// Exports the quadtree as a macrocell file. Golly centers the root on the origin when
+  /// loading it, so the live cells keep their relative positions but not their coordinates.
+  pub fn to_macrocell(&self) -> Macrocell {
+    let mut nodes = vec![];
+    let mut ids = HashMap::default();
+    if self.write_node(self.root, &mut nodes, &mut ids) == 0 {
+      // the file still needs a root to tell the size
+      nodes.push(match self.level(self.root) {
+        3 => MacrocellNode::Leaf([0; 8]),
+        level => MacrocellNode::Node {
+          level,
+          children: [0; 4],
+        },
+      });
+    }
+
+    Macrocell {
+      nodes,
+      rule: Some(Rule {
+        birth: self.birth,
+        survival: self.survival,
+        ..Rule::default()
+      }),
+      generation: Some(self.generation),
+      comments: vec![],
+    }
+  }
+
+  fn level(&self, node: NodeId) -> u8 {
+    self.nodes[node as usize].level
+  }
+
+  fn children(&self, node: NodeId) -> [NodeId; 4] {
+    self.nodes[node as usize].children
+  }
+
+  fn population_of(&self, node: NodeId) -> u64 {
+    self.nodes[node as usize].population
+  }
+
+  /// The canonical node made of the four quadrants.
+  fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+    if let Some(&id) = self.ids.get(&children) {
+      return id;
+    }
+
+    let population = children.iter().fold(0u64, |sum, &child| {
+      sum.saturating_add(self.population_of(child))
+    });
+    let id = self.nodes.len() as NodeId;
+    self.nodes.push(Node {
+      children,
+      level: self.level(children[0]) + 1,
+      population,
+    });
+    self.ids.insert(children, id);
+    id
+  }
+
+  fn empty(&mut self, level: u8) -> NodeId {
+    while self.empty.len() <= level as usize {
+      let below = *self.empty.last().unwrap();
+      let node = self.join([below; 4]);
+      self.empty.push(node);
+    }
+    self.empty[level as usize]
+  }
+
+  /// The node one level below, made of the innermost quadrants of the quadrants.
+  fn center(&mut self, node: NodeId) -> NodeId {
+    let [nw, ne, sw, se] = self.children(node);
+    self.join([
+      self.children(nw)[3],
+      self.children(ne)[2],
+      self.children(sw)[1],
+      self.children(se)[0],
+    ])
+  }
+
+  /// Whether all live cells are inside the center of the node, so it can be shrunk to it.
+  fn is_centered(&self, node: NodeId) -> bool {
+    let children = self.children(node);
+    (0..4).all(|i| {
+      let inner = self.children(children[i])[3 - i];
+      self.population_of(children[i]) == self.population_of(inner)
+    })
+  }
+
+  /// Doubles the size of the root, keeping the cells in its center.
+  fn expand(&mut self) {
+    let level = self.level(self.root);
+    let empty = self.empty(level - 1);
+    let [nw, ne, sw, se] = self.children(self.root);
+
+    let quadrants = [
+      [empty, empty, empty, nw],
+      [empty, empty, ne, empty],
+      [empty, sw, empty, empty],
+      [se, empty, empty, empty],
+    ]
+    .map(|children| self.join(children));
+    self.root = self.join(quadrants);
+    self.origin -= I64Vec2::splat(1 << (level - 1));
+  }
+
+  /// The center of `node` advanced by `2^log2` generations, where `log2` is at most two
+  /// below the level of `node`.
+  fn successor(&mut self, node: NodeId, log2: u8) -> NodeId {
+    let level = self.level(node);
+    if self.population_of(node) == 0 {
+      return self.empty(level - 1);
+    }
+    if let Some(&result) = self.results.get(&(node, log2)) {
+      return result;
+    }
+
+    let result = if level == 2 {
+      self.step_leaves(node)
+    } else {
+      let [nw, ne, sw, se] = self.children(node);
+      // the nine overlapping squares of half the size, row by row
+      let horizontal = |life: &mut Self, west: NodeId, east: NodeId| {
+        let [_, west_ne, _, west_se] = life.children(west);
+        let [east_nw, _, east_sw, _] = life.children(east);
+        life.join([west_ne, east_nw, west_se, east_sw])
+      };
+      let vertical = |life: &mut Self, north: NodeId, south: NodeId| {
+        let [_, _, north_sw, north_se] = life.children(north);
+        let [south_nw, south_ne, _, _] = life.children(south);
+        life.join([north_sw, north_se, south_nw, south_ne])
+      };
+      let squares = [
+        nw,
+        horizontal(self, nw, ne),
+        ne,
+        vertical(self, nw, sw),
+        self.center(node),
+        vertical(self, ne, se),
+        sw,
+        horizontal(self, sw, se),
+        se,
+      ];
+
+      // at full speed both halves advance, otherwise only the second one does
+      let full_speed = log2 == level - 2;
+      let inner_log2 = if full_speed { log2 - 1 } else { log2 };
+      let s = squares.map(|square| match full_speed {
+        true => self.successor(square, inner_log2),
+        false => self.center(square),
+      });
+
+      let quadrants = [
+        [s[0], s[1], s[3], s[4]],
+        [s[1], s[2], s[4], s[5]],
+        [s[3], s[4], s[6], s[7]],
+        [s[4], s[5], s[7], s[8]],
+      ]
+      .map(|children| {
+        let quadrant = self.join(children);
+        self.successor(quadrant, inner_log2)
+      });
+      self.join(quadrants)
+    };
+
+    self.results.insert((node, log2), result);
+    result
+  }
+
+  /// Applies the rule once to the center of a 4x4 node.
+  fn step_leaves(&mut self, node: NodeId) -> NodeId {
+    // bit `x + 4 * y` holds the cell at `(x, y)`
+    let mut cells = 0u16;
+    for (quadrant, child) in self.children(node).into_iter().enumerate() {
+      for (i, leaf) in self.children(child).into_iter().enumerate() {
+        let x = (quadrant & 1) * 2 + (i & 1);
+        let y = (quadrant >> 1) * 2 + (i >> 1);
+        cells |= u16::from(leaf == ALIVE) << (x + 4 * y);
+      }
+    }
+
+    let leaves = [5, 6, 9, 10].map(|cell: u32| {
+      // the 3x3 block around cell 5 without its center, moved over to `cell`
+      let neighbors = 0x0757u16 << (cell - 5);
+      let count = (cells & neighbors).count_ones();
+      let mask = match (cells >> cell) & 1 {
+        1 => self.survival,
+        _ => self.birth,
+      };
+      if (mask >> count) & 1 != 0 {
+        ALIVE
+      } else {
+        DEAD
+      }
+    });
+    self.join(leaves)
+  }
+
+  fn set_in(&mut self, node: NodeId, x: u64, y: u64, alive: bool) -> NodeId {
+    let level = self.level(node);
+    if level == 0 {
+      return if alive { ALIVE } else { DEAD };
+    }
+
+    let half = 1 << (level - 1);
+    let quadrant = usize::from(x >= half) + 2 * usize::from(y >= half);
+    let mut children = self.children(node);
+    children[quadrant] = self.set_in(children[quadrant], x % half, y % half, alive);
+    self.join(children)
+  }
+
+  fn build(&mut self, source: &WordSource, level: u8, x: u32, y: u32) -> NodeId {
+    if x >= source.size.x || y >= source.size.y {
+      return self.empty(level);
+    }
+    if level == 0 {
+      return if source.get(x, y) { ALIVE } else { DEAD };
+    }
+    // a square of 32 cells on a side covers exactly one word per row
+    if level == 5 && source.is_empty_word_column(x, y) {
+      return self.empty(level);
+    }
+
+    let half = 1 << (level - 1);
+    let children = [(0, 0), (half, 0), (0, half), (half, half)]
+      .map(|(dx, dy)| self.build(source, level - 1, x + dx, y + dy));
+    self.join(children)
+  }
+
+  /// The node for the square of `2^level` cells at `(x, y)` of an 8x8 macrocell leaf.
+  fn build_leaf(&mut self, rows: [u8; 8], level: u8, x: u32, y: u32) -> NodeId {
+    if level == 0 {
+      let alive = (rows[y as usize] >> (7 - x)) & 1 != 0;
+      return if alive { ALIVE } else { DEAD };
+    }
+
+    let half = 1 << (level - 1);
+    let children = [(0, 0), (half, 0), (0, half), (half, half)]
+      .map(|(dx, dy)| self.build_leaf(rows, level - 1, x + dx, y + dy));
+    self.join(children)
+  }
+
+  /// Appends the macrocell nodes of `node` after those of its children and returns its
+  /// 1-based index, 0 for an empty node.
+  fn write_node(
+    &self,
+    node: NodeId,
+    nodes: &mut Vec<MacrocellNode>,
+    ids: &mut HashMap<NodeId, u32>,
+  ) -> u32 {
+    if self.population_of(node) == 0 {
+      return 0;
+    }
+    if let Some(&id) = ids.get(&node) {
+      return id;
+    }
+
+    let level = self.level(node);
+    let macrocell_node = match level {
+      3 => {
+        let mut rows = [0u8; 8];
+        self.visit(
+          node,
+          I64Vec2::ZERO,
+          I64Vec2::ZERO,
+          I64Vec2::splat(8),
+          &mut |x, y| {
+            rows[y as usize] |= 1 << (7 - x);
+          },
+        );
+        MacrocellNode::Leaf(rows)
+      }
+      _ => {
+        let children = self
+          .children(node)
+          .map(|child| self.write_node(child, nodes, ids));
+        MacrocellNode::Node { level, children }
+      }
+    };
+
+    nodes.push(macrocell_node);
+    let id = nodes.len() as u32;
+    ids.insert(node, id);
+    id
+  }
+
+  /// The lowest (or highest, with `max`) coordinate of a live cell along `axis` relative to
+  /// the node.
+  fn extent(
+    &self,
+    node: NodeId,
+    axis: usize,
+    max: bool,
+    memo: &mut HashMap<NodeId, Option<u64>>,
+  ) -> Option<u64> {
+    let Node {
+      children,
+      level,
+      population,
+    } = self.nodes[node as usize];
+    if population == 0 {
+      return None;
+    }
+    if level == 0 {
+      return Some(0);
+    }
+    if let Some(&extent) = memo.get(&node) {
+      return extent;
+    }
+
+    let half = 1 << (level - 1);
+    let sides: [usize; 2] = if max { [1, 0] } else { [0, 1] };
+    let mut extent = None;
+    for side in sides {
+      let extents = (0..4)
+        .filter(|i| (i >> axis) & 1 == side)
+        .filter_map(|i| self.extent(children[i], axis, max, memo))
+        .map(|value| value + side as u64 * half);
+      extent = if max { extents.max() } else { extents.min() };
+      if extent.is_some() {
+        break;
+      }
+    }
+
+    memo.insert(node, extent);
+    extent
+  }
+
+  fn for_each_live(&self, origin: I64Vec2, size: UVec2, f: &mut dyn FnMut(u32, u32)) {
+    let end = origin + size.as_i64vec2();
+    self.visit(self.root, self.origin, origin, end, f);
+  }
+
+  fn visit(
+    &self,
+    node: NodeId,
+    position: I64Vec2,
+    start: I64Vec2,
+    end: I64Vec2,
+    f: &mut dyn FnMut(u32, u32),
+  ) {
+    let Node {
+      children,
+      level,
+      population,
+    } = self.nodes[node as usize];
+    let node_end = position + I64Vec2::splat(1 << level);
+    if population == 0 || node_end.cmple(start).any() || position.cmpge(end).any() {
+      return;
+    }
+    if level == 0 {
+      let cell = position - start;
+      f(cell.x as u32, cell.y as u32);
+      return;
+    }
+
+    let half = 1 << (level - 1);
+    for (i, child) in children.into_iter().enumerate() {
+      let offset = I64Vec2::new((i & 1) as i64, (i >> 1) as i64) * half;
+      self.visit(child, position + offset, start, end, f);
+    }
+  }
+
+  /// Rebuilds the arena from the nodes reachable from the root and forgets memoized
+  /// results.
+  fn collect_garbage(&mut self) {
+    let old = mem::take(&mut self.nodes);
+    self.nodes.extend_from_slice(&old[..2]);
+    self.ids.clear();
+    self.results.clear();
+    self.empty = vec![DEAD];
+
+    let mut remap = HashMap::default();
+    self.root = self.copy_node(&old, self.root, &mut remap);
+  }
+
+  fn copy_node(
+    &mut self,
+    old: &[Node],
+    node: NodeId,
+    remap: &mut HashMap<NodeId, NodeId>,
+  ) -> NodeId {
+    if node == DEAD || node == ALIVE {
+      return node;
+    }
+    if let Some(&copy) = remap.get(&node) {
+      return copy;
+    }
+
+    let children = old[node as usize]
+      .children
+      .map(|child| self.copy_node(old, child, remap));
+    let copy = self.join(children);
+    remap.insert(node, copy);
+    copy
+  }
+}
+
+/// A universe in the 1-bit storage buffer layout.
+struct WordSource<'a> {
+  size: UVec2,
+  buffer_size_x: u32,
+  words: &'a [u32],
+}
+
+impl WordSource<'_> {
+  fn get(&self, x: u32, y: u32) -> bool {
+    let word = self.words[(y * self.buffer_size_x + x / 32) as usize];
+    (word >> (31 - x % 32)) & 1 != 0
+  }
+
+  /// Whether the word at column `x` is empty in the 32 rows from `y` on.
+  fn is_empty_word_column(&self, x: u32, y: u32) -> bool {
+    (y..(y + 32).min(self.size.y))
+      .all(|y| self.words[(y * self.buffer_size_x + x / 32) as usize] == 0)
+  }
+}
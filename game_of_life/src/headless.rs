@@ -0,0 +1,253 @@
+use std::{
+  fmt::Write as _,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::Arc,
+};
+
+use bevy::{
+  app::AppExit,
+  ecs::{
+    event::{EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::{error, info},
+  math::{UVec2, Vec2},
+};
+
+use crate::{
+  cpu::CpuUniverse,
+  data_structs::{Generation, GenerationLimit, Params, Topology},
+  export::write_pattern_file,
+  loader::{LoadPattern, pattern_offset, read_pattern},
+  pattern::Pattern,
+  readback::{RequestSnapshot, UniverseSnapshot},
+  rule::Rule,
+};
+
+/// Where the generations of a headless run are computed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeadlessBackend {
+  /// The compute shaders, on a render device without a surface.
+  #[default]
+  Gpu,
+  /// `CpuUniverse`, for machines without a GPU.
+  Cpu,
+}
+
+/// The universe a headless run starts from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeadlessStart {
+  /// A random soup, the same for both backends.
+  Seed(u32),
+  /// A pattern file, placed where it was exported from or in the middle of the universe.
+  Pattern(PathBuf),
+}
+
+/// A batch job run without a window: `generations` generations from `start`, then the
+/// final state is written to `output` and the population every `sample_interval`
+/// generations to `statistics` as CSV.
+#[derive(Resource, Clone, Debug)]
+pub struct HeadlessRun {
+  pub backend: HeadlessBackend,
+  pub start: HeadlessStart,
+  pub generations: u64,
+  /// Size of the universe in cells.
+  pub size: UVec2,
+  /// Written as plaintext, macrocell or RLE depending on the extension, like an
+  /// `ExportPattern`.
+  pub output: PathBuf,
+  pub statistics: PathBuf,
+  pub sample_interval: u64,
+}
+
+impl Default for HeadlessRun {
+  fn default() -> Self {
+    Self {
+      backend: HeadlessBackend::default(),
+      start: HeadlessStart::Seed(0),
+      generations: 1000,
+      size: UVec2::splat(1024),
+      output: "final.rle".into(),
+      statistics: "population.csv".into(),
+      sample_interval: 100,
+    }
+  }
+}
+
+/// Population samples of the GPU run so far, each taken from a snapshot at the generation
+/// the render graph node was stopped at.
+#[derive(Resource, Default)]
+pub struct HeadlessProgress {
+  /// Generation to stop at, known once the starting universe has been read back.
+  target: Option<u64>,
+  requested: bool,
+  samples: Vec<(u64, u64)>,
+}
+
+/// Sends the starting pattern of a GPU run to the loader.
+pub fn start_gpu_run(
+  run: Res<HeadlessRun>,
+  params: Res<Params>,
+  mut loads: EventWriter<LoadPattern>,
+  mut exit: EventWriter<AppExit>,
+) {
+  let HeadlessStart::Pattern(path) = &run.start else {
+    return;
+  };
+  let universe = UVec2::new(params.cell_count_x, params.buffer_size_y);
+  let center = Vec2::new(params.center_x, params.center_y);
+  match load_start_pattern(path, universe, center) {
+    Ok((pattern, offset)) => {
+      loads.write(LoadPattern {
+        pattern: Arc::new(pattern),
+        offset,
+      });
+    }
+    Err(err) => {
+      error!("Failed to load {}: {err}", path.display());
+      exit.write(AppExit::error());
+    }
+  }
+}
+
+/// Lets the render graph node run up to the next sample, reads the universe back once it
+/// got there and finishes the run at the last generation.
+pub fn drive_gpu_run(
+  run: Res<HeadlessRun>,
+  generation: Res<Generation>,
+  mut limit: ResMut<GenerationLimit>,
+  mut progress: ResMut<HeadlessProgress>,
+  mut snapshots: EventReader<UniverseSnapshot>,
+  mut requests: EventWriter<RequestSnapshot>,
+  mut exit: EventWriter<AppExit>,
+) {
+  if let Some(snapshot) = snapshots.read().last() {
+    progress.requested = false;
+    let target = *progress
+      .target
+      .get_or_insert(snapshot.generation.saturating_add(run.generations));
+    progress
+      .samples
+      .push((snapshot.generation, snapshot.population()));
+
+    if snapshot.generation >= target {
+      exit.write(finish_run(&run, snapshot, &progress.samples));
+      return;
+    }
+    limit.0 = snapshot
+      .generation
+      .saturating_add(run.sample_interval.max(1))
+      .min(target);
+  }
+
+  if !progress.requested && generation.get() >= limit.0 {
+    requests.write(RequestSnapshot);
+    progress.requested = true;
+  }
+}
+
+/// Runs the whole job on `CpuUniverse` in one go.
+pub fn run_cpu(
+  run: Res<HeadlessRun>,
+  rule: Res<Rule>,
+  topology: Res<Topology>,
+  mut exit: EventWriter<AppExit>,
+) {
+  let mut rule = *rule;
+  let mut generation = 0;
+  let mut universe = match &run.start {
+    HeadlessStart::Seed(seed) => {
+      let mut universe = CpuUniverse::new(run.size, &rule, *topology);
+      universe.randomize(*seed);
+      universe
+    }
+    HeadlessStart::Pattern(path) => {
+      let center = run.size.as_vec2() / 2.0;
+      let (pattern, offset) = match load_start_pattern(path, run.size, center) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+          error!("Failed to load {}: {err}", path.display());
+          exit.write(AppExit::error());
+          return;
+        }
+      };
+      rule = pattern.rule.unwrap_or(rule);
+      generation = pattern.generation.unwrap_or(0);
+      let mut universe = CpuUniverse::new(run.size, &rule, *topology);
+      universe.load(&pattern, offset);
+      universe
+    }
+  };
+
+  let start = generation;
+  let target = start.saturating_add(run.generations);
+  let mut samples = vec![(start, universe.snapshot(start).population())];
+  while generation < target {
+    universe.step();
+    generation += 1;
+    if (generation - start) % run.sample_interval.max(1) == 0 || generation == target {
+      samples.push((generation, universe.snapshot(generation).population()));
+    }
+  }
+
+  exit.write(finish_run(&run, &universe.snapshot(generation), &samples));
+}
+
+/// Reads the starting pattern and works out where it goes, checking that it fits.
+fn load_start_pattern(
+  path: &Path,
+  universe: UVec2,
+  center: Vec2,
+) -> Result<(Pattern, UVec2), Box<dyn std::error::Error>> {
+  let source = fs::read_to_string(path)?;
+  let extension = path.extension().and_then(|extension| extension.to_str());
+  let pattern = read_pattern(&source, extension, universe)?;
+
+  let offset = pattern_offset(&pattern, center);
+  let size = UVec2::new(pattern.width, pattern.height);
+  if (offset + size).cmpgt(universe).any() {
+    return Err(
+      format!(
+        "a {}x{} pattern at {offset} does not fit into the {}x{} universe",
+        size.x, size.y, universe.x, universe.y
+      )
+      .into(),
+    );
+  }
+  Ok((pattern, offset))
+}
+
+/// Writes the final state and the population samples, returning how the app should exit.
+fn finish_run(run: &HeadlessRun, snapshot: &UniverseSnapshot, samples: &[(u64, u64)]) -> AppExit {
+  let mut pattern = snapshot.to_pattern(snapshot.live_bounds().unwrap_or_default());
+  pattern.rule = Some(snapshot.rule);
+
+  let result =
+    write_pattern_file(&run.output, &pattern).and_then(|()| write_statistics(run, samples));
+  if let Err(err) = result {
+    error!("Failed to write the results: {err}");
+    return AppExit::error();
+  }
+
+  let populations = samples.iter().map(|&(_, population)| population);
+  info!(
+    "Generation {}: population {} (min {}, max {}), written to {} and {}",
+    snapshot.generation,
+    snapshot.population(),
+    populations.clone().min().unwrap_or_default(),
+    populations.max().unwrap_or_default(),
+    run.output.display(),
+    run.statistics.display()
+  );
+  AppExit::Success
+}
+
+fn write_statistics(run: &HeadlessRun, samples: &[(u64, u64)]) -> io::Result<()> {
+  let mut csv = String::from("generation,population\n");
+  for (generation, population) in samples {
+    writeln!(csv, "{generation},{population}").unwrap();
+  }
+  fs::write(&run.statistics, csv)
+}
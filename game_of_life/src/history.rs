@@ -0,0 +1,278 @@
+use std::collections::VecDeque;
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::info,
+  render::{
+    Extract,
+    extract_resource::ExtractResource,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages},
+    renderer::RenderDevice,
+  },
+};
+
+use crate::{bind_group::GLBufferLayout, controls::MAX_GENERATIONS_PER_FRAME};
+
+/// How many past generations are kept on the GPU to rewind to.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, ExtractResource)]
+pub struct History {
+  /// Generations between keyframes, 1 keeps every generation. Rewinding to a generation
+  /// between keyframes re-simulates it from the keyframe before it, within one frame, so
+  /// this is clamped to `MAX_GENERATIONS_PER_FRAME`.
+  pub keyframe_interval: u32,
+  /// Most keyframes kept, 0 turns rewinding off.
+  pub max_keyframes: u32,
+  /// Most bytes of GPU memory the keyframes take up. The device's largest buffer caps this
+  /// as well, since they all live in one.
+  pub max_bytes: u64,
+}
+
+impl Default for History {
+  fn default() -> Self {
+    Self {
+      keyframe_interval: 1,
+      max_keyframes: 1000,
+      max_bytes: 256 << 20,
+    }
+  }
+}
+
+/// Goes back `generations` in the history, or as far back as it reaches.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct RewindGenerations {
+  pub generations: u64,
+}
+
+/// A generation saved in the history.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keyframe {
+  pub generation: u64,
+  /// Index of the universe-sized slot of the history buffer the generation is in.
+  pub slot: u32,
+}
+
+/// The generations the history holds, oldest first, and the slots they are in.
+#[derive(Clone, Debug, Default)]
+pub struct Keyframes {
+  keyframes: VecDeque<Keyframe>,
+  free: Vec<u32>,
+}
+
+impl Keyframes {
+  pub fn new(capacity: u32) -> Self {
+    Self {
+      keyframes: VecDeque::with_capacity(capacity as usize),
+      free: (0..capacity).rev().collect(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.keyframes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.keyframes.is_empty()
+  }
+
+  /// Most keyframes held at once.
+  pub fn capacity(&self) -> usize {
+    self.keyframes.len() + self.free.len()
+  }
+
+  /// The keyframes from oldest to newest.
+  pub fn iter(&self) -> impl Iterator<Item = Keyframe> + '_ {
+    self.keyframes.iter().copied()
+  }
+
+  pub fn oldest(&self) -> Option<Keyframe> {
+    self.keyframes.front().copied()
+  }
+
+  pub fn newest(&self) -> Option<Keyframe> {
+    self.keyframes.back().copied()
+  }
+
+  pub fn clear(&mut self) {
+    while self.forget_newest() {}
+  }
+
+  /// Picks the slot to save `generation` into. Keyframes at or after `generation` belong to
+  /// a timeline that has been replaced, e.g. by an edit, and are forgotten, as is the oldest
+  /// keyframe once the history is full. `None` if the history has no slots.
+  pub fn save(&mut self, generation: u64) -> Option<u32> {
+    while self
+      .newest()
+      .is_some_and(|keyframe| keyframe.generation >= generation)
+    {
+      self.forget_newest();
+    }
+    let slot = match self.free.pop() {
+      Some(slot) => slot,
+      None => self.keyframes.pop_front()?.slot,
+    };
+    self.keyframes.push_back(Keyframe { generation, slot });
+    Some(slot)
+  }
+
+  /// Forgets the keyframes after `target` and returns the newest one left, which `target` is
+  /// re-simulated from.
+  pub fn rewind_to(&mut self, target: u64) -> Option<Keyframe> {
+    while self
+      .newest()
+      .is_some_and(|keyframe| keyframe.generation > target)
+    {
+      self.forget_newest();
+    }
+    self.newest()
+  }
+
+  fn forget_newest(&mut self) -> bool {
+    let Some(keyframe) = self.keyframes.pop_back() else {
+      return false;
+    };
+    self.free.push(keyframe.slot);
+    true
+  }
+}
+
+/// Generations to rewind once the node gets to it.
+#[derive(Resource, Default)]
+pub struct PendingRewind(pub u64);
+
+/// A copy of the latest generation into the history, made after `after` generations of
+/// the frame's batch have been computed.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyframeSave {
+  pub after: u32,
+  pub slot: u32,
+}
+
+/// The history buffer with one universe-sized slot per keyframe, and the copies the render
+/// graph node makes into and out of it this frame.
+#[derive(Resource, Default)]
+pub struct GLHistory {
+  pub buffer: Option<Buffer>,
+  pub keyframes: Keyframes,
+  pub saves: Vec<KeyframeSave>,
+  /// The slot to copy back into the generation buffers before re-simulating.
+  pub restore: Option<u32>,
+  keyframe_interval: u32,
+  // what the buffer was allocated for
+  settings: Option<History>,
+  frame_size: u64,
+}
+
+impl GLHistory {
+  pub fn frame_size(&self) -> u64 {
+    self.frame_size
+  }
+
+  /// Plans the saves of a frame computing `batch` generations after `generation`. The
+  /// generation from before the batch is saved as well if the universe was edited or the
+  /// history just started. Only the generations still in the history at the end of the
+  /// batch are saved, the batch's later saves would push the others out again.
+  pub fn plan_saves(&mut self, generation: u64, batch: u32, edited: bool) {
+    if self.buffer.is_none() {
+      return;
+    }
+    let end = generation + u64::from(batch);
+    let kept = self.keyframes.capacity() as u64 * u64::from(self.keyframe_interval);
+    let still_kept = |generation: u64| end - generation < kept;
+
+    if (edited || self.keyframes.is_empty()) && still_kept(generation) {
+      self.save(0, generation);
+    }
+    for after in 1..=batch {
+      let generation = generation + u64::from(after);
+      if generation.is_multiple_of(u64::from(self.keyframe_interval)) && still_kept(generation) {
+        self.save(after, generation);
+      }
+    }
+  }
+
+  /// Starts over, saving the generation computed by the frame's first pass.
+  pub fn restart(&mut self, generation: u64) {
+    self.keyframes.clear();
+    if self.buffer.is_some() {
+      self.save(1, generation);
+    }
+  }
+
+  fn save(&mut self, after: u32, generation: u64) {
+    if let Some(slot) = self.keyframes.save(generation) {
+      self.saves.push(KeyframeSave { after, slot });
+    }
+  }
+
+  /// Plans going back `generations` from `generation`, returning the generation rewound to
+  /// and the number of generations to re-simulate from its keyframe.
+  pub fn plan_rewind(&mut self, generation: u64, generations: u64) -> Option<(u64, u32)> {
+    let oldest = self.keyframes.oldest()?;
+    let target = generation
+      .saturating_sub(generations)
+      .max(oldest.generation);
+    if target >= generation {
+      info!("Reached generation {generation}, the oldest one in the history");
+      return None;
+    }
+    let keyframe = self.keyframes.rewind_to(target)?;
+    self.restore = Some(keyframe.slot);
+    info!("Rewinding to generation {target}");
+    Some((target, (target - keyframe.generation) as u32))
+  }
+}
+
+pub fn extract_rewinds(
+  mut rewinds: Extract<EventReader<RewindGenerations>>,
+  mut pending: ResMut<PendingRewind>,
+) {
+  for rewind in rewinds.read() {
+    pending.0 = pending.0.saturating_add(rewind.generations);
+  }
+}
+
+/// (Re)allocates the history buffer for the universe's layout and the `History` settings,
+/// forgetting the keyframes.
+pub fn prepare_history(
+  mut history: ResMut<GLHistory>,
+  settings: Res<History>,
+  layout: Option<Res<GLBufferLayout>>,
+  device: Res<RenderDevice>,
+) {
+  let Some(layout) = layout else {
+    return;
+  };
+  let frame_size = u64::from(layout.buffer_size_x * layout.buffer_size_y) * 4;
+  if history.settings == Some(*settings) && history.frame_size == frame_size {
+    return;
+  }
+
+  let capacity = u64::from(settings.max_keyframes)
+    .min(settings.max_bytes / frame_size)
+    .min(device.limits().max_buffer_size / frame_size) as u32;
+  *history = GLHistory {
+    buffer: (capacity > 0).then(|| {
+      device.create_buffer(&BufferDescriptor {
+        label: Some("history"),
+        size: u64::from(capacity) * frame_size,
+        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+      })
+    }),
+    keyframes: Keyframes::new(capacity),
+    keyframe_interval: settings
+      .keyframe_interval
+      .clamp(1, MAX_GENERATIONS_PER_FRAME),
+    settings: Some(*settings),
+    frame_size,
+    ..Default::default()
+  };
+  info!(
+    "Keeping {capacity} keyframes every {} generations for rewinding",
+    history.keyframe_interval
+  );
+}
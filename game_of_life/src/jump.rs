@@ -0,0 +1,180 @@
+use std::{error::Error, fmt, sync::Arc};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::{info, warn},
+  math::{I64Vec2, UVec2},
+  tasks::{AsyncComputeTaskPool, Task, futures::check_ready},
+};
+
+use crate::{
+  data_structs::Topology,
+  hashlife::{HashLife, UnsupportedRule},
+  loader::LoadPattern,
+  pattern::Pattern,
+  readback::{RequestSnapshot, UniverseSnapshot},
+};
+
+/// Advances the universe by `generations` on the CPU with HashLife and writes the result
+/// back. HashLife runs on the unbounded plane, so jumps are refused for topologies that wrap
+/// or have alive edges. With dead edges the jump is approximate: cells that end up outside
+/// the universe are dropped, but they do not die at the edges on the way.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct JumpGenerations {
+  pub generations: u64,
+}
+
+/// How far the J key jumps ahead.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JumpControls {
+  pub generations: u64,
+}
+
+impl Default for JumpControls {
+  fn default() -> Self {
+    Self {
+      generations: 1_000_000,
+    }
+  }
+}
+
+/// Generations to jump once the next snapshot arrives.
+#[derive(Resource, Default)]
+pub struct PendingJump(Option<u64>);
+
+/// Why a jump could not run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JumpError {
+  Rule(UnsupportedRule),
+  /// A topology whose edges wrap around or are alive, which the unbounded plane HashLife
+  /// runs on has nothing in common with.
+  Topology(Topology),
+}
+
+impl From<UnsupportedRule> for JumpError {
+  fn from(err: UnsupportedRule) -> Self {
+    JumpError::Rule(err)
+  }
+}
+
+impl fmt::Display for JumpError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      JumpError::Rule(err) => err.fmt(f),
+      JumpError::Topology(topology) => write!(
+        f,
+        "HashLife runs on the unbounded plane and does not support the {topology:?} topology"
+      ),
+    }
+  }
+}
+
+impl Error for JumpError {}
+
+/// The jump running in the background.
+#[derive(Resource, Default)]
+pub struct JumpTask(Option<Task<Result<Pattern, JumpError>>>);
+
+pub fn request_jumps(
+  mut jumps: EventReader<JumpGenerations>,
+  mut pending: ResMut<PendingJump>,
+  mut requests: EventWriter<RequestSnapshot>,
+) {
+  for jump in jumps.read() {
+    let generations = pending.0.unwrap_or(0).saturating_add(jump.generations);
+    pending.0 = Some(generations);
+    requests.write(RequestSnapshot);
+  }
+}
+
+pub fn start_jumps(
+  mut snapshots: EventReader<UniverseSnapshot>,
+  mut pending: ResMut<PendingJump>,
+  mut task: ResMut<JumpTask>,
+  topology: Res<Topology>,
+) {
+  let Some(snapshot) = snapshots.read().last() else {
+    return;
+  };
+  if task.0.is_some() {
+    return;
+  }
+  let Some(generations) = pending.0.take() else {
+    return;
+  };
+
+  info!(
+    "Jumping {generations} generations from generation {}",
+    snapshot.generation
+  );
+  let snapshot = snapshot.clone();
+  let rule = snapshot.rule;
+  let topology = *topology;
+  if topology == Topology::DeadEdges {
+    warn!(
+      "The jump is approximate: cells crossing the dead edges keep evolving outside the \
+       universe and are only dropped at the end"
+    );
+  }
+  task.0 = Some(AsyncComputeTaskPool::get().spawn(async move {
+    if topology != Topology::DeadEdges {
+      return Err(JumpError::Topology(topology));
+    }
+    let mut life = HashLife::from_words(
+      &rule,
+      snapshot.size,
+      snapshot.buffer_size_x(),
+      snapshot.words(),
+    )?;
+    life.set_generation(snapshot.generation);
+    life.advance(generations);
+
+    // only the part of the live cells inside the universe can be written back
+    let Some((origin, size)) = life.live_bounds() else {
+      return Ok(life.to_pattern(I64Vec2::ZERO, UVec2::ZERO));
+    };
+    let start = origin.max(I64Vec2::ZERO);
+    let end = (origin + size.as_i64vec2()).min(snapshot.size.as_i64vec2());
+    let visible = (end - start).max(I64Vec2::ZERO);
+    Ok(life.to_pattern(start, UVec2::new(visible.x as u32, visible.y as u32)))
+  }));
+}
+
+pub fn finish_jumps(
+  mut task: ResMut<JumpTask>,
+  pending: Res<PendingJump>,
+  mut loads: EventWriter<LoadPattern>,
+  mut requests: EventWriter<RequestSnapshot>,
+) {
+  let Some(running) = task.0.as_mut() else {
+    return;
+  };
+  let Some(result) = check_ready(running) else {
+    return;
+  };
+  task.0 = None;
+  // the snapshots of jumps asked for while this one ran were dropped, the next one shows
+  // where this jump got to
+  if pending.0.is_some() {
+    requests.write(RequestSnapshot);
+  }
+
+  match result {
+    Ok(pattern) => {
+      info!(
+        "Jumped to generation {}",
+        pattern.generation.unwrap_or_default()
+      );
+      let offset = pattern.position.unwrap_or_default().as_uvec2();
+      loads.write(LoadPattern {
+        pattern: Arc::new(pattern),
+        offset,
+      });
+    }
+    Err(err) => warn!("Cannot jump: {err}"),
+  }
+}
@@ -1,114 +1,435 @@
 mod bind_group;
+mod census;
+mod controls;
+mod cpu;
 mod data_structs;
+mod export;
+mod hashlife;
+mod headless;
+mod history;
+mod jump;
+mod loader;
+mod oscillation;
+mod paint;
+pub mod pattern;
 mod pipeline;
+mod population;
+mod readback;
 mod render_graph;
+mod resize;
+mod rule;
+mod selection;
+mod stamp;
+mod telemetry;
 
 use std::time::Duration;
 
-use bind_group::{GLBindGroup, prepare_bind_group};
-use data_structs::{ComputeState, MainImage, Params, Telemetry};
+pub use census::{Census, CensusObject, CensusRun, ObjectKind, classify, wechsler};
+pub use controls::{
+  MAX_GENERATIONS_PER_FRAME, MAX_TARGET_TPS, Reseed, SimulationControl, SimulationControls,
+};
+pub use cpu::{CpuUniverse, Viewport};
+pub use data_structs::{AgeGradient, Axis, CellColoring, Topology};
+pub use export::{ExportBounds, ExportPattern};
+pub use hashlife::{HashLife, MAX_STEP_LOG2, UnsupportedRule};
+pub use headless::{HeadlessBackend, HeadlessRun, HeadlessStart};
+pub use history::{History, Keyframe, Keyframes, RewindGenerations};
+pub use jump::{JumpControls, JumpGenerations};
+pub use loader::LoadPattern;
+pub use oscillation::{Fingerprint, Fingerprints, OnSettled, OscillationDetection, Settled};
+pub use paint::{
+  Brush, BrushShape, CellEdit, CellEdits, MAX_CELL_EDITS, PaintCells, bresenham_line,
+};
+pub use population::{Population, PopulationCounting};
+pub use readback::{RequestSnapshot, UniverseSnapshot};
+pub use resize::{
+  Anchor, ResizeUniverse, UniverseLimits, UniverseResized, UniverseSizeError, check_universe_size,
+};
+pub use rule::{LargerThanLife, Neighborhood, Rule, RuleParseError};
+pub use selection::{Clipboard, ExportClipboard, RegionEdit, Selection};
+pub use stamp::{StampMode, StampTool, stamp_cells};
+pub use telemetry::{
+  Clock, Span, TIMING_SAMPLES, Telemetry, Timing, TimingRing, TimingSummary, diagnostic_path,
+};
+
+use bind_group::{bind_group_outdated, prepare_bind_group};
+use census::run_census;
+use controls::{apply_reseeds, apply_simulation_controls, extract_reseeds};
+use data_structs::{ComputeState, Generation, GenerationLimit, MainImage, Params};
+use export::{PendingExports, request_exports, write_exports};
+use headless::{HeadlessProgress, drive_gpu_run, run_cpu, start_gpu_run};
+use history::{GLHistory, PendingRewind, extract_rewinds, prepare_history};
+use jump::{JumpTask, PendingJump, finish_jumps, request_jumps, start_jumps};
+use loader::{
+  PendingPattern, apply_pattern_rule, extract_pattern_loads, handle_file_drop, write_pattern,
+};
 
 use bevy::{
   app::{Plugin, Startup, Update},
   asset::{Assets, RenderAssetUsages},
   core_pipeline::core_2d::Camera2d,
   ecs::{
-    event::EventReader,
+    event::{EventReader, EventWriter},
+    resource::Resource,
     schedule::{
       IntoScheduleConfigs,
-      common_conditions::{not, resource_exists},
+      common_conditions::{resource_exists, resource_exists_and_changed},
     },
-    system::{Commands, Res, ResMut, Single},
+    system::{Commands, Local, Res, ResMut, Single},
   },
   image::Image,
   input::{
     ButtonInput,
+    keyboard::KeyCode,
     mouse::{MouseButton, MouseScrollUnit, MouseWheel},
   },
-  log::info,
-  math::Vec2,
+  log::{error, info},
+  math::UVec2,
   render::{
-    Render, RenderApp, RenderSet,
+    ExtractSchedule, Render, RenderApp, RenderSet,
     extract_resource::ExtractResourcePlugin,
     render_graph::RenderGraph,
     render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+    renderer::{RenderDevice, render_system},
   },
   sprite::Sprite,
   time::common_conditions::on_timer,
   utils::default,
-  window::{Window, WindowMoved},
+  window::{
+    MonitorSelection, Window, WindowMode, WindowMoved, WindowResized, WindowScaleFactorChanged,
+  },
 };
+use bytemuck::Zeroable;
+use oscillation::{FingerprintReadback, handle_settled, receive_fingerprints};
+use paint::{GLEdits, PaintStroke, PendingPaint, extract_paint, handle_painting, prepare_edits};
 use pipeline::GLPipeline;
+use population::{
+  GLPopulation, PopulationReadback, map_population, print_population, receive_population,
+};
+use readback::{
+  GLReadbacks, Readback, map_readbacks, prepare_readback, receive_snapshots, request_snapshots,
+};
 use render_graph::{GLNode, GLNodeLabel};
+use resize::{PendingResize, apply_resizes, extract_resizes};
+use selection::{
+  ClipboardReadback, GLRegion, PendingRegionEdits, export_clipboard, extract_region_edits,
+  handle_selection, map_clipboard, prepare_region_edit, receive_clipboard,
+};
+use stamp::{GLGhost, handle_stamping, prepare_ghost};
+use telemetry::{
+  GLTimestamps, TelemetryReadback, map_timestamps, print_telemetry, receive_telemetry,
+};
 
 use crate::{
   bind_group::sync_params,
   data_structs::{GpuParamsHandle, MouseData, WindowData},
 };
 
-pub struct GameOfLifePlugin;
+pub struct GameOfLifePlugin {
+  /// Size of the display texture when there is no window to take it from.
+  pub resolution: UVec2,
+  /// Size of the universe in cells, which `ResizeUniverse` changes later on. Headless runs
+  /// take theirs from `HeadlessRun::size` instead.
+  pub size: UVec2,
+  /// Runs a batch job without a window instead of the interactive viewer.
+  pub headless: Option<HeadlessRun>,
+  /// Runs a soup census on the CPU instead, which takes precedence over `headless`.
+  pub census: Option<CensusRun>,
+}
+
+impl Default for GameOfLifePlugin {
+  fn default() -> Self {
+    Self {
+      resolution: UVec2::new(1280, 720),
+      size: UVec2::new(10000, 10000),
+      headless: None,
+      census: None,
+    }
+  }
+}
+
+/// Size of the display texture when there is no window.
+#[derive(Resource)]
+struct Resolution(UVec2);
+
+/// Size of the universe the viewer starts with.
+#[derive(Resource)]
+struct UniverseSize(UVec2);
+
+impl GameOfLifePlugin {
+  fn universe_size(&self) -> UVec2 {
+    self.headless.as_ref().map_or(self.size, |run| run.size)
+  }
+}
 
 impl Plugin for GameOfLifePlugin {
   fn build(&self, app: &mut bevy::app::App) {
-    info!("Building pipeline");
+    app.init_resource::<Rule>();
+    app.init_resource::<Topology>();
+    app.init_resource::<CellColoring>();
+    app.insert_resource(Resolution(self.resolution));
+    app.insert_resource(UniverseSize(self.universe_size()));
 
+    if let Some(census) = &self.census {
+      // like the CPU backend, without a render app
+      app.insert_resource(census.clone());
+      app.add_systems(Update, run_census);
+      return;
+    }
+    if let Some(run) = &self.headless {
+      app.insert_resource(run.clone());
+      if run.backend == HeadlessBackend::Cpu {
+        // no render app to set up, the whole run happens in the first update
+        app.add_systems(Update, run_cpu);
+        return;
+      }
+      // as fast as the GPU computes them
+      app.insert_resource(SimulationControls {
+        target_tps: 0,
+        ..Default::default()
+      });
+      app.insert_resource(History {
+        max_keyframes: 0,
+        ..Default::default()
+      });
+      app.insert_resource(OscillationDetection {
+        window: 0,
+        ..Default::default()
+      });
+      app.init_resource::<GenerationLimit>();
+      app.init_resource::<HeadlessProgress>();
+      app.add_systems(Startup, start_gpu_run.after(setup));
+      app.add_systems(Update, drive_gpu_run.after(receive_snapshots));
+    } else {
+      app.add_systems(Update, handle_mouse_input);
+      app.add_systems(Update, handle_keyboard_input);
+      app.add_systems(Update, handle_window_move);
+      app.add_systems(Update, (handle_window_resize, toggle_fullscreen));
+      app.add_systems(Update, handle_file_drop);
+      app.add_systems(Update, handle_painting);
+      app.add_systems(Update, handle_stamping);
+      app.add_systems(Update, handle_selection);
+      app.world_mut().commands().spawn(Camera2d);
+    }
+
+    info!("Building pipeline");
+    app.init_resource::<Generation>();
+    app.init_resource::<SimulationControls>();
+    app.init_resource::<History>();
+    app.init_resource::<PopulationCounting>();
+    app.init_resource::<Population>();
+    app.init_resource::<PopulationReadback>();
+    app.init_resource::<OscillationDetection>();
+    app.init_resource::<Fingerprints>();
+    app.init_resource::<FingerprintReadback>();
+    app.init_resource::<Telemetry>();
+    app.init_resource::<TelemetryReadback>();
+    app.init_resource::<Readback>();
+    app.init_resource::<PendingExports>();
+    app.init_resource::<JumpControls>();
+    app.init_resource::<PendingJump>();
+    app.init_resource::<JumpTask>();
+    app.init_resource::<Brush>();
+    app.init_resource::<PaintStroke>();
+    app.init_resource::<StampTool>();
+    app.init_resource::<Selection>();
+    app.init_resource::<Clipboard>();
+    app.init_resource::<ClipboardReadback>();
+    app.add_event::<LoadPattern>();
+    app.add_event::<RequestSnapshot>();
+    app.add_event::<UniverseSnapshot>();
+    app.add_event::<ExportPattern>();
+    app.add_event::<JumpGenerations>();
+    app.add_event::<SimulationControl>();
+    app.add_event::<RewindGenerations>();
+    app.add_event::<Population>();
+    app.add_event::<Fingerprint>();
+    app.add_event::<Settled>();
+    app.add_event::<Reseed>();
+    app.add_event::<PaintCells>();
+    app.add_event::<RegionEdit>();
+    app.add_event::<ExportClipboard>();
+    app.add_event::<ResizeUniverse>();
+    app.add_event::<UniverseResized>();
     app.add_systems(Startup, setup);
     app.add_systems(
       Update,
-      print_telemetry.run_if(on_timer(Duration::from_millis(1000))),
+      (
+        receive_telemetry,
+        print_telemetry.run_if(on_timer(Duration::from_millis(1000))),
+      )
+        .chain(),
+    );
+    app.add_systems(Update, finish_jumps.after(start_jumps));
+    app.add_systems(
+      Update,
+      (
+        receive_fingerprints,
+        handle_settled,
+        (apply_simulation_controls, apply_reseeds),
+      )
+        .chain(),
     );
-    app.add_systems(Update, handle_mouse_input);
-    app.add_systems(Update, handle_window_move);
-
-    app.world_mut().commands().spawn(Camera2d);
+    app.add_systems(
+      Update,
+      (
+        receive_population,
+        print_population.run_if(on_timer(Duration::from_millis(1000))),
+      )
+        .chain(),
+    );
+    app.add_systems(Update, (receive_clipboard, export_clipboard).chain());
+    app.add_systems(
+      Update,
+      (
+        (request_exports, request_jumps),
+        request_snapshots,
+        receive_snapshots,
+        (write_exports, start_jumps),
+      )
+        .chain(),
+    );
+    app.add_systems(
+      Update,
+      (
+        apply_pattern_rule,
+        apply_rule.run_if(resource_exists_and_changed::<Rule>),
+      )
+        .chain(),
+    );
+    app.add_systems(
+      Update,
+      apply_topology.run_if(resource_exists_and_changed::<Topology>),
+    );
+    app.add_systems(
+      Update,
+      apply_coloring.run_if(resource_exists_and_changed::<CellColoring>),
+    );
+    app.add_systems(Update, apply_resizes);
 
     app.add_plugins(ExtractResourcePlugin::<Params>::default());
+    app.add_plugins(ExtractResourcePlugin::<Rule>::default());
     app.add_plugins(ExtractResourcePlugin::<MainImage>::default());
     app.add_plugins(ExtractResourcePlugin::<ComputeState>::default());
-    app.add_plugins(ExtractResourcePlugin::<Telemetry>::default());
+    app.add_plugins(ExtractResourcePlugin::<TelemetryReadback>::default());
+    app.add_plugins(ExtractResourcePlugin::<Generation>::default());
+    app.add_plugins(ExtractResourcePlugin::<Readback>::default());
+    app.add_plugins(ExtractResourcePlugin::<GenerationLimit>::default());
+    app.add_plugins(ExtractResourcePlugin::<SimulationControls>::default());
+    app.add_plugins(ExtractResourcePlugin::<History>::default());
+    app.add_plugins(ExtractResourcePlugin::<PopulationCounting>::default());
+    app.add_plugins(ExtractResourcePlugin::<PopulationReadback>::default());
+    app.add_plugins(ExtractResourcePlugin::<OscillationDetection>::default());
+    app.add_plugins(ExtractResourcePlugin::<FingerprintReadback>::default());
+    app.add_plugins(ExtractResourcePlugin::<StampTool>::default());
+    app.add_plugins(ExtractResourcePlugin::<Clipboard>::default());
+    app.add_plugins(ExtractResourcePlugin::<ClipboardReadback>::default());
 
     let render_app = app.sub_app_mut(RenderApp);
+    render_app.init_resource::<PendingPattern>();
+    render_app.init_resource::<GLReadbacks>();
+    render_app.init_resource::<PendingPaint>();
+    render_app.init_resource::<PendingRegionEdits>();
+    render_app.init_resource::<PendingRewind>();
+    render_app.init_resource::<GLHistory>();
+    render_app.init_resource::<PendingResize>();
+    render_app.add_systems(
+      ExtractSchedule,
+      (
+        extract_pattern_loads,
+        extract_paint,
+        extract_region_edits,
+        extract_rewinds,
+        extract_reseeds,
+        extract_resizes,
+      ),
+    );
 
     info!("Preparing bind groups");
     render_app.add_systems(
       Render,
       (
-        prepare_bind_group.run_if(not(resource_exists::<GLBindGroup>)),
+        prepare_bind_group.run_if(bind_group_outdated),
         sync_params.run_if(resource_exists::<GpuParamsHandle>),
+        write_pattern.after(prepare_bind_group),
+        // a readback in the frame a pattern is written must see the pattern
+        prepare_readback.after(write_pattern),
+        prepare_edits.after(prepare_bind_group),
+        prepare_ghost,
+        prepare_region_edit.after(prepare_bind_group),
+        prepare_history.after(prepare_bind_group),
       )
         .in_set(RenderSet::PrepareBindGroups),
     );
+    render_app.add_systems(
+      Render,
+      (map_readbacks, map_clipboard, map_population, map_timestamps)
+        .after(render_system)
+        .in_set(RenderSet::Render),
+    );
 
     info!("Preparing render graph node");
     let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
-    render_graph.add_node(GLNodeLabel, GLNode::default());
+    let node = match self.headless {
+      Some(_) => GLNode::headless(),
+      None => GLNode::default(),
+    };
+    render_graph.add_node(GLNodeLabel, node);
     render_graph.add_node_edge(GLNodeLabel, bevy::render::graph::CameraDriverLabel);
 
     info!("Building pipeline done");
   }
 
   fn finish(&self, app: &mut bevy::app::App) {
-    let render_app = app.sub_app_mut(RenderApp);
+    // the CPU backend runs without a render app
+    let bits_per_cell = app
+      .world()
+      .get_resource::<Rule>()
+      .map_or(1, Rule::bits_per_cell);
+    let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+      return;
+    };
+    // the buffers are only allocated once the first frame is prepared, which would fail with
+    // a validation error far from the size that caused it
+    let limits = UniverseLimits::of(render_app.world().get_resource());
+    if let Err(err) = check_universe_size(self.universe_size(), bits_per_cell, limits) {
+      panic!("Cannot allocate the universe: {err}");
+    }
+    render_app.init_resource::<GLEdits>();
+    render_app.init_resource::<GLGhost>();
+    render_app.init_resource::<GLRegion>();
+    render_app.init_resource::<GLPopulation>();
+    render_app.init_resource::<GLTimestamps>();
     render_app.init_resource::<GLPipeline>();
   }
 }
 
-fn setup(mut commands: Commands, window: Single<&Window>, mut image_assets: ResMut<Assets<Image>>) {
+fn setup(
+  mut commands: Commands,
+  window: Option<Single<&Window>>,
+  resolution: Res<Resolution>,
+  size: Res<UniverseSize>,
+  headless: Option<Res<HeadlessRun>>,
+  mut image_assets: ResMut<Assets<Image>>,
+  rule: Res<Rule>,
+  topology: Res<Topology>,
+  coloring: Res<CellColoring>,
+) {
   commands.insert_resource(MouseData::default());
   commands.insert_resource(WindowData::default());
-  commands.insert_resource(Telemetry::default());
 
-  let resolution_x = window.physical_width();
-  let resolution_y = window.physical_height();
+  let (resolution_x, resolution_y) = match &window {
+    Some(window) => (window.physical_width(), window.physical_height()),
+    None => resolution.0.into(),
+  };
 
-  let cell_count_x = 10000;
-  let cell_count_y = 10000;
-  let buffer_size_x = (cell_count_x + 31) / 32;
+  let (cell_count_x, cell_count_y) = size.0.into();
+  let buffer_size_x = cell_count_x.div_ceil(32);
   let buffer_size_y = cell_count_y;
   let center_x = cell_count_x as f32 / 2.0;
   let center_y = cell_count_y as f32 / 2.0;
 
-  commands.insert_resource(Params {
+  let mut params = Params {
     buffer_size_x,
     buffer_size_y,
     resolution_x,
@@ -116,13 +437,43 @@ fn setup(mut commands: Commands, window: Single<&Window>, mut image_assets: ResM
     center_x,
     center_y,
     zoom: 4.0,
-    random_seed: rand::random::<u32>(),
-  });
+    random_seed: match headless.as_deref() {
+      Some(HeadlessRun {
+        start: HeadlessStart::Seed(seed),
+        ..
+      }) => *seed,
+      _ => rand::random::<u32>(),
+    },
+    bits_per_cell: 1,
+    cell_count_x,
+    topology: topology.shader_id(),
+    coloring: coloring.shader_id(),
+    ..Zeroable::zeroed()
+  };
+  params.set_rule(&rule);
+  commands.insert_resource(params);
 
+  let image_handle = image_assets.add(display_image(UVec2::new(resolution_x, resolution_y)));
+  commands.insert_resource(MainImage(image_handle.clone()));
+  // headless runs still bind the texture, they just never draw or show it
+  let Some(window) = window else {
+    return;
+  };
+
+  // the texture has a texel per physical pixel, stretched over the window's logical size
+  commands.spawn((Sprite {
+    image: image_handle.clone(),
+    custom_size: Some(window.size()),
+    ..default()
+  },));
+}
+
+/// The storage texture the display pass draws the universe into.
+fn display_image(resolution: UVec2) -> Image {
   let mut image = Image::new_fill(
     Extent3d {
-      width: resolution_x,
-      height: resolution_y,
+      width: resolution.x,
+      height: resolution.y,
       depth_or_array_layers: 1,
     },
     TextureDimension::D2,
@@ -132,32 +483,172 @@ fn setup(mut commands: Commands, window: Single<&Window>, mut image_assets: ResM
   );
   image.texture_descriptor.usage =
     TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+  image
+}
 
-  let image_handle = image_assets.add(image.clone());
+/// Reallocates the display texture for the new physical size of the window. The view keeps
+/// its center, so the visible part of the universe grows or shrinks around it.
+fn handle_window_resize(
+  mut commands: Commands,
+  window: Single<&Window>,
+  mut resized: EventReader<WindowResized>,
+  mut rescaled: EventReader<WindowScaleFactorChanged>,
+  mut params: ResMut<Params>,
+  mut image_assets: ResMut<Assets<Image>>,
+  mut sprite: Single<&mut Sprite>,
+) {
+  // both are sent for a single change at times, one reallocation covers them
+  if resized.read().count() + rescaled.read().count() == 0 {
+    return;
+  }
+  // the sprite covers the window in logical pixels, which a scale factor change alters
+  // even when the physical size stays the same
+  sprite.custom_size = Some(window.size());
+  let resolution = UVec2::new(window.physical_width(), window.physical_height());
+  // a minimized window has no pixels to draw into
+  if resolution == UVec2::new(params.resolution_x, params.resolution_y)
+    || resolution.cmpeq(UVec2::ZERO).any()
+  {
+    return;
+  }
 
-  commands.spawn((Sprite {
-    image: image_handle.clone(),
-    custom_size: Some(Vec2 {
-      x: resolution_x as f32,
-      y: resolution_y as f32,
-    }),
-    ..default()
-  },));
-  commands.insert_resource(MainImage(image_handle));
+  info!("Resizing the display to {}x{}", resolution.x, resolution.y);
+  params.resolution_x = resolution.x;
+  params.resolution_y = resolution.y;
+  // the old texture is dropped along with the last handle to it
+  let image_handle = image_assets.add(display_image(resolution));
+  commands.insert_resource(MainImage(image_handle.clone()));
+  sprite.image = image_handle;
 }
 
-fn print_telemetry(telemetry: Res<Telemetry>) {
-  let data = telemetry.ticks.lock().unwrap();
-  let avg_s = data.iter().sum::<f32>() / data.len() as f32;
-  let avg_ms = avg_s * 1000.0;
-  let avg_hz = 1.0 / avg_s;
-  info!("Average tick is {avg_ms:.2} milliseconds ({avg_hz:.2} ticks per second)");
+fn toggle_fullscreen(keys: Res<ButtonInput<KeyCode>>, mut window: Single<&mut Window>) {
+  if keys.just_pressed(KeyCode::F11) {
+    window.mode = match window.mode {
+      WindowMode::Windowed => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+      _ => WindowMode::Windowed,
+    };
+  }
+}
+
+/// Lays the buffers out for the new rule, or puts the previous rule back if the universe
+/// does not fit with the bits per cell the new one needs.
+fn apply_rule(
+  mut rule: ResMut<Rule>,
+  mut applied: Local<Option<Rule>>,
+  mut params: ResMut<Params>,
+  device: Option<Res<RenderDevice>>,
+) {
+  let size = UVec2::new(params.cell_count_x, params.buffer_size_y);
+  let limits = UniverseLimits::of(device.as_deref());
+  if let Err(err) = check_universe_size(size, rule.bits_per_cell(), limits) {
+    error!("Cannot switch to rule {}: {err}", *rule);
+    if let Some(applied) = *applied {
+      *rule = applied;
+    }
+    return;
+  }
+  info!("Switching to rule {}", *rule);
+  params.set_rule(&rule);
+  *applied = Some(*rule);
+}
+
+fn apply_topology(topology: Res<Topology>, mut params: ResMut<Params>) {
+  info!("Switching to topology {:?}", *topology);
+  params.topology = topology.shader_id();
+}
+
+fn apply_coloring(coloring: Res<CellColoring>, mut params: ResMut<Params>) {
+  info!("Switching to {:?} coloring", *coloring);
+  params.coloring = coloring.shader_id();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_keyboard_input(
+  keys: Res<ButtonInput<KeyCode>>,
+  mut params: ResMut<Params>,
+  mut brush: ResMut<Brush>,
+  mut coloring: ResMut<CellColoring>,
+  mut exports: EventWriter<ExportPattern>,
+  mut jumps: EventWriter<JumpGenerations>,
+  mut rewinds: EventWriter<RewindGenerations>,
+  controls: Res<SimulationControls>,
+  jump_controls: Res<JumpControls>,
+  mut control_events: EventWriter<SimulationControl>,
+) {
+  if keys.just_pressed(KeyCode::KeyT) {
+    params.tile_display ^= 1;
+  }
+  // G cycles through plain white and the age gradients
+  if keys.just_pressed(KeyCode::KeyG) {
+    *coloring = coloring.next();
+  }
+  // Ctrl+E exports the clipboard instead
+  let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+  let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+  if keys.just_pressed(KeyCode::KeyE) && !ctrl {
+    exports.write(ExportPattern {
+      path: "universe.rle".into(),
+      bounds: if shift {
+        ExportBounds::Universe
+      } else {
+        ExportBounds::LiveCells
+      },
+    });
+  }
+  if keys.just_pressed(KeyCode::KeyB) {
+    brush.shape = match brush.shape {
+      BrushShape::Square => BrushShape::Round,
+      BrushShape::Round => BrushShape::Square,
+    };
+  }
+  if keys.just_pressed(KeyCode::BracketLeft) {
+    brush.radius = brush.radius.saturating_sub(1);
+  }
+  if keys.just_pressed(KeyCode::BracketRight) {
+    brush.radius = (brush.radius + 1).min(64);
+  }
+  if keys.just_pressed(KeyCode::Space) {
+    control_events.write(SimulationControl::TogglePause);
+  }
+  if keys.just_pressed(KeyCode::KeyS) {
+    control_events.write(SimulationControl::Step);
+  }
+  // Minus and Equal halve and double the speed, with Shift the generations per frame
+  let faster = keys.just_pressed(KeyCode::Equal);
+  let slower = keys.just_pressed(KeyCode::Minus);
+  if (faster || slower) && shift {
+    let generations = controls.generations_per_frame;
+    control_events.write(SimulationControl::SetGenerationsPerFrame(if faster {
+      generations * 2
+    } else {
+      generations / 2
+    }));
+  } else if faster || slower {
+    let tps = controls.target_tps;
+    control_events.write(SimulationControl::SetTargetTps(if faster {
+      (tps * 2).clamp(1, MAX_TARGET_TPS)
+    } else {
+      (tps / 2).max(1)
+    }));
+  }
+  // Shift goes back further, pause first to watch it
+  if keys.just_pressed(KeyCode::Backspace) {
+    rewinds.write(RewindGenerations {
+      generations: if shift { 100 } else { 1 },
+    });
+  }
+  if keys.just_pressed(KeyCode::KeyJ) {
+    jumps.write(JumpGenerations {
+      generations: jump_controls.generations,
+    });
+  }
 }
 
 fn handle_mouse_input(
   mut params: ResMut<Params>,
   mut wheel_events: EventReader<MouseWheel>,
   button_input: Res<ButtonInput<MouseButton>>,
+  keys: Res<ButtonInput<KeyCode>>,
   window: Single<&Window>,
   mut prev_mouse_data: ResMut<MouseData>,
 ) {
@@ -173,7 +664,18 @@ fn handle_mouse_input(
 
   if let Some(pos) = window.cursor_position() {
     let left_just_pressed = button_input.just_pressed(MouseButton::Left);
-    let left_being_pressed = button_input.pressed(MouseButton::Left);
+    // Ctrl turns a left drag into a brush stroke and Shift into a selection, neither of
+    // which may pan the view
+    let painting = keys.any_pressed([
+      KeyCode::ControlLeft,
+      KeyCode::ControlRight,
+      KeyCode::ShiftLeft,
+      KeyCode::ShiftRight,
+    ]);
+    let left_being_pressed = button_input.pressed(MouseButton::Left) && !painting;
+    if painting {
+      prev_mouse_data.pos = Some(pos);
+    }
 
     if left_being_pressed {
       let old_pos = match prev_mouse_data.pos {
@@ -185,12 +687,12 @@ fn handle_mouse_input(
       };
       prev_mouse_data.pos = Some(pos);
 
-      let delta = (pos - old_pos) / params.zoom;
+      let delta = (pos - old_pos) * window.scale_factor() / params.zoom;
 
       //avoids changing center when user is just clicking without dragging
       if !left_just_pressed {
-        params.center_x = params.center_x - delta.x;
-        params.center_y = params.center_y - delta.y;
+        params.center_x -= delta.x;
+        params.center_y -= delta.y;
       }
     }
   }
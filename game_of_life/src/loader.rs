@@ -0,0 +1,228 @@
+use std::{error::Error, fs, sync::Arc};
+
+use bevy::{
+  ecs::{
+    change_detection::DetectChangesMut,
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  input::{ButtonInput, keyboard::KeyCode},
+  log::{error, info, warn},
+  math::{IVec2, UVec2, Vec2},
+  render::{
+    Extract,
+    render_resource::CommandEncoderDescriptor,
+    renderer::{RenderDevice, RenderQueue},
+  },
+  window::FileDragAndDrop,
+};
+
+use crate::{
+  bind_group::{GLAges, GLBufferLayout, GLBuffers, GLResize},
+  data_structs::{ComputeState, Generation, Params},
+  history::GLHistory,
+  pattern::{Macrocell, ParseError, ParseErrorKind, Pattern, PatternFormat},
+  population::GLPopulation,
+  rule::Rule,
+  stamp::StampTool,
+};
+
+/// Replaces the contents of the universe with `pattern`, placing its top left corner at
+/// cell `offset`. A rule or generation given by the pattern becomes the current one.
+#[derive(Event, Clone)]
+pub struct LoadPattern {
+  pub pattern: Arc<Pattern>,
+  pub offset: UVec2,
+}
+
+/// The latest pattern load extracted to the render world, waiting for buffers in its layout.
+#[derive(Resource, Default)]
+pub struct PendingPattern(pub Option<LoadPattern>);
+
+pub fn apply_pattern_rule(mut loads: EventReader<LoadPattern>, mut rule: ResMut<Rule>) {
+  for load in loads.read() {
+    if let Some(pattern_rule) = load.pattern.rule {
+      rule.set_if_neq(pattern_rule);
+    }
+  }
+}
+
+/// Loads dropped pattern files where they were exported from, or centered on the view.
+/// With Shift held they are attached to the cursor for stamping instead.
+pub fn handle_file_drop(
+  mut drop_events: EventReader<FileDragAndDrop>,
+  mut loads: EventWriter<LoadPattern>,
+  mut stamp: ResMut<StampTool>,
+  keys: Res<ButtonInput<KeyCode>>,
+  params: Res<Params>,
+) {
+  for event in drop_events.read() {
+    let FileDragAndDrop::DroppedFile { path_buf, .. } = event else {
+      continue;
+    };
+
+    let source = match fs::read_to_string(path_buf) {
+      Ok(source) => source,
+      Err(err) => {
+        error!("Failed to read {}: {err}", path_buf.display());
+        continue;
+      }
+    };
+    let extension = path_buf
+      .extension()
+      .and_then(|extension| extension.to_str());
+    let universe = UVec2::new(params.cell_count_x, params.buffer_size_y);
+    let pattern = match read_pattern(&source, extension, universe) {
+      Ok(pattern) => pattern,
+      Err(err) => {
+        error!("Failed to load {}: {err}", path_buf.display());
+        continue;
+      }
+    };
+
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+      info!(
+        "Attaching {}x{} pattern from {} to the cursor",
+        pattern.width,
+        pattern.height,
+        path_buf.display()
+      );
+      stamp.pattern = Some(Arc::new(pattern));
+      continue;
+    }
+
+    info!(
+      "Loading {}x{} pattern from {}",
+      pattern.width,
+      pattern.height,
+      path_buf.display()
+    );
+    let offset = pattern_offset(&pattern, Vec2::new(params.center_x, params.center_y));
+    loads.write(LoadPattern {
+      pattern: Arc::new(pattern),
+      offset,
+    });
+  }
+}
+
+/// Parses a dropped file. Macrocell files can hold patterns far too large to expand into
+/// cells, so their live cells are measured against the universe first.
+pub(crate) fn read_pattern(
+  source: &str,
+  extension: Option<&str>,
+  universe: UVec2,
+) -> Result<Pattern, Box<dyn Error>> {
+  let Some(format) = PatternFormat::detect(source, extension) else {
+    return Err(ParseError::new(1, 1, ParseErrorKind::UnknownFormat).into());
+  };
+  if format != PatternFormat::Macrocell {
+    return Ok(format.parse(source)?);
+  }
+
+  let macrocell = Macrocell::parse(source)?;
+  if let Some((_, size)) = macrocell.live_bounds()
+    && (size.x > u64::from(universe.x) || size.y > u64::from(universe.y))
+  {
+    return Err(
+      format!(
+        "a {}x{} pattern does not fit into the {}x{} universe",
+        size.x, size.y, universe.x, universe.y
+      )
+      .into(),
+    );
+  }
+  macrocell
+    .to_pattern()
+    .ok_or_else(|| "pattern is too large to load".into())
+}
+
+/// Where a loaded pattern goes: the cell it was exported from, or centered on `center`.
+pub(crate) fn pattern_offset(pattern: &Pattern, center: Vec2) -> UVec2 {
+  match pattern.position {
+    Some(position) if position.cmpge(IVec2::ZERO).all() => position.as_uvec2(),
+    _ => UVec2::new(
+      (center.x - pattern.width as f32 / 2.0).max(0.0) as u32,
+      (center.y - pattern.height as f32 / 2.0).max(0.0) as u32,
+    ),
+  }
+}
+
+pub fn extract_pattern_loads(
+  mut loads: Extract<EventReader<LoadPattern>>,
+  mut pending: ResMut<PendingPattern>,
+) {
+  // every load replaces the whole universe, so only the last one matters
+  if let Some(load) = loads.read().last() {
+    pending.0 = Some(load.clone());
+  }
+}
+
+/// Clears both generation buffers and the ages, and writes the pending pattern into the
+/// generation buffers row by row.
+#[allow(clippy::too_many_arguments)]
+pub fn write_pattern(
+  mut pending: ResMut<PendingPattern>,
+  state: Option<Res<ComputeState>>,
+  params: Res<Params>,
+  layout: Option<Res<GLBufferLayout>>,
+  buffers: Option<Res<GLBuffers>>,
+  resize: Option<Res<GLResize>>,
+  ages: Option<Res<GLAges>>,
+  generation: Res<Generation>,
+  history: Option<ResMut<GLHistory>>,
+  population: Option<ResMut<GLPopulation>>,
+  device: Res<RenderDevice>,
+  queue: Res<RenderQueue>,
+) {
+  // wait for the buffers in the layout of the pattern's rule, and for the initial random
+  // soup or the cells copied over into a resized universe, which would otherwise overwrite
+  // the pattern
+  let ready = matches!(
+    state.as_deref(),
+    Some(ComputeState::STEP | ComputeState::WAIT)
+  ) && layout.is_some_and(|layout| layout.matches(&params))
+    && resize.is_none();
+  let (Some(buffers), true) = (buffers, ready) else {
+    return;
+  };
+  let Some(LoadPattern { pattern, offset }) = pending.0.take() else {
+    return;
+  };
+
+  if offset.x + pattern.width > params.cell_count_x
+    || offset.y + pattern.height > params.buffer_size_y
+  {
+    warn!(
+      "A {}x{} pattern at {offset} does not fit into the universe",
+      pattern.width, pattern.height
+    );
+    return;
+  }
+
+  let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+  for buffer in &buffers.0 {
+    encoder.clear_buffer(buffer, 0, None);
+  }
+  if let Some(ages) = ages.filter(|ages| ages.tracked) {
+    encoder.clear_buffer(&ages.buffer, 0, None);
+  }
+  queue.submit([encoder.finish()]);
+  generation.set(pattern.generation.unwrap_or(0));
+  // the generations before the pattern have nothing to do with it
+  if let Some(mut history) = history {
+    history.keyframes.clear();
+  }
+  if let Some(mut population) = population {
+    population.recount = true;
+  }
+
+  // writes are staged until the next submission, so they land after the clear
+  let (first_word, rows) = pattern.pack_rows(offset.x, params.bits_per_cell);
+  for (y, row) in rows.iter().enumerate() {
+    let word = first_word + (offset.y + y as u32) * params.buffer_size_x;
+    for buffer in &buffers.0 {
+      queue.write_buffer(buffer, word as u64 * 4, bytemuck::cast_slice(row));
+    }
+  }
+}
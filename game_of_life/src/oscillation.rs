@@ -0,0 +1,219 @@
+use std::{
+  collections::VecDeque,
+  mem,
+  sync::{Arc, Mutex},
+};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::info,
+  render::extract_resource::ExtractResource,
+};
+
+use crate::{
+  controls::{Reseed, SimulationControl},
+  population::PopulationCounting,
+};
+
+/// How the universe is watched for settling down. The GPU hashes every generation whose
+/// population is counted, so periods are found in steps of `PopulationCounting::interval`
+/// and still lifes are only told apart from oscillators while it is 1.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, ExtractResource)]
+pub struct OscillationDetection {
+  /// Recent fingerprints a new one is compared against, which bounds the longest period
+  /// found. 0 stops hashing the generations.
+  pub window: u32,
+  pub on_settled: OnSettled,
+}
+
+impl Default for OscillationDetection {
+  fn default() -> Self {
+    Self {
+      window: 256,
+      on_settled: OnSettled::Continue,
+    }
+  }
+}
+
+/// What happens when the universe settles down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnSettled {
+  #[default]
+  Continue,
+  Pause,
+  /// Starts over with a random soup of a new seed.
+  Reseed,
+}
+
+/// A 64-bit hash of a generation computed on the GPU. Empty words do not change it, so an
+/// empty universe hashes to 0.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+  pub generation: u64,
+  pub hash: u64,
+}
+
+/// The universe repeating a generation from the window of recent fingerprints, sent once
+/// when it starts and again only after it changed.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Settled {
+  /// No cell is left, not even a dying one.
+  Extinct {
+    generation: u64,
+  },
+  StillLife {
+    generation: u64,
+  },
+  Oscillating {
+    generation: u64,
+    period: u64,
+  },
+}
+
+impl Settled {
+  /// The generation the repetition was seen at.
+  pub fn generation(&self) -> u64 {
+    match *self {
+      Settled::Extinct { generation }
+      | Settled::StillLife { generation }
+      | Settled::Oscillating { generation, .. } => generation,
+    }
+  }
+
+  /// Generations until the universe repeats, 1 for an empty universe and still lifes.
+  pub fn period(&self) -> u64 {
+    match *self {
+      Settled::Extinct { .. } | Settled::StillLife { .. } => 1,
+      Settled::Oscillating { period, .. } => period,
+    }
+  }
+
+  fn same_state(&self, other: &Settled) -> bool {
+    mem::discriminant(self) == mem::discriminant(other) && self.period() == other.period()
+  }
+}
+
+/// The recent fingerprints, oldest first, and what was last reported about them.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct Fingerprints {
+  recent: VecDeque<Fingerprint>,
+  reported: Option<Settled>,
+}
+
+impl Fingerprints {
+  /// Adds the fingerprint of the next counted generation. A fingerprint that does not
+  /// follow the last one within `interval` generations, after a rewind, jump or load,
+  /// starts the window over, since the universe in between is unknown.
+  pub fn observe(
+    &mut self,
+    fingerprint: Fingerprint,
+    window: u32,
+    interval: u32,
+  ) -> Option<Settled> {
+    let follows = self.recent.back().is_some_and(|last| {
+      fingerprint.generation > last.generation
+        && fingerprint.generation - last.generation <= u64::from(interval.max(1))
+    });
+    if !follows || window == 0 {
+      self.recent.clear();
+      self.reported = None;
+    }
+    if window == 0 {
+      return None;
+    }
+
+    let generation = fingerprint.generation;
+    let settled = if fingerprint.hash == 0 {
+      Some(Settled::Extinct { generation })
+    } else {
+      self
+        .recent
+        .iter()
+        .rev()
+        .find(|seen| seen.hash == fingerprint.hash)
+        .map(|seen| match generation - seen.generation {
+          1 => Settled::StillLife { generation },
+          period => Settled::Oscillating { generation, period },
+        })
+    };
+
+    self.recent.push_back(fingerprint);
+    while self.recent.len() > window as usize {
+      self.recent.pop_front();
+    }
+
+    let Some(settled) = settled else {
+      self.reported = None;
+      return None;
+    };
+    if self
+      .reported
+      .is_some_and(|reported| reported.same_state(&settled))
+    {
+      return None;
+    }
+    self.reported = Some(settled);
+    Some(settled)
+  }
+}
+
+/// Shared with the render world, whose buffer mapping callbacks push the fingerprints into
+/// it.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct FingerprintReadback(pub Arc<Mutex<Vec<Fingerprint>>>);
+
+/// Sends the fingerprints read back since the last frame as `Fingerprint` events, oldest
+/// first, and a `Settled` event when they start repeating.
+pub fn receive_fingerprints(
+  readback: Res<FingerprintReadback>,
+  detection: Res<OscillationDetection>,
+  counting: Res<PopulationCounting>,
+  mut fingerprints: ResMut<Fingerprints>,
+  mut hashes: EventWriter<Fingerprint>,
+  mut settled: EventWriter<Settled>,
+) {
+  let completed = mem::take(&mut *readback.0.lock().unwrap());
+  for &fingerprint in &completed {
+    if let Some(event) = fingerprints.observe(fingerprint, detection.window, counting.interval) {
+      settled.write(event);
+    }
+  }
+  hashes.write_batch(completed);
+}
+
+pub fn handle_settled(
+  mut settled: EventReader<Settled>,
+  detection: Res<OscillationDetection>,
+  mut controls: EventWriter<SimulationControl>,
+  mut reseeds: EventWriter<Reseed>,
+) {
+  let mut any = false;
+  for event in settled.read() {
+    any = true;
+    match event {
+      Settled::Extinct { generation } => info!("Extinct at generation {generation}"),
+      Settled::StillLife { generation } => info!("Still life at generation {generation}"),
+      Settled::Oscillating { generation, period } => {
+        info!("Oscillating with period {period} at generation {generation}")
+      }
+    }
+  }
+  if !any {
+    return;
+  }
+  match detection.on_settled {
+    OnSettled::Continue => {}
+    OnSettled::Pause => {
+      controls.write(SimulationControl::Pause);
+    }
+    OnSettled::Reseed => {
+      reseeds.write(Reseed {
+        seed: rand::random::<u32>(),
+      });
+    }
+  }
+}
@@ -0,0 +1,306 @@
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut, Single},
+    world::{FromWorld, World},
+  },
+  input::{ButtonInput, keyboard::KeyCode, mouse::MouseButton},
+  math::{IVec2, UVec2, Vec2},
+  platform::collections::{HashMap, HashSet},
+  render::{
+    Extract,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages},
+    renderer::{RenderDevice, RenderQueue},
+  },
+  window::Window,
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+  bind_group::GLBufferLayout,
+  cpu::{CpuUniverse, Viewport},
+  data_structs::{ComputeState, Params},
+};
+
+/// Most words the `apply_edits` kernel changes in one frame, the rest wait for the next
+/// frames.
+pub const MAX_CELL_EDITS: usize = 1 << 16;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BrushShape {
+  #[default]
+  Square,
+  Round,
+}
+
+/// What a stroke paints around every cell of its path.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Brush {
+  pub shape: BrushShape,
+  /// Cells between the center and the edge, 0 paints single cells.
+  pub radius: u32,
+}
+
+impl Brush {
+  /// The cells covered by the brush centered on `center`.
+  pub fn cells(&self, center: IVec2) -> impl Iterator<Item = IVec2> + use<> {
+    let radius = self.radius as i32;
+    let shape = self.shape;
+    (-radius..=radius)
+      .flat_map(move |dy| (-radius..=radius).map(move |dx| IVec2::new(dx, dy)))
+      // the extra `radius` keeps single cells from sticking out of the sides
+      .filter(move |offset| {
+        shape == BrushShape::Square || offset.length_squared() <= radius * radius + radius
+      })
+      .map(move |offset| center + offset)
+  }
+}
+
+/// The cells of the Bresenham line from `from` to `to`, both included.
+pub fn bresenham_line(from: IVec2, to: IVec2) -> Vec<IVec2> {
+  let delta = (to - from).abs();
+  let step = (to - from).signum();
+  let mut error = delta.x - delta.y;
+  let mut cell = from;
+
+  let mut cells = vec![cell];
+  while cell != to {
+    let doubled = 2 * error;
+    if doubled > -delta.y {
+      error -= delta.y;
+      cell.x += step.x;
+    }
+    if doubled < delta.x {
+      error += delta.x;
+      cell.y += step.y;
+    }
+    cells.push(cell);
+  }
+  cells
+}
+
+/// Sets `cells` to `state`, on top of whatever the universe holds.
+#[derive(Event, Clone, Debug)]
+pub struct PaintCells {
+  pub cells: Vec<UVec2>,
+  pub state: u8,
+}
+
+/// A change to the cells of one storage buffer word, as read by the `apply_edits` kernel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Pod, Zeroable)]
+pub struct CellEdit {
+  pub index: u32,
+  /// Bits of the cells that change.
+  pub mask: u32,
+  /// Their new value.
+  pub bits: u32,
+}
+
+/// Cell changes merged into one edit per word, for a buffer of `buffer_size_x` words per
+/// row and `bits_per_cell`.
+#[derive(Clone, Debug, Default)]
+pub struct CellEdits {
+  buffer_size_x: u32,
+  bits_per_cell: u32,
+  words: HashMap<u32, CellEdit>,
+}
+
+impl CellEdits {
+  pub fn new(buffer_size_x: u32, bits_per_cell: u32) -> Self {
+    Self {
+      buffer_size_x,
+      bits_per_cell,
+      words: HashMap::default(),
+    }
+  }
+
+  /// Whether the edits are laid out like the storage buffers `params` describes.
+  pub(crate) fn matches(&self, params: &Params) -> bool {
+    self.buffer_size_x == params.buffer_size_x && self.bits_per_cell == params.bits_per_cell
+  }
+
+  /// Sets a cell, overriding earlier changes to it.
+  pub fn set(&mut self, cell: UVec2, state: u8) {
+    let cells_per_word = 32 / self.bits_per_cell;
+    let index = cell.x / cells_per_word + cell.y * self.buffer_size_x;
+    let shift = (cells_per_word - 1 - cell.x % cells_per_word) * self.bits_per_cell;
+    let mask = ((1u32 << self.bits_per_cell) - 1) << shift;
+    let bits = (u32::from(state) << shift) & mask;
+
+    let edit = self.words.entry(index).or_insert(CellEdit {
+      index,
+      mask: 0,
+      bits: 0,
+    });
+    edit.mask |= mask;
+    edit.bits = (edit.bits & !mask) | bits;
+  }
+
+  pub fn len(&self) -> usize {
+    self.words.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.words.is_empty()
+  }
+
+  /// Takes up to `max` edits, in buffer order.
+  pub fn drain(&mut self, max: usize) -> Vec<CellEdit> {
+    let mut indices: Vec<u32> = self.words.keys().copied().collect();
+    indices.sort_unstable();
+    indices.truncate(max);
+    indices
+      .into_iter()
+      .filter_map(|index| self.words.remove(&index))
+      .collect()
+  }
+}
+
+/// Cell of the previous frame of the stroke being drawn, which the next cell is joined to.
+#[derive(Resource, Default)]
+pub struct PaintStroke(Option<IVec2>);
+
+/// Draws with the brush while Ctrl and the left button are held and erases while the
+/// right button is held.
+pub fn handle_painting(
+  window: Single<&Window>,
+  buttons: Res<ButtonInput<MouseButton>>,
+  keys: Res<ButtonInput<KeyCode>>,
+  params: Res<Params>,
+  brush: Res<Brush>,
+  mut stroke: ResMut<PaintStroke>,
+  mut paints: EventWriter<PaintCells>,
+) {
+  let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+  let state = if buttons.pressed(MouseButton::Right) {
+    Some(0)
+  } else if ctrl && buttons.pressed(MouseButton::Left) {
+    Some(1)
+  } else {
+    None
+  };
+  let (Some(state), Some(cell)) = (state, cursor_cell(&window, &params)) else {
+    stroke.0 = None;
+    return;
+  };
+
+  // the cursor jumps between frames, so join the cells it was over with a line
+  let from = stroke.0.unwrap_or(cell);
+  stroke.0 = Some(cell);
+  let size = IVec2::new(params.cell_count_x as i32, params.buffer_size_y as i32);
+  let cells: HashSet<UVec2> = bresenham_line(from, cell)
+    .into_iter()
+    .flat_map(|point| brush.cells(point))
+    .filter(|cell| cell.cmpge(IVec2::ZERO).all() && cell.cmplt(size).all())
+    .map(|cell| cell.as_uvec2())
+    .collect();
+
+  if !cells.is_empty() {
+    paints.write(PaintCells {
+      cells: cells.into_iter().collect(),
+      state,
+    });
+  }
+}
+
+/// The cell under the cursor, with the math of the `display` kernel.
+pub(crate) fn cursor_cell(window: &Window, params: &Params) -> Option<IVec2> {
+  let viewport = Viewport {
+    resolution: UVec2::new(params.resolution_x, params.resolution_y),
+    center: Vec2::new(params.center_x, params.center_y),
+    zoom: params.zoom,
+    tile_display: params.tile_display != 0,
+  };
+  // the texture has a texel per physical pixel and is centered on the window
+  let cursor = (window.cursor_position()? - window.size() / 2.0) * window.scale_factor();
+  let pixel = viewport.resolution.as_vec2() / 2.0 + cursor;
+  if pixel.cmplt(Vec2::ZERO).any() {
+    return None;
+  }
+
+  let position = CpuUniverse::pixel_position(&viewport, pixel.floor().as_uvec2());
+  Some(position.floor().as_ivec2())
+}
+
+/// Painted cells extracted to the render world, waiting for buffers that hold a generation.
+#[derive(Resource, Default)]
+pub struct PendingPaint {
+  cells: Vec<PaintCells>,
+  edits: CellEdits,
+}
+
+/// The buffer bound to the `apply_edits` kernel and the number of edits it holds this
+/// frame.
+#[derive(Resource)]
+pub struct GLEdits {
+  pub buffer: Buffer,
+  pub count: u32,
+}
+
+impl FromWorld for GLEdits {
+  fn from_world(world: &mut World) -> Self {
+    let device = world.resource::<RenderDevice>();
+    let buffer = device.create_buffer(&BufferDescriptor {
+      label: None,
+      // the edit count, followed by the edits
+      size: (4 + MAX_CELL_EDITS * size_of::<CellEdit>()) as u64,
+      usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+      mapped_at_creation: false,
+    });
+    Self { buffer, count: 0 }
+  }
+}
+
+pub fn extract_paint(
+  mut paints: Extract<EventReader<PaintCells>>,
+  mut pending: ResMut<PendingPaint>,
+) {
+  pending.cells.extend(paints.read().cloned());
+}
+
+/// Uploads the next batch of edits, merged by word, for the render graph node to apply.
+pub fn prepare_edits(
+  mut pending: ResMut<PendingPaint>,
+  edits: Option<ResMut<GLEdits>>,
+  state: Option<Res<ComputeState>>,
+  params: Res<Params>,
+  layout: Option<Res<GLBufferLayout>>,
+  queue: Res<RenderQueue>,
+) {
+  let Some(mut edits) = edits else {
+    return;
+  };
+  edits.count = 0;
+
+  // painting into the initial random soup would be overwritten
+  let ready = matches!(
+    state.as_deref(),
+    Some(ComputeState::STEP | ComputeState::WAIT)
+  ) && layout.is_some_and(|layout| layout.matches(&params));
+  if !ready {
+    return;
+  }
+
+  let pending = &mut *pending;
+  if !pending.edits.matches(&params) {
+    pending.edits = CellEdits::new(params.buffer_size_x, params.bits_per_cell);
+  }
+  for paint in pending.cells.drain(..) {
+    for &cell in &paint.cells {
+      if cell.x < params.cell_count_x && cell.y < params.buffer_size_y {
+        pending.edits.set(cell, paint.state);
+      }
+    }
+  }
+  if pending.edits.is_empty() {
+    return;
+  }
+
+  let batch = pending.edits.drain(MAX_CELL_EDITS);
+  let mut contents = vec![batch.len() as u32];
+  contents.extend_from_slice(bytemuck::cast_slice(&batch));
+  queue.write_buffer(&edits.buffer, 0, bytemuck::cast_slice(&contents));
+  edits.count = batch.len() as u32;
+}
@@ -0,0 +1,132 @@
+use crate::rule::Rule;
+
+use super::{MAX_PATTERN_CELLS, ParseError, ParseErrorKind, Pattern};
+
+/// Parses a Life 1.06 file: a `#Life 1.06` header followed by one `x y` coordinate pair
+/// of a live cell per line. Coordinates may be negative, the pattern spans their bounding
+/// box.
+pub fn parse_life_106(source: &str) -> Result<Pattern, ParseError> {
+  let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line));
+  expect_header(&mut lines, "#Life 1.06")?;
+
+  let mut comments = vec![];
+  let mut cells = vec![];
+  for (number, line) in lines {
+    if let Some(comment) = line.strip_prefix('#') {
+      comments.push(comment.trim().to_string());
+      continue;
+    }
+    if line.trim().is_empty() {
+      continue;
+    }
+    cells.push(parse_coordinates(line, number)?);
+  }
+
+  let mut pattern = from_cells(&cells, source.lines().count())?;
+  pattern.comments = comments;
+  Ok(pattern)
+}
+
+/// Parses a Life 1.05 file: a `#Life 1.05` header, `#D` description lines, an optional
+/// `#N` (Conway's Life) or `#R 23/3` rule line, and blocks of `.`/`*` rows, each placed at
+/// the offset of the `#P x y` line above it.
+pub fn parse_life_105(source: &str) -> Result<Pattern, ParseError> {
+  let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line));
+  expect_header(&mut lines, "#Life 1.05")?;
+
+  let mut comments = vec![];
+  let mut rule = None;
+  let mut cells = vec![];
+  let mut origin = (0, 0);
+  let mut y = 0;
+
+  for (number, line) in lines {
+    if let Some(directive) = line.strip_prefix('#') {
+      let mut chars = directive.chars();
+      let kind = chars.next().map(|c| c.to_ascii_uppercase());
+      let argument = chars.as_str();
+      match kind {
+        Some('P') => {
+          origin = parse_coordinates(argument, number)?;
+          y = 0;
+        }
+        Some('N') => rule = Some(Rule::default()),
+        Some('R') => {
+          let column = 3 + argument.len() - argument.trim_start().len();
+          let parsed = argument
+            .trim()
+            .parse()
+            .map_err(|error| ParseError::new(number, column, ParseErrorKind::InvalidRule(error)))?;
+          rule = Some(parsed);
+        }
+        _ => comments.push(argument.trim().to_string()),
+      }
+      continue;
+    }
+
+    for (x, character) in line.trim_end().chars().enumerate() {
+      match character {
+        '.' => {}
+        '*' => cells.push((origin.0 + x as i64, origin.1 + y)),
+        c => {
+          let kind = ParseErrorKind::UnexpectedCharacter(c);
+          return Err(ParseError::new(number, x + 1, kind));
+        }
+      }
+    }
+    y += 1;
+  }
+
+  let mut pattern = from_cells(&cells, source.lines().count())?;
+  pattern.rule = rule;
+  pattern.comments = comments;
+  Ok(pattern)
+}
+
+fn expect_header<'a>(
+  lines: &mut impl Iterator<Item = (usize, &'a str)>,
+  header: &str,
+) -> Result<(), ParseError> {
+  match lines.next() {
+    Some((_, line)) if line.trim_end().eq_ignore_ascii_case(header) => Ok(()),
+    _ => Err(ParseError::new(1, 1, ParseErrorKind::MissingHeader)),
+  }
+}
+
+fn parse_coordinates(text: &str, line: usize) -> Result<(i64, i64), ParseError> {
+  let column = text.len() - text.trim_start().len() + 1;
+  let invalid = || ParseError::new(line, column, ParseErrorKind::InvalidCoordinates);
+
+  let mut values = text.split_whitespace().map(|value| value.parse::<i32>());
+  match (values.next(), values.next(), values.next()) {
+    (Some(Ok(x)), Some(Ok(y)), None) => Ok((x.into(), y.into())),
+    _ => Err(invalid()),
+  }
+}
+
+/// Builds the pattern spanning the bounding box of the live `cells`.
+fn from_cells(cells: &[(i64, i64)], line_count: usize) -> Result<Pattern, ParseError> {
+  let Some(&(first_x, first_y)) = cells.first() else {
+    return Ok(Pattern::new(0, 0));
+  };
+  let (mut min_x, mut min_y, mut max_x, mut max_y) = (first_x, first_y, first_x, first_y);
+  for &(x, y) in cells {
+    min_x = min_x.min(x);
+    min_y = min_y.min(y);
+    max_x = max_x.max(x);
+    max_y = max_y.max(y);
+  }
+
+  // coordinates are `i32`, so the spans fit into `u64`
+  let width = (max_x - min_x + 1) as u64;
+  let height = (max_y - min_y + 1) as u64;
+  if width * height > MAX_PATTERN_CELLS {
+    return Err(ParseError::new(line_count, 1, ParseErrorKind::OutOfBounds));
+  }
+
+  let mut pattern = Pattern::new(width as u32, height as u32);
+  for &(x, y) in cells {
+    pattern.set((x - min_x) as u32, (y - min_y) as u32, 1);
+  }
+  Ok(pattern)
+}
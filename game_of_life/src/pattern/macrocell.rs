@@ -0,0 +1,479 @@
+use std::fmt;
+
+use bevy::{math::U64Vec2, platform::collections::HashMap};
+
+use crate::rule::Rule;
+
+use super::{MAX_PATTERN_CELLS, ParseError, ParseErrorKind, Pattern};
+
+/// A node of a macrocell file. Nodes refer to their children by their 1-based position in
+/// the file, with 0 standing for an empty square of the right size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum MacrocellNode {
+  /// An 8x8 square of a two-state file, one byte per row with the leftmost cell in the
+  /// most significant bit.
+  Leaf([u8; 8]),
+  /// The states of a 2x2 square of a multi-state file, in `nw ne sw se` order.
+  Cells([u8; 4]),
+  /// A square of `2^level` cells made of four squares one level below, in `nw ne sw se`
+  /// order.
+  Node { level: u8, children: [u32; 4] },
+}
+
+impl MacrocellNode {
+  pub(crate) fn level(&self) -> u8 {
+    match self {
+      MacrocellNode::Leaf(_) => 3,
+      MacrocellNode::Cells(_) => 1,
+      MacrocellNode::Node { level, .. } => *level,
+    }
+  }
+}
+
+/// A pattern in Golly's macrocell format: a quadtree written bottom up, so that identical
+/// squares are stored once and huge sparse or repetitive patterns stay small. The last
+/// node is the root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Macrocell {
+  pub(crate) nodes: Vec<MacrocellNode>,
+  pub rule: Option<Rule>,
+  pub generation: Option<u64>,
+  pub comments: Vec<String>,
+}
+
+impl Macrocell {
+  /// Parses a `[M2]` macrocell file with `#R` rule, `#G` generation and other `#` comment
+  /// lines. Two-state files hold 8x8 leaves of `.`/`*` rows ended by `$`, multi-state
+  /// files `1 nw ne sw se` lines of cell states; both build up with `level nw ne sw se`
+  /// node lines.
+  pub fn parse(source: &str) -> Result<Self, ParseError> {
+    let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line));
+    match lines.next() {
+      Some((_, header)) if header.starts_with("[M2]") => {}
+      _ => return Err(ParseError::new(1, 1, ParseErrorKind::MissingHeader)),
+    }
+
+    let mut macrocell = Macrocell {
+      nodes: vec![],
+      rule: None,
+      generation: None,
+      comments: vec![],
+    };
+
+    for (number, line) in lines {
+      if let Some(comment) = line.strip_prefix('#') {
+        let mut chars = comment.chars();
+        let kind = chars.next();
+        let value = chars.as_str().trim();
+        match kind {
+          Some('R') => {
+            let column = line.len() - chars.as_str().trim_start().len() + 1;
+            let rule = value.parse().map_err(|error| {
+              ParseError::new(number, column, ParseErrorKind::InvalidRule(error))
+            })?;
+            macrocell.rule = Some(rule);
+          }
+          Some('G') => macrocell.generation = value.parse().ok(),
+          _ => macrocell.comments.push(value.to_string()),
+        }
+        continue;
+      }
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      let node = match line.starts_with(|c: char| c.is_ascii_digit()) {
+        true => parse_node(line, number, &macrocell.nodes)?,
+        false => parse_leaf(line, number)?,
+      };
+      macrocell.nodes.push(node);
+    }
+
+    if macrocell.nodes.is_empty() {
+      let line = source.lines().count();
+      return Err(ParseError::new(line, 1, ParseErrorKind::InvalidNode));
+    }
+    Ok(macrocell)
+  }
+
+  /// Builds the quadtree of a pattern, using the multi-state node lines when the pattern
+  /// or its rule has more than two states.
+  pub fn from_pattern(pattern: &Pattern) -> Self {
+    let multi_state = pattern.rule.is_some_and(|rule| rule.states > 2)
+      || pattern.cells.iter().any(|&state| state > 1);
+    let min_level = if multi_state { 1 } else { 3 };
+    let side = pattern.width.max(pattern.height).max(1).next_power_of_two();
+    let level = (side.trailing_zeros() as u8).max(min_level);
+
+    let mut builder = Builder {
+      pattern,
+      multi_state,
+      nodes: vec![],
+      ids: HashMap::default(),
+    };
+    let root = builder.build(level, 0, 0);
+    if root == 0 {
+      // the file still needs a root to tell the size
+      builder.nodes.push(match level {
+        1 => MacrocellNode::Cells([0; 4]),
+        3 if !multi_state => MacrocellNode::Leaf([0; 8]),
+        _ => MacrocellNode::Node {
+          level,
+          children: [0; 4],
+        },
+      });
+    }
+
+    Macrocell {
+      nodes: builder.nodes,
+      rule: pattern.rule,
+      generation: pattern.generation,
+      comments: pattern.comments.clone(),
+    }
+  }
+
+  /// Number of cells on each side of the root square.
+  pub fn side(&self) -> u64 {
+    1 << self.nodes.last().map_or(0, |node| node.level())
+  }
+
+  /// The smallest rectangle holding every non-dead cell, as its top left cell inside the
+  /// root square and its size.
+  pub fn live_bounds(&self) -> Option<(U64Vec2, U64Vec2)> {
+    let root = self.nodes.len() as u32;
+    let extent = |axis, max| {
+      let mut memo = HashMap::default();
+      self.extent(root, axis, max, &mut memo)
+    };
+    let min = U64Vec2::new(extent(0, false)?, extent(1, false)?);
+    let max = U64Vec2::new(extent(0, true)?, extent(1, true)?);
+    Some((min, max - min + 1))
+  }
+
+  /// Copies the bounding box of the live cells into a pattern, or returns `None` if it has
+  /// more than `MAX_PATTERN_CELLS` cells.
+  pub fn to_pattern(&self) -> Option<Pattern> {
+    let (min, size) = self.live_bounds().unwrap_or_default();
+    if size.x.saturating_mul(size.y) > MAX_PATTERN_CELLS {
+      return None;
+    }
+
+    let mut pattern = Pattern::new(size.x as u32, size.y as u32);
+    let root = self.nodes.len() as u32;
+    self.visit(
+      root,
+      U64Vec2::ZERO,
+      min,
+      min + size,
+      &mut |position, state| {
+        let cell = position - min;
+        pattern.set(cell.x as u32, cell.y as u32, state);
+      },
+    );
+    pattern.rule = self.rule;
+    pattern.generation = self.generation;
+    pattern.comments = self.comments.clone();
+    Some(pattern)
+  }
+
+  fn node(&self, index: u32) -> &MacrocellNode {
+    &self.nodes[index as usize - 1]
+  }
+
+  /// The lowest (or highest, with `max`) coordinate of a live cell along `axis` relative to
+  /// the node.
+  fn extent(
+    &self,
+    index: u32,
+    axis: usize,
+    max: bool,
+    memo: &mut HashMap<u32, Option<u64>>,
+  ) -> Option<u64> {
+    if index == 0 {
+      return None;
+    }
+    if let Some(&extent) = memo.get(&index) {
+      return extent;
+    }
+
+    let mut cells = vec![];
+    let mut extent = None;
+    match *self.node(index) {
+      MacrocellNode::Leaf(rows) => {
+        for (y, row) in rows.into_iter().enumerate() {
+          for x in 0..8 {
+            if (row >> (7 - x)) & 1 != 0 {
+              cells.push([x as u64, y as u64][axis]);
+            }
+          }
+        }
+      }
+      MacrocellNode::Cells(states) => {
+        for (i, state) in states.into_iter().enumerate() {
+          if state != 0 {
+            cells.push([i as u64 & 1, i as u64 >> 1][axis]);
+          }
+        }
+      }
+      MacrocellNode::Node { level, children } => {
+        let half = 1 << (level - 1);
+        let sides: [usize; 2] = if max { [1, 0] } else { [0, 1] };
+        for side in sides {
+          let extents = (0..4)
+            .filter(|i| (i >> axis) & 1 == side)
+            .filter_map(|i| self.extent(children[i], axis, max, memo))
+            .map(|value| value + side as u64 * half);
+          extent = if max { extents.max() } else { extents.min() };
+          if extent.is_some() {
+            break;
+          }
+        }
+      }
+    }
+    if !cells.is_empty() {
+      extent = if max {
+        cells.into_iter().max()
+      } else {
+        cells.into_iter().min()
+      };
+    }
+
+    memo.insert(index, extent);
+    extent
+  }
+
+  /// Calls `f` with the position and state of every non-dead cell of the node between
+  /// `start` and `end`.
+  fn visit(
+    &self,
+    index: u32,
+    position: U64Vec2,
+    start: U64Vec2,
+    end: U64Vec2,
+    f: &mut dyn FnMut(U64Vec2, u8),
+  ) {
+    if index == 0 {
+      return;
+    }
+    let node = self.node(index);
+    let node_end = position + U64Vec2::splat(1 << node.level());
+    if node_end.cmple(start).any() || position.cmpge(end).any() {
+      return;
+    }
+
+    let mut cell = |offset: U64Vec2, state: u8| {
+      let cell = position + offset;
+      if state != 0 && cell.cmpge(start).all() && cell.cmplt(end).all() {
+        f(cell, state);
+      }
+    };
+    match *node {
+      MacrocellNode::Leaf(rows) => {
+        for (y, row) in rows.into_iter().enumerate() {
+          for x in 0..8 {
+            cell(U64Vec2::new(x, y as u64), (row >> (7 - x)) & 1);
+          }
+        }
+      }
+      MacrocellNode::Cells(states) => {
+        for (i, state) in states.into_iter().enumerate() {
+          cell(U64Vec2::new(i as u64 & 1, i as u64 >> 1), state);
+        }
+      }
+      MacrocellNode::Node { level, children } => {
+        let half = 1 << (level - 1);
+        for (i, child) in children.into_iter().enumerate() {
+          let offset = U64Vec2::new(i as u64 & 1, i as u64 >> 1) * half;
+          self.visit(child, position + offset, start, end, f);
+        }
+      }
+    }
+  }
+}
+
+/// Writes the macrocell file that `Macrocell::parse` reads back.
+impl fmt::Display for Macrocell {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "[M2] (shaders)")?;
+    if let Some(rule) = self.rule {
+      writeln!(f, "#R {rule}")?;
+    }
+    if let Some(generation) = self.generation {
+      writeln!(f, "#G {generation}")?;
+    }
+    for comment in &self.comments {
+      writeln!(f, "#C {comment}")?;
+    }
+
+    for node in &self.nodes {
+      match node {
+        MacrocellNode::Leaf(rows) => {
+          // trailing dead cells and rows are left out
+          let height = rows.iter().rposition(|&row| row != 0).map_or(0, |y| y + 1);
+          for &row in &rows[..height] {
+            for x in 0..8 - row.trailing_zeros().min(8) {
+              let alive = (row >> (7 - x)) & 1 != 0;
+              write!(f, "{}", if alive { '*' } else { '.' })?;
+            }
+            write!(f, "$")?;
+          }
+          writeln!(f)?;
+        }
+        MacrocellNode::Cells([nw, ne, sw, se]) => writeln!(f, "1 {nw} {ne} {sw} {se}")?,
+        MacrocellNode::Node { level, children } => {
+          let [nw, ne, sw, se] = children;
+          writeln!(f, "{level} {nw} {ne} {sw} {se}")?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Parses a macrocell file into the bounding box of its live cells.
+pub fn parse_macrocell(source: &str) -> Result<Pattern, ParseError> {
+  let macrocell = Macrocell::parse(source)?;
+  macrocell.to_pattern().ok_or_else(|| {
+    let line = source.lines().count();
+    ParseError::new(line, 1, ParseErrorKind::OutOfBounds)
+  })
+}
+
+/// Writes `pattern` as a macrocell file.
+pub fn write_macrocell(pattern: &Pattern) -> String {
+  Macrocell::from_pattern(pattern).to_string()
+}
+
+/// Parses a `.`/`*` leaf line of up to 8 rows, each ended by `$`.
+fn parse_leaf(line: &str, number: usize) -> Result<MacrocellNode, ParseError> {
+  let mut rows = [0u8; 8];
+  let (mut x, mut y) = (0, 0);
+
+  for (i, character) in line.trim_end().chars().enumerate() {
+    let error = |kind| Err(ParseError::new(number, i + 1, kind));
+    match character {
+      '.' | '*' if x >= 8 || y >= 8 => return error(ParseErrorKind::OutOfBounds),
+      '.' => x += 1,
+      '*' => {
+        rows[y] |= 1 << (7 - x);
+        x += 1;
+      }
+      '$' if y >= 8 => return error(ParseErrorKind::OutOfBounds),
+      '$' => {
+        y += 1;
+        x = 0;
+      }
+      c => return error(ParseErrorKind::UnexpectedCharacter(c)),
+    }
+  }
+
+  Ok(MacrocellNode::Leaf(rows))
+}
+
+/// Parses a `level nw ne sw se` node line, checking that the children come earlier in
+/// the file and are one level below.
+fn parse_node(
+  line: &str,
+  number: usize,
+  nodes: &[MacrocellNode],
+) -> Result<MacrocellNode, ParseError> {
+  let invalid = |column| ParseError::new(number, column, ParseErrorKind::InvalidNode);
+  let mut values = [0u32; 5];
+  let mut count = 0;
+  for token in line.split_whitespace() {
+    let column = token.as_ptr() as usize - line.as_ptr() as usize + 1;
+    if count == values.len() {
+      return Err(invalid(column));
+    }
+    values[count] = token.parse().map_err(|_| invalid(column))?;
+    count += 1;
+  }
+  if count < values.len() {
+    return Err(invalid(line.len() + 1));
+  }
+
+  let [level, children @ ..] = values;
+  if level == 1 {
+    let states = children.map(u8::try_from);
+    return match states {
+      [Ok(nw), Ok(ne), Ok(sw), Ok(se)] => Ok(MacrocellNode::Cells([nw, ne, sw, se])),
+      _ => Err(ParseError::new(number, 1, ParseErrorKind::InvalidState)),
+    };
+  }
+
+  let child_level = |child: u32| match child {
+    0 => Some(level - 1),
+    _ => nodes
+      .get(child as usize - 1)
+      .map(|node| u32::from(node.level())),
+  };
+  if !(2..63).contains(&level)
+    || children
+      .iter()
+      .any(|&child| child_level(child) != Some(level - 1))
+  {
+    return Err(invalid(1));
+  }
+
+  Ok(MacrocellNode::Node {
+    level: level as u8,
+    children,
+  })
+}
+
+struct Builder<'a> {
+  pattern: &'a Pattern,
+  multi_state: bool,
+  nodes: Vec<MacrocellNode>,
+  ids: HashMap<MacrocellNode, u32>,
+}
+
+impl Builder<'_> {
+  fn state(&self, x: u32, y: u32) -> u8 {
+    match x < self.pattern.width && y < self.pattern.height {
+      true => self.pattern.get(x, y),
+      false => 0,
+    }
+  }
+
+  /// The index of the node for the square of `2^level` cells at `(x, y)`, 0 if it is empty.
+  fn build(&mut self, level: u8, x: u32, y: u32) -> u32 {
+    if x >= self.pattern.width || y >= self.pattern.height {
+      return 0;
+    }
+
+    let node = match level {
+      1 if self.multi_state => MacrocellNode::Cells([
+        self.state(x, y),
+        self.state(x + 1, y),
+        self.state(x, y + 1),
+        self.state(x + 1, y + 1),
+      ]),
+      3 if !self.multi_state => MacrocellNode::Leaf(std::array::from_fn(|dy| {
+        (0..8).fold(0u8, |row, dx| {
+          row | u8::from(self.state(x + dx, y + dy as u32) != 0) << (7 - dx)
+        })
+      })),
+      _ => {
+        let half = 1 << (level - 1);
+        let children = [(0, 0), (half, 0), (0, half), (half, half)]
+          .map(|(dx, dy)| self.build(level - 1, x + dx, y + dy));
+        MacrocellNode::Node { level, children }
+      }
+    };
+
+    let empty = match node {
+      MacrocellNode::Leaf(rows) => rows == [0; 8],
+      MacrocellNode::Cells(states) => states == [0; 4],
+      MacrocellNode::Node { children, .. } => children == [0; 4],
+    };
+    if empty {
+      return 0;
+    }
+
+    *self.ids.entry(node).or_insert_with(|| {
+      self.nodes.push(node);
+      self.nodes.len() as u32
+    })
+  }
+}
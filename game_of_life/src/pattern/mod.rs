@@ -0,0 +1,244 @@
+mod life;
+mod macrocell;
+mod plaintext;
+mod rle;
+
+use std::fmt;
+
+use bevy::math::IVec2;
+
+use crate::rule::{Rule, RuleParseError};
+
+pub use life::{parse_life_105, parse_life_106};
+pub(crate) use macrocell::MacrocellNode;
+pub use macrocell::{Macrocell, parse_macrocell, write_macrocell};
+pub use plaintext::{parse_plaintext, write_plaintext};
+pub use rle::{parse_rle, write_rle};
+
+/// Patterns with more cells than this are rejected instead of allocated.
+pub const MAX_PATTERN_CELLS: u64 = 1 << 28;
+
+/// The pattern file formats that can be imported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternFormat {
+  Rle,
+  /// LifeWiki `.cells` files.
+  Plaintext,
+  Life105,
+  Life106,
+  /// Golly `.mc` quadtree files.
+  Macrocell,
+}
+
+impl PatternFormat {
+  /// Detects the format from the first lines of `source`, falling back to the file
+  /// `extension` when the header is not conclusive.
+  pub fn detect(source: &str, extension: Option<&str>) -> Option<Self> {
+    let mut lines = source
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty());
+    let first = lines.next().unwrap_or_default();
+
+    if first.eq_ignore_ascii_case("#Life 1.05") {
+      return Some(Self::Life105);
+    }
+    if first.eq_ignore_ascii_case("#Life 1.06") {
+      return Some(Self::Life106);
+    }
+    if first.starts_with("[M2]") {
+      return Some(Self::Macrocell);
+    }
+    if first.starts_with('!') {
+      return Some(Self::Plaintext);
+    }
+    let header = std::iter::once(first)
+      .chain(lines)
+      .find(|line| !line.starts_with('#'));
+    if header.is_some_and(|line| line.starts_with('x') && line.contains('=')) {
+      return Some(Self::Rle);
+    }
+
+    match extension?.to_ascii_lowercase().as_str() {
+      "rle" => Some(Self::Rle),
+      "cells" => Some(Self::Plaintext),
+      "lif" | "life" => Some(Self::Life106),
+      "mc" => Some(Self::Macrocell),
+      _ => None,
+    }
+  }
+
+  pub fn parse(self, source: &str) -> Result<Pattern, ParseError> {
+    match self {
+      Self::Rle => parse_rle(source),
+      Self::Plaintext => parse_plaintext(source),
+      Self::Life105 => parse_life_105(source),
+      Self::Life106 => parse_life_106(source),
+      Self::Macrocell => parse_macrocell(source),
+    }
+  }
+}
+
+/// Detects the format of `source` and parses it.
+pub fn parse_pattern(source: &str, extension: Option<&str>) -> Result<Pattern, ParseError> {
+  match PatternFormat::detect(source, extension) {
+    Some(format) => format.parse(source),
+    None => Err(ParseError::new(1, 1, ParseErrorKind::UnknownFormat)),
+  }
+}
+
+/// A rectangular block of cells read from a pattern file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+  pub width: u32,
+  pub height: u32,
+  /// Cell states row by row, 0 is dead and 1 is alive.
+  pub cells: Vec<u8>,
+  pub rule: Option<Rule>,
+  /// Cell of the universe the top left corner was exported from.
+  pub position: Option<IVec2>,
+  /// Generation the pattern was exported at.
+  pub generation: Option<u64>,
+  pub comments: Vec<String>,
+}
+
+impl Pattern {
+  pub fn new(width: u32, height: u32) -> Self {
+    Self {
+      width,
+      height,
+      cells: vec![0; (width * height) as usize],
+      rule: None,
+      position: None,
+      generation: None,
+      comments: vec![],
+    }
+  }
+
+  pub fn get(&self, x: u32, y: u32) -> u8 {
+    self.cells[(x + y * self.width) as usize]
+  }
+
+  pub fn set(&mut self, x: u32, y: u32, state: u8) {
+    self.cells[(x + y * self.width) as usize] = state;
+  }
+
+  /// The pattern turned a quarter turn clockwise. Rotated and flipped patterns lose the
+  /// position they were exported from.
+  pub fn rotated_clockwise(&self) -> Pattern {
+    self.transformed(self.height, self.width, |x, y| (self.height - 1 - y, x))
+  }
+
+  pub fn rotated_counterclockwise(&self) -> Pattern {
+    self.transformed(self.height, self.width, |x, y| (y, self.width - 1 - x))
+  }
+
+  /// The pattern mirrored left to right.
+  pub fn flipped_horizontally(&self) -> Pattern {
+    self.transformed(self.width, self.height, |x, y| (self.width - 1 - x, y))
+  }
+
+  /// The pattern mirrored top to bottom.
+  pub fn flipped_vertically(&self) -> Pattern {
+    self.transformed(self.width, self.height, |x, y| (x, self.height - 1 - y))
+  }
+
+  /// A `width` by `height` copy with every cell moved to where `to` maps it.
+  fn transformed(&self, width: u32, height: u32, to: impl Fn(u32, u32) -> (u32, u32)) -> Pattern {
+    let mut pattern = Pattern {
+      rule: self.rule,
+      generation: self.generation,
+      comments: self.comments.clone(),
+      ..Pattern::new(width, height)
+    };
+    for y in 0..self.height {
+      for x in 0..self.width {
+        let (to_x, to_y) = to(x, y);
+        pattern.set(to_x, to_y, self.get(x, y));
+      }
+    }
+    pattern
+  }
+
+  /// Packs the rows of the pattern into the storage buffer layout, as if it was placed at
+  /// column `offset_x`. Returns the buffer column of the first word and the words of every
+  /// row; cells of the edge words outside the pattern are dead.
+  pub fn pack_rows(&self, offset_x: u32, bits_per_cell: u32) -> (u32, Vec<Vec<u32>>) {
+    let cells_per_word = 32 / bits_per_cell;
+    let state_mask = (1u32 << bits_per_cell) - 1;
+    let first_word = offset_x / cells_per_word;
+    let word_count = (offset_x + self.width).div_ceil(cells_per_word) - first_word;
+
+    let rows = (0..self.height)
+      .map(|y| {
+        let mut words = vec![0u32; word_count as usize];
+        for x in 0..self.width {
+          let state = match bits_per_cell {
+            1 => u32::from(self.get(x, y) == 1),
+            _ => u32::from(self.get(x, y)) & state_mask,
+          };
+          let column = offset_x + x;
+          let shift = (cells_per_word - 1 - column % cells_per_word) * bits_per_cell;
+          words[(column / cells_per_word - first_word) as usize] |= state << shift;
+        }
+        words
+      })
+      .collect();
+
+    (first_word, rows)
+  }
+}
+
+/// A pattern file that could not be parsed, with the 1-based position of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+  pub line: usize,
+  pub column: usize,
+  pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+  MissingHeader,
+  InvalidHeader,
+  InvalidRule(RuleParseError),
+  UnexpectedCharacter(char),
+  InvalidState,
+  InvalidCoordinates,
+  /// Cells past the width or height declared in the header, or a pattern larger than
+  /// `MAX_PATTERN_CELLS`.
+  OutOfBounds,
+  MissingTerminator,
+  /// A macrocell node line that is malformed or refers to a missing child.
+  InvalidNode,
+  /// Neither the header nor the file extension match a supported format.
+  UnknownFormat,
+}
+
+impl ParseError {
+  pub(crate) fn new(line: usize, column: usize, kind: ParseErrorKind) -> Self {
+    Self { line, column, kind }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {}, column {}: ", self.line, self.column)?;
+    match &self.kind {
+      ParseErrorKind::MissingHeader => write!(f, "missing pattern header"),
+      ParseErrorKind::InvalidHeader => write!(f, "malformed header"),
+      ParseErrorKind::InvalidRule(error) => write!(f, "invalid rule: {error}"),
+      ParseErrorKind::UnexpectedCharacter(character) => {
+        write!(f, "unexpected character '{character}'")
+      }
+      ParseErrorKind::InvalidState => write!(f, "invalid cell state"),
+      ParseErrorKind::InvalidCoordinates => write!(f, "expected 'x y' cell coordinates"),
+      ParseErrorKind::OutOfBounds => write!(f, "cells outside the pattern size limits"),
+      ParseErrorKind::MissingTerminator => write!(f, "pattern does not end with '!'"),
+      ParseErrorKind::InvalidNode => write!(f, "invalid macrocell node"),
+      ParseErrorKind::UnknownFormat => write!(f, "unknown pattern format"),
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
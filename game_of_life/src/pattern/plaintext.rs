@@ -0,0 +1,90 @@
+use std::fmt::Write;
+
+use super::{ParseError, ParseErrorKind, Pattern};
+
+/// Parses a LifeWiki plaintext pattern: `!` comment lines followed by one line per row,
+/// with `.` for dead and `O` or `*` for live cells. Rows may be shorter than the widest
+/// one, and empty lines are empty rows. `!Rule:` and `!Generation:` comments written by
+/// `write_plaintext` are read back.
+pub fn parse_plaintext(source: &str) -> Result<Pattern, ParseError> {
+  let mut comments = vec![];
+  let mut rule = None;
+  let mut generation = None;
+  let mut rows: Vec<&str> = vec![];
+
+  for (i, line) in source.lines().enumerate() {
+    let number = i + 1;
+    if let Some(comment) = line.strip_prefix('!') {
+      if let Some(value) = comment.strip_prefix("Rule:") {
+        let column = line.len() - value.trim_start().len() + 1;
+        let parsed = value
+          .trim()
+          .parse()
+          .map_err(|error| ParseError::new(number, column, ParseErrorKind::InvalidRule(error)))?;
+        rule = Some(parsed);
+      } else if let Some(value) = comment.strip_prefix("Generation:") {
+        generation = value.trim().parse().ok();
+      } else {
+        comments.push(comment.trim().to_string());
+      }
+      continue;
+    }
+
+    let line = line.trim_end();
+    if let Some((column, character)) = line
+      .chars()
+      .enumerate()
+      .find(|(_, c)| !matches!(c, '.' | 'O' | '*'))
+    {
+      let kind = ParseErrorKind::UnexpectedCharacter(character);
+      return Err(ParseError::new(number, column + 1, kind));
+    }
+    // blank lines before the first row belong to the comment block
+    if !rows.is_empty() || !line.is_empty() {
+      rows.push(line);
+    }
+  }
+
+  while rows.last().is_some_and(|row| row.is_empty()) {
+    rows.pop();
+  }
+
+  let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+  let mut pattern = Pattern::new(width as u32, rows.len() as u32);
+  pattern.rule = rule;
+  pattern.generation = generation;
+  pattern.comments = comments;
+  for (y, row) in rows.iter().enumerate() {
+    for (x, character) in row.chars().enumerate() {
+      if character != '.' {
+        pattern.set(x as u32, y as u32, 1);
+      }
+    }
+  }
+
+  Ok(pattern)
+}
+
+/// Writes `pattern` in the format read by `parse_plaintext`, with the rule and generation
+/// as comments. Plaintext only knows two states, so all non-dead cells are written alive.
+pub fn write_plaintext(pattern: &Pattern) -> String {
+  let mut cells = String::new();
+  for comment in &pattern.comments {
+    writeln!(cells, "!{comment}").unwrap();
+  }
+  if let Some(rule) = pattern.rule {
+    writeln!(cells, "!Rule: {rule}").unwrap();
+  }
+  if let Some(generation) = pattern.generation {
+    writeln!(cells, "!Generation: {generation}").unwrap();
+  }
+
+  // rows keep their trailing dead cells so that the width and height survive a round trip
+  for y in 0..pattern.height {
+    for x in 0..pattern.width {
+      cells.push(if pattern.get(x, y) == 0 { '.' } else { 'O' });
+    }
+    cells.push('\n');
+  }
+  cells
+}
@@ -0,0 +1,265 @@
+use std::fmt::Write;
+
+use bevy::math::IVec2;
+
+use crate::rule::Rule;
+
+use super::{MAX_PATTERN_CELLS, ParseError, ParseErrorKind, Pattern};
+
+/// Parses a Golly-style run length encoded pattern: `#` comment lines, an
+/// `x = .., y = .., rule = ..` header and `b`/`o` or multi-state `.`/`A`..`yO` cell runs,
+/// with `$` ending a row and `!` ending the pattern.
+pub fn parse_rle(source: &str) -> Result<Pattern, ParseError> {
+  let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line));
+  let mut comments = vec![];
+  let mut comment_rule = None;
+  let mut extended = None;
+
+  let (header_line, header) = loop {
+    match lines.next() {
+      Some((number, line)) if line.starts_with('#') => {
+        // the old `#r` line carries the rule before the header could
+        if let Some(rule) = line.strip_prefix("#r") {
+          comment_rule = Some(parse_rule(rule, number, 3)?);
+        }
+        if let Some(attributes) = line.strip_prefix("#CXRLE") {
+          extended = Some(parse_extended(attributes));
+          continue;
+        }
+        comments.push(line.get(2..).unwrap_or_default().trim().to_string());
+      }
+      Some((_, line)) if line.trim().is_empty() => {}
+      Some(header) => break header,
+      None => {
+        let line = source.lines().count().max(1);
+        return Err(ParseError::new(line, 1, ParseErrorKind::MissingHeader));
+      }
+    }
+  };
+
+  let (width, height, header_rule) = parse_header(header, header_line)?;
+  if u64::from(width) * u64::from(height) > MAX_PATTERN_CELLS {
+    return Err(ParseError::new(header_line, 1, ParseErrorKind::OutOfBounds));
+  }
+  let mut pattern = Pattern::new(width, height);
+  pattern.rule = header_rule.or(comment_rule);
+  pattern.comments = comments;
+  (pattern.position, pattern.generation) = extended.unwrap_or_default();
+
+  let mut x = 0u32;
+  let mut y = 0u32;
+  let mut run: Option<u32> = None;
+  let mut prefix: Option<u32> = None;
+  let mut last_position = (header_line, header.len() + 1);
+
+  for (number, line) in lines {
+    for (i, character) in line.chars().enumerate() {
+      let column = i + 1;
+      last_position = (number, column);
+      let error = |kind| Err(ParseError::new(number, column, kind));
+
+      let state = match character {
+        '0'..='9' => {
+          let digit = character.to_digit(10).unwrap();
+          match run
+            .unwrap_or(0)
+            .checked_mul(10)
+            .and_then(|r| r.checked_add(digit))
+          {
+            Some(value) => run = Some(value),
+            None => return error(ParseErrorKind::OutOfBounds),
+          }
+          continue;
+        }
+        'p'..='y' if prefix.is_none() => {
+          prefix = Some(character as u32 - 'p' as u32 + 1);
+          continue;
+        }
+        'b' | '.' if prefix.is_none() => 0,
+        'o' if prefix.is_none() => 1,
+        'A'..='X' => {
+          let state = prefix.take().unwrap_or(0) * 24 + (character as u32 - 'A' as u32 + 1);
+          match u8::try_from(state) {
+            Ok(state) => state,
+            Err(_) => return error(ParseErrorKind::InvalidState),
+          }
+        }
+        '$' => {
+          match y.checked_add(run.take().unwrap_or(1)) {
+            Some(row) => y = row,
+            None => return error(ParseErrorKind::OutOfBounds),
+          }
+          x = 0;
+          continue;
+        }
+        '!' => return Ok(pattern),
+        c if c.is_whitespace() && prefix.is_none() => continue,
+        c => return error(ParseErrorKind::UnexpectedCharacter(c)),
+      };
+
+      let count = run.take().unwrap_or(1);
+      if state != 0 {
+        if x.checked_add(count).is_none_or(|end| end > width) || y >= height {
+          return error(ParseErrorKind::OutOfBounds);
+        }
+        for dx in 0..count {
+          pattern.set(x + dx, y, state);
+        }
+      }
+      x = x.saturating_add(count);
+    }
+  }
+
+  let (line, column) = last_position;
+  Err(ParseError::new(
+    line,
+    column,
+    ParseErrorKind::MissingTerminator,
+  ))
+}
+
+fn parse_header(header: &str, line: usize) -> Result<(u32, u32, Option<Rule>), ParseError> {
+  let mut width = None;
+  let mut height = None;
+  let mut rule = None;
+
+  let mut offset = 0;
+  while offset < header.len() {
+    let rest = &header[offset..];
+    let item = rest.split(',').next().unwrap_or_default();
+    let column = offset + item.len() - item.trim_start().len() + 1;
+    let invalid = ParseError::new(line, column, ParseErrorKind::InvalidHeader);
+
+    let Some((key, value)) = item.split_once('=') else {
+      return Err(invalid);
+    };
+    match key.trim() {
+      "x" => width = Some(value.trim().parse::<u32>().map_err(|_| invalid)?),
+      "y" => height = Some(value.trim().parse::<u32>().map_err(|_| invalid)?),
+      "rule" => {
+        // Larger than Life rules contain commas, so the rule takes up the rest of the line
+        let value = &rest[key.len() + 1..];
+        let value_column = offset + key.len() + value.len() - value.trim_start().len() + 2;
+        rule = Some(parse_rule(value, line, value_column)?);
+        break;
+      }
+      _ => return Err(invalid),
+    }
+    offset += item.len() + 1;
+  }
+
+  match (width, height) {
+    (Some(width), Some(height)) => Ok((width, height, rule)),
+    _ => Err(ParseError::new(line, 1, ParseErrorKind::MissingHeader)),
+  }
+}
+
+/// Parses the `Pos=x,y Gen=n` attributes of Golly's `#CXRLE` line, skipping malformed ones.
+fn parse_extended(attributes: &str) -> (Option<IVec2>, Option<u64>) {
+  let mut position = None;
+  let mut generation = None;
+  for (key, value) in attributes
+    .split_whitespace()
+    .filter_map(|attribute| attribute.split_once('='))
+  {
+    match key {
+      "Pos" => {
+        position = value
+          .split_once(',')
+          .and_then(|(x, y)| Some(IVec2::new(x.parse().ok()?, y.parse().ok()?)));
+      }
+      "Gen" => generation = value.parse().ok(),
+      _ => {}
+    }
+  }
+  (position, generation)
+}
+
+/// Parses a rule from a header, ignoring Golly's `:T100,100` style topology suffix.
+fn parse_rule(value: &str, line: usize, column: usize) -> Result<Rule, ParseError> {
+  let rule = value.split(':').next().unwrap_or_default();
+  rule
+    .trim()
+    .parse()
+    .map_err(|error| ParseError::new(line, column, ParseErrorKind::InvalidRule(error)))
+}
+
+/// Longest line `write_rle` emits, as recommended by the format.
+const MAX_LINE_LENGTH: usize = 70;
+
+/// Writes `pattern` in the format read by `parse_rle`. Position and generation go into a
+/// `#CXRLE` line and patterns with more than two states use the multi-state cell tokens.
+pub fn write_rle(pattern: &Pattern) -> String {
+  let mut rle = String::new();
+  for comment in &pattern.comments {
+    writeln!(rle, "#C {comment}").unwrap();
+  }
+  if pattern.position.is_some() || pattern.generation.is_some() {
+    rle.push_str("#CXRLE");
+    if let Some(position) = pattern.position {
+      write!(rle, " Pos={},{}", position.x, position.y).unwrap();
+    }
+    if let Some(generation) = pattern.generation {
+      write!(rle, " Gen={generation}").unwrap();
+    }
+    rle.push('\n');
+  }
+  write!(rle, "x = {}, y = {}", pattern.width, pattern.height).unwrap();
+  if let Some(rule) = pattern.rule {
+    write!(rle, ", rule = {rule}").unwrap();
+  }
+  rle.push('\n');
+
+  let multi_state = pattern.rule.is_some_and(|rule| rule.states > 2)
+    || pattern.cells.iter().any(|&state| state > 1);
+  let mut line = String::new();
+  let mut push = |count: u32, token: &str| {
+    let run = match count {
+      1 => token.to_string(),
+      _ => format!("{count}{token}"),
+    };
+    if line.len() + run.len() > MAX_LINE_LENGTH {
+      rle.push_str(&line);
+      rle.push('\n');
+      line.clear();
+    }
+    line.push_str(&run);
+  };
+
+  // empty rows are folded into the `$` run in front of the next non-empty one
+  let mut row_ends = 0;
+  for y in 0..pattern.height {
+    let row = &pattern.cells[(y * pattern.width) as usize..((y + 1) * pattern.width) as usize];
+    let Some(length) = row.iter().rposition(|&state| state != 0) else {
+      row_ends += 1;
+      continue;
+    };
+    if y > 0 {
+      push(row_ends, "$");
+    }
+    row_ends = 1;
+
+    for run in row[..=length].chunk_by(|a, b| a == b) {
+      push(run.len() as u32, &state_token(run[0], multi_state));
+    }
+  }
+  push(1, "!");
+
+  rle.push_str(&line);
+  rle.push('\n');
+  rle
+}
+
+fn state_token(state: u8, multi_state: bool) -> String {
+  match (state, multi_state) {
+    (0, false) => "b".to_string(),
+    (_, false) => "o".to_string(),
+    (0, true) => ".".to_string(),
+    (1..=24, true) => char::from(b'A' + state - 1).to_string(),
+    (_, true) => {
+      let prefix = char::from(b'p' + (state - 1) / 24 - 1);
+      let state = char::from(b'A' + (state - 1) % 24);
+      format!("{prefix}{state}")
+    }
+  }
+}
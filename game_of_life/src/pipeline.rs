@@ -1,3 +1,5 @@
+use std::num::NonZeroU64;
+
 use bevy::{
   asset::DirectAssetAccessExt,
   ecs::{
@@ -8,19 +10,31 @@ use bevy::{
     render_resource::{
       BindGroupLayout, BindGroupLayoutEntries, CachedComputePipelineId, ComputePipelineDescriptor,
       PipelineCache, ShaderStages, StorageTextureAccess, TextureFormat,
-      binding_types::{storage_buffer, texture_storage_2d, uniform_buffer},
+      binding_types::{
+        storage_buffer, storage_buffer_read_only, storage_buffer_read_only_sized,
+        storage_buffer_sized, texture_storage_2d, uniform_buffer, uniform_buffer_sized,
+      },
     },
     renderer::RenderDevice,
   },
 };
 
-use crate::data_structs::Params;
+use crate::{data_structs::Params, selection::RegionOp};
 
 #[derive(Resource)]
 pub struct GLPipeline {
   pub layout: BindGroupLayout,
   pub update_pipeline: CachedComputePipelineId,
+  pub update_generations_pipeline: CachedComputePipelineId,
+  pub update_larger_than_life_pipeline: CachedComputePipelineId,
+  pub update_ages_pipeline: CachedComputePipelineId,
   pub randomize_pipeline: CachedComputePipelineId,
+  pub apply_edits_pipeline: CachedComputePipelineId,
+  pub edit_region_pipeline: CachedComputePipelineId,
+  pub copy_region_pipeline: CachedComputePipelineId,
+  pub resize_pipeline: CachedComputePipelineId,
+  pub count_population_pipeline: CachedComputePipelineId,
+  pub hash_generation_pipeline: CachedComputePipelineId,
   pub display_pipeline: CachedComputePipelineId,
 }
 
@@ -36,6 +50,14 @@ impl FromWorld for GLPipeline {
         (
           uniform_buffer::<Params>(false),
           texture_storage_2d(TextureFormat::Rgba8Unorm, StorageTextureAccess::ReadWrite),
+          storage_buffer_read_only::<Vec<u32>>(false),
+          storage_buffer::<Vec<u32>>(false),
+          storage_buffer_read_only_sized(false, None),
+          storage_buffer_read_only::<Vec<u32>>(false),
+          uniform_buffer_sized(false, NonZeroU64::new(size_of::<RegionOp>() as u64)),
+          storage_buffer::<Vec<u32>>(false),
+          storage_buffer::<u32>(false),
+          storage_buffer_sized(false, NonZeroU64::new(8)),
           storage_buffer::<Vec<u32>>(false),
         ),
       ),
@@ -54,6 +76,38 @@ impl FromWorld for GLPipeline {
       zero_initialize_workgroup_memory: false,
     });
 
+    let update_generations_pipeline =
+      pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: None,
+        layout: vec![layout.clone()],
+        push_constant_ranges: vec![],
+        shader: shader.clone(),
+        shader_defs: vec![],
+        entry_point: "update_generations".into(),
+        zero_initialize_workgroup_memory: false,
+      });
+
+    let update_larger_than_life_pipeline =
+      pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: None,
+        layout: vec![layout.clone()],
+        push_constant_ranges: vec![],
+        shader: shader.clone(),
+        shader_defs: vec![],
+        entry_point: "update_larger_than_life".into(),
+        zero_initialize_workgroup_memory: false,
+      });
+
+    let update_ages_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+      label: None,
+      layout: vec![layout.clone()],
+      push_constant_ranges: vec![],
+      shader: shader.clone(),
+      shader_defs: vec![],
+      entry_point: "update_ages".into(),
+      zero_initialize_workgroup_memory: false,
+    });
+
     let randomize_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
       label: None,
       layout: vec![layout.clone()],
@@ -64,6 +118,68 @@ impl FromWorld for GLPipeline {
       zero_initialize_workgroup_memory: false,
     });
 
+    let apply_edits_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+      label: None,
+      layout: vec![layout.clone()],
+      push_constant_ranges: vec![],
+      shader: shader.clone(),
+      shader_defs: vec![],
+      entry_point: "apply_edits".into(),
+      zero_initialize_workgroup_memory: false,
+    });
+
+    let edit_region_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+      label: None,
+      layout: vec![layout.clone()],
+      push_constant_ranges: vec![],
+      shader: shader.clone(),
+      shader_defs: vec![],
+      entry_point: "edit_region".into(),
+      zero_initialize_workgroup_memory: false,
+    });
+
+    let copy_region_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+      label: None,
+      layout: vec![layout.clone()],
+      push_constant_ranges: vec![],
+      shader: shader.clone(),
+      shader_defs: vec![],
+      entry_point: "copy_region".into(),
+      zero_initialize_workgroup_memory: false,
+    });
+
+    let resize_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+      label: None,
+      layout: vec![layout.clone()],
+      push_constant_ranges: vec![],
+      shader: shader.clone(),
+      shader_defs: vec![],
+      entry_point: "resize".into(),
+      zero_initialize_workgroup_memory: false,
+    });
+
+    let count_population_pipeline =
+      pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: None,
+        layout: vec![layout.clone()],
+        push_constant_ranges: vec![],
+        shader: shader.clone(),
+        shader_defs: vec![],
+        entry_point: "count_population".into(),
+        zero_initialize_workgroup_memory: false,
+      });
+
+    let hash_generation_pipeline =
+      pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: None,
+        layout: vec![layout.clone()],
+        push_constant_ranges: vec![],
+        shader: shader.clone(),
+        shader_defs: vec![],
+        entry_point: "hash_generation".into(),
+        zero_initialize_workgroup_memory: false,
+      });
+
     let display_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
       label: None,
       layout: vec![layout.clone()],
@@ -77,7 +193,16 @@ impl FromWorld for GLPipeline {
     GLPipeline {
       layout,
       update_pipeline,
+      update_generations_pipeline,
+      update_larger_than_life_pipeline,
+      update_ages_pipeline,
       randomize_pipeline,
+      apply_edits_pipeline,
+      edit_region_pipeline,
+      copy_region_pipeline,
+      resize_pipeline,
+      count_population_pipeline,
+      hash_generation_pipeline,
       display_pipeline,
     }
   }
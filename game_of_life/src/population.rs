@@ -0,0 +1,192 @@
+use std::{
+  mem,
+  sync::{Arc, Mutex},
+};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+    world::{FromWorld, World},
+  },
+  log::{error, info},
+  render::{
+    extract_resource::ExtractResource,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, MapMode},
+    renderer::RenderDevice,
+  },
+};
+
+use crate::oscillation::{Fingerprint, FingerprintReadback};
+
+/// How often the live cells are counted on the GPU.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, ExtractResource)]
+pub struct PopulationCounting {
+  /// Generations between counts, 0 stops counting.
+  pub interval: u32,
+}
+
+impl Default for PopulationCounting {
+  fn default() -> Self {
+    Self { interval: 1 }
+  }
+}
+
+impl PopulationCounting {
+  /// The generations of a batch of `batch` generations after `generation` that are counted,
+  /// with the number of generations into the batch they are computed after.
+  pub fn samples(&self, generation: u64, batch: u32) -> impl Iterator<Item = (u32, u64)> + use<> {
+    let interval = u64::from(self.interval);
+    (1..=batch)
+      .map(move |after| (after, generation + u64::from(after)))
+      .filter(move |&(_, generation)| interval > 0 && generation.is_multiple_of(interval))
+  }
+}
+
+/// Bytes of the staging buffer per sample: the count, then the two halves of the
+/// fingerprint.
+const SAMPLE_SIZE: u64 = 12;
+
+/// The latest count of live cells, not counting the dying states of Generations rules.
+#[derive(Resource, Event, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Population {
+  pub generation: u64,
+  pub count: u64,
+}
+
+/// Shared with the render world, whose buffer mapping callbacks push the counts into it.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct PopulationReadback(Arc<Mutex<Vec<Population>>>);
+
+/// Sends the counts read back since the last frame as `Population` events, oldest first,
+/// and keeps the latest one as a resource.
+pub fn receive_population(
+  readback: Res<PopulationReadback>,
+  mut population: ResMut<Population>,
+  mut counts: EventWriter<Population>,
+) {
+  let completed = mem::take(&mut *readback.0.lock().unwrap());
+  if let Some(&latest) = completed.last() {
+    *population = latest;
+  }
+  counts.write_batch(completed);
+}
+
+pub fn print_population(population: Res<Population>) {
+  info!(
+    "Population {} at generation {}",
+    population.count, population.generation
+  );
+}
+
+/// A count of the latest generation after `after` generations of the frame's batch.
+#[derive(Clone, Copy, Debug)]
+pub struct PopulationSample {
+  pub after: u32,
+  pub generation: u64,
+}
+
+/// The counter the `count_population` kernel adds to, the fingerprint `hash_generation`
+/// adds to, and the counts the render graph node makes this frame.
+#[derive(Resource)]
+pub struct GLPopulation {
+  pub counter: Buffer,
+  pub fingerprint: Buffer,
+  pub samples: Vec<PopulationSample>,
+  /// Whether the samples of this frame are hashed as well as counted.
+  pub hashing: bool,
+  /// Receives the counter and fingerprint after every sample, in the order of `samples`.
+  pub staging: Option<Buffer>,
+  /// Whether the universe was replaced, so the next frame is counted even between samples.
+  pub recount: bool,
+}
+
+impl FromWorld for GLPopulation {
+  fn from_world(world: &mut World) -> Self {
+    let device = world.resource::<RenderDevice>();
+    let [counter, fingerprint] = [4, 8].map(|size| {
+      device.create_buffer(&BufferDescriptor {
+        label: None,
+        size,
+        usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE,
+        mapped_at_creation: false,
+      })
+    });
+    Self {
+      counter,
+      fingerprint,
+      samples: Vec::new(),
+      hashing: false,
+      staging: None,
+      recount: false,
+    }
+  }
+}
+
+impl GLPopulation {
+  /// Plans the counts of a frame, allocating the staging buffer they are copied into.
+  pub fn plan(&mut self, samples: Vec<PopulationSample>, hashing: bool, device: &RenderDevice) {
+    self.staging = (!samples.is_empty()).then(|| {
+      device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: samples.len() as u64 * SAMPLE_SIZE,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+      })
+    });
+    self.samples = samples;
+    self.hashing = hashing;
+  }
+
+  /// Copies the counter and fingerprint into the staging slot of `sample`, clearing them for
+  /// the next one.
+  pub fn copy_sample(&self, encoder: &mut CommandEncoder, staging: &Buffer, sample: usize) {
+    let offset = sample as u64 * SAMPLE_SIZE;
+    encoder.copy_buffer_to_buffer(&self.counter, 0, staging, offset, 4);
+    encoder.copy_buffer_to_buffer(&self.fingerprint, 0, staging, offset + 4, 8);
+    encoder.clear_buffer(&self.counter, 0, None);
+    encoder.clear_buffer(&self.fingerprint, 0, None);
+  }
+}
+
+/// Maps the counts and fingerprints copied by the render graph node, without waiting for
+/// the GPU.
+pub fn map_population(
+  mut population: ResMut<GLPopulation>,
+  readback: Res<PopulationReadback>,
+  fingerprint_readback: Res<FingerprintReadback>,
+) {
+  let Some(staging) = population.staging.take() else {
+    return;
+  };
+  let samples = mem::take(&mut population.samples);
+  let hashing = population.hashing;
+  let completed = readback.0.clone();
+  let fingerprints = fingerprint_readback.0.clone();
+  let mapped = staging.clone();
+
+  staging.slice(..).map_async(MapMode::Read, move |result| {
+    if let Err(err) = result {
+      error!("Failed to map the population buffer: {err}");
+      return;
+    }
+    let words: Vec<u32> = bytemuck::cast_slice(&mapped.slice(..).get_mapped_range()).to_vec();
+    mapped.unmap();
+
+    let mut completed = completed.lock().unwrap();
+    let mut fingerprints = fingerprints.lock().unwrap();
+    for (sample, words) in samples.iter().zip(words.chunks_exact(3)) {
+      completed.push(Population {
+        generation: sample.generation,
+        count: u64::from(words[0]),
+      });
+      if hashing {
+        fingerprints.push(Fingerprint {
+          generation: sample.generation,
+          hash: u64::from(words[1]) | (u64::from(words[2]) << 32),
+        });
+      }
+    }
+  });
+}
@@ -0,0 +1,253 @@
+use std::{
+  mem,
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+  },
+};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::error,
+  math::{URect, UVec2},
+  render::{
+    extract_resource::ExtractResource,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages, MapMode},
+    renderer::RenderDevice,
+  },
+};
+
+use crate::{
+  bind_group::GLBufferLayout,
+  data_structs::{ComputeState, Generation, Params},
+  export::ExportBounds,
+  pattern::Pattern,
+  rule::Rule,
+};
+
+/// Asks for a copy of the universe, which arrives as a `UniverseSnapshot` event a few
+/// frames later, once the GPU is done with it.
+#[derive(Event, Clone, Copy, Debug, Default)]
+pub struct RequestSnapshot;
+
+/// The contents of the generation buffer, read back from the GPU.
+#[derive(Event, Clone)]
+pub struct UniverseSnapshot {
+  pub generation: u64,
+  /// Logical size of the universe in cells.
+  pub size: UVec2,
+  /// The rule the universe was running, which the words are laid out for.
+  pub rule: Rule,
+  bits_per_cell: u32,
+  buffer_size_x: u32,
+  words: Arc<Vec<u32>>,
+}
+
+impl UniverseSnapshot {
+  pub(crate) fn new(
+    generation: u64,
+    size: UVec2,
+    rule: Rule,
+    buffer_size_x: u32,
+    words: Vec<u32>,
+  ) -> Self {
+    Self {
+      generation,
+      size,
+      rule,
+      bits_per_cell: rule.bits_per_cell(),
+      buffer_size_x,
+      words: Arc::new(words),
+    }
+  }
+
+  fn cells_per_word(&self) -> u32 {
+    32 / self.bits_per_cell
+  }
+
+  pub fn get(&self, x: u32, y: u32) -> u8 {
+    let cells_per_word = self.cells_per_word();
+    let word = self.words[(y * self.buffer_size_x + x / cells_per_word) as usize];
+    let shift = (cells_per_word - 1 - x % cells_per_word) * self.bits_per_cell;
+    let state_mask = (1u32 << self.bits_per_cell) - 1;
+    ((word >> shift) & state_mask) as u8
+  }
+
+  pub fn buffer_size_x(&self) -> u32 {
+    self.buffer_size_x
+  }
+
+  /// The storage buffer contents, in the layout of the rule at the time of the snapshot.
+  pub fn words(&self) -> &[u32] {
+    &self.words
+  }
+
+  /// Number of live cells, not counting the dying states of Generations rules.
+  pub fn population(&self) -> u64 {
+    let cells = self.words.iter();
+    match self.bits_per_cell {
+      1 => cells.map(|word| u64::from(word.count_ones())).sum(),
+      _ => cells
+        .flat_map(|word| word.to_be_bytes())
+        .filter(|&state| state == 1)
+        .count() as u64,
+    }
+  }
+
+  /// The smallest rectangle holding every non-dead cell, `None` if there are none.
+  pub fn live_bounds(&self) -> Option<URect> {
+    let cells_per_word = self.cells_per_word();
+    let mut bounds: Option<URect> = None;
+
+    for (y, row) in self.words.chunks(self.buffer_size_x as usize).enumerate() {
+      let first = row.iter().position(|&word| word != 0);
+      let last = row.iter().rposition(|&word| word != 0);
+      let (Some(first), Some(last)) = (first, last) else {
+        continue;
+      };
+
+      // the leftmost cell of a word sits in its most significant bits
+      let min_x = first as u32 * cells_per_word + row[first].leading_zeros() / self.bits_per_cell;
+      let max_x =
+        last as u32 * cells_per_word + (31 - row[last].trailing_zeros()) / self.bits_per_cell;
+      let row_bounds = URect::new(min_x, y as u32, max_x + 1, y as u32 + 1);
+      bounds = Some(bounds.map_or(row_bounds, |bounds| bounds.union(row_bounds)));
+    }
+
+    bounds
+  }
+
+  /// Copies the cells inside `bounds` into a pattern that remembers where it came from.
+  pub fn to_pattern(&self, bounds: URect) -> Pattern {
+    let size = bounds.size();
+    let mut pattern = Pattern::new(size.x, size.y);
+    for y in 0..size.y {
+      for x in 0..size.x {
+        pattern.set(x, y, self.get(bounds.min.x + x, bounds.min.y + y));
+      }
+    }
+    pattern.position = Some(bounds.min.as_ivec2());
+    pattern.generation = Some(self.generation);
+    pattern
+  }
+
+  /// The pattern an export of `bounds` writes: the cells along with the rule, the generation
+  /// and the position they were copied from.
+  pub fn export_pattern(&self, bounds: ExportBounds) -> Pattern {
+    let bounds = match bounds {
+      ExportBounds::Universe => URect::from_corners(UVec2::ZERO, self.size),
+      ExportBounds::LiveCells => self.live_bounds().unwrap_or_default(),
+    };
+    let mut pattern = self.to_pattern(bounds);
+    pattern.rule = Some(self.rule);
+    pattern
+  }
+}
+
+/// Shared between the main and the render world: the main world raises `requested`, the
+/// buffer mapping callbacks push their snapshots into `completed`.
+#[derive(Resource, Clone, ExtractResource, Default)]
+pub struct Readback {
+  requested: Arc<AtomicBool>,
+  completed: Arc<Mutex<Vec<UniverseSnapshot>>>,
+}
+
+pub struct StagingReadback {
+  pub staging: Buffer,
+  buffer_size_x: u32,
+  cell_count_x: u32,
+  buffer_size_y: u32,
+  rule: Rule,
+}
+
+/// Staging buffers the render graph node copies the latest generation into this frame.
+#[derive(Resource, Default)]
+pub struct GLReadbacks {
+  pub requested: Vec<StagingReadback>,
+}
+
+pub fn request_snapshots(mut requests: EventReader<RequestSnapshot>, readback: Res<Readback>) {
+  if requests.read().count() > 0 {
+    readback.requested.store(true, Ordering::Relaxed);
+  }
+}
+
+pub fn receive_snapshots(readback: Res<Readback>, mut snapshots: EventWriter<UniverseSnapshot>) {
+  let completed = mem::take(&mut *readback.completed.lock().unwrap());
+  snapshots.write_batch(completed);
+}
+
+/// Allocates a staging buffer for a requested snapshot once the buffers hold a generation.
+pub fn prepare_readback(
+  readback: Res<Readback>,
+  mut readbacks: ResMut<GLReadbacks>,
+  state: Option<Res<ComputeState>>,
+  params: Res<Params>,
+  rule: Res<Rule>,
+  layout: Option<Res<GLBufferLayout>>,
+  device: Res<RenderDevice>,
+) {
+  let ready = matches!(
+    state.as_deref(),
+    Some(ComputeState::STEP | ComputeState::WAIT)
+  ) && layout.is_some_and(|layout| layout.matches(&params));
+  if !ready || !readback.requested.swap(false, Ordering::Relaxed) {
+    return;
+  }
+
+  let staging = device.create_buffer(&BufferDescriptor {
+    label: None,
+    size: u64::from(params.buffer_size_x * params.buffer_size_y) * 4,
+    usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+    mapped_at_creation: false,
+  });
+  readbacks.requested.push(StagingReadback {
+    staging,
+    buffer_size_x: params.buffer_size_x,
+    cell_count_x: params.cell_count_x,
+    buffer_size_y: params.buffer_size_y,
+    rule: *rule,
+  });
+}
+
+/// Maps the staging buffers filled by the render graph. The callbacks run once the GPU has
+/// finished the copy, so the frame never waits for them.
+pub fn map_readbacks(
+  mut readbacks: ResMut<GLReadbacks>,
+  readback: Res<Readback>,
+  generation: Res<Generation>,
+) {
+  for request in readbacks.requested.drain(..) {
+    let completed = readback.completed.clone();
+    let generation = generation.get();
+    let StagingReadback {
+      staging,
+      buffer_size_x,
+      cell_count_x,
+      buffer_size_y,
+      rule,
+    } = request;
+    let mapped = staging.clone();
+
+    staging.slice(..).map_async(MapMode::Read, move |result| {
+      if let Err(err) = result {
+        error!("Failed to map the readback buffer: {err}");
+        return;
+      }
+      let words = bytemuck::cast_slice(&mapped.slice(..).get_mapped_range()).to_vec();
+      mapped.unmap();
+
+      completed.lock().unwrap().push(UniverseSnapshot::new(
+        generation,
+        UVec2::new(cell_count_x, buffer_size_y),
+        rule,
+        buffer_size_x,
+        words,
+      ));
+    });
+  }
+}
@@ -2,38 +2,117 @@ use bevy::{
   ecs::world::World,
   render::{
     render_graph::{self, RenderLabel},
-    render_resource::{ComputePassDescriptor, PipelineCache},
-    renderer::RenderContext,
+    render_resource::{Buffer, CachedComputePipelineId, ComputePassDescriptor, PipelineCache},
+    renderer::{RenderContext, RenderDevice},
   },
   time::Time,
 };
 
 use crate::{
-  bind_group::GLBindGroup,
-  data_structs::{ComputeState, Params, Telemetry},
+  bind_group::{GLAges, GLBindGroup, GLBuffers, GLResize},
+  controls::SimulationControls,
+  data_structs::{ComputeState, Generation, GenerationLimit, Params},
+  history::{GLHistory, PendingRewind},
+  oscillation::OscillationDetection,
+  paint::GLEdits,
   pipeline::GLPipeline,
+  population::{GLPopulation, PopulationCounting, PopulationSample},
+  readback::GLReadbacks,
+  selection::{GLRegion, REGION_COPY},
+  telemetry::{Clock, GLTimestamps, Span, TelemetryReadback, Timing},
 };
 
-const COMPUTE_WG_SIZE: u32 = 1024;
+pub(crate) const COMPUTE_WG_SIZE: u32 = 1024;
 const DISPLAY_WG_SIZE: u32 = 32;
+const LTL_TILE_X: u32 = 32;
+const LTL_TILE_Y: u32 = 8;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub struct GLNodeLabel;
 
 pub struct GLNode {
   last_step_time: Option<f32>,
-  target_tps: u32,
+  // index of the bind group whose `current` buffer holds the latest generation
+  front: usize,
+  // generations the STEP and REWIND states compute, one dispatch each
+  batch: u32,
+  // single steps of `SimulationControls` already run
+  steps_taken: u64,
+  display: bool,
 }
 
 impl Default for GLNode {
   fn default() -> Self {
     Self {
       last_step_time: None,
-      target_tps: 10,
+      front: 0,
+      batch: 1,
+      steps_taken: 0,
+      display: true,
     }
   }
 }
 
+impl GLNode {
+  /// A node for runs without a window, which has no display pass.
+  pub fn headless() -> Self {
+    Self {
+      display: false,
+      ..Self::default()
+    }
+  }
+
+  fn start_step(&mut self, batch: u32, single_step: bool, generation: &Generation) {
+    self.batch = batch;
+    if single_step {
+      self.steps_taken += 1;
+    }
+    generation.advance(u64::from(batch));
+  }
+}
+
+/// The update kernel for the rule in `params`, with its workgroup counts.
+fn update_pass(pipeline: &GLPipeline, params: &Params) -> (CachedComputePipelineId, u32, u32) {
+  let compute_wg = (params.buffer_size_x * params.buffer_size_y).div_ceil(COMPUTE_WG_SIZE);
+  if params.range > 0 {
+    // larger than life works on 2d tiles of cells instead of one word per invocation
+    return (
+      pipeline.update_larger_than_life_pipeline,
+      params.cell_count_x.div_ceil(LTL_TILE_X),
+      params.buffer_size_y.div_ceil(LTL_TILE_Y),
+    );
+  }
+  match params.bits_per_cell {
+    1 => (pipeline.update_pipeline, compute_wg, 1),
+    _ => (pipeline.update_generations_pipeline, compute_wg, 1),
+  }
+}
+
+/// Copies the latest generation into the history slots saved after `after` generations.
+fn save_keyframes(
+  render_context: &mut RenderContext,
+  history: Option<&GLHistory>,
+  after: u32,
+  source: &Buffer,
+) {
+  let Some(history) = history else {
+    return;
+  };
+  let Some(buffer) = &history.buffer else {
+    return;
+  };
+  for save in history.saves.iter().filter(|save| save.after == after) {
+    let offset = u64::from(save.slot) * history.frame_size();
+    render_context.command_encoder().copy_buffer_to_buffer(
+      source,
+      0,
+      buffer,
+      offset,
+      history.frame_size(),
+    );
+  }
+}
+
 impl render_graph::Node for GLNode {
   fn run(
     &self,
@@ -52,42 +131,267 @@ impl render_graph::Node for GLNode {
       return Ok(());
     };
 
-    let compute_wg =
-      (params.buffer_size_x * params.buffer_size_y + COMPUTE_WG_SIZE - 1) / (COMPUTE_WG_SIZE);
-    let display_wg_x = (params.resolution_x + DISPLAY_WG_SIZE - 1) / (DISPLAY_WG_SIZE);
-    let display_wg_y = (params.resolution_y + DISPLAY_WG_SIZE - 1) / (DISPLAY_WG_SIZE);
+    let compute_wg = (params.buffer_size_x * params.buffer_size_y).div_ceil(COMPUTE_WG_SIZE);
+    let display_wg_x = params.resolution_x.div_ceil(DISPLAY_WG_SIZE);
+    let display_wg_y = params.resolution_y.div_ceil(DISPLAY_WG_SIZE);
+    let Some(buffers) = world.get_resource::<GLBuffers>() else {
+      return Ok(());
+    };
+    let history = world.get_resource::<GLHistory>();
+
+    // a new soup or a rewind starts the ages over, no cell of it has aged yet
+    let ages = world.get_resource::<GLAges>().filter(|ages| ages.tracked);
+    if matches!(state, ComputeState::RANDOMIZE | ComputeState::REWIND)
+      && let Some(ages) = ages
+    {
+      render_context
+        .command_encoder()
+        .clear_buffer(&ages.buffer, 0, None);
+    }
+
+    // the keyframe rewound to replaces the latest generation, before anything else sees it
+    if *state == ComputeState::REWIND
+      && let Some(history) = history
+      && let Some(buffer) = &history.buffer
+      && let Some(slot) = history.restore
+    {
+      render_context.command_encoder().copy_buffer_to_buffer(
+        buffer,
+        u64::from(slot) * history.frame_size(),
+        &buffers.0[self.front],
+        0,
+        history.frame_size(),
+      );
+    }
+
+    // the cells of a resized universe are copied over first, everything after works on them
+    let mut front = self.front;
+    if *state == ComputeState::RESIZE
+      && let Some(resize) = world.get_resource::<GLResize>()
+      && let Some(resize_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.resize_pipeline)
+    {
+      let mut pass = render_context
+        .command_encoder()
+        .begin_compute_pass(&ComputePassDescriptor::default());
+      pass.set_bind_group(0, &resize.bind_groups[front], &[]);
+      pass.set_pipeline(resize_pipeline);
+      pass.dispatch_workgroups(compute_wg, 1, 1);
+      front = 1 - front;
+    }
 
     let mut pass = render_context
       .command_encoder()
       .begin_compute_pass(&ComputePassDescriptor::default());
-    pass.set_bind_group(0, &bind_group.0, &[]);
+    pass.set_bind_group(0, &bind_group.0[front], &[]);
 
-    match state {
-      ComputeState::RANDOMIZE => {
-        let Some(randomize_pipeline) =
-          pipeline_cache.get_compute_pipeline(pipeline.randomize_pipeline)
-        else {
-          return Ok(());
-        };
+    // `next` of the other bind group is the buffer holding the latest generation
+    if let Some(edits) = world.get_resource::<GLEdits>()
+      && edits.count > 0
+      && let Some(edit_pipeline) =
+        pipeline_cache.get_compute_pipeline(pipeline.apply_edits_pipeline)
+    {
+      pass.set_bind_group(0, &bind_group.0[1 - front], &[]);
+      pass.set_pipeline(edit_pipeline);
+      pass.dispatch_workgroups(edits.count.div_ceil(COMPUTE_WG_SIZE), 1, 1);
+      pass.set_bind_group(0, &bind_group.0[front], &[]);
+    }
 
-        pass.set_pipeline(randomize_pipeline);
+    // region edits work on the latest generation too, copies read it before cuts clear it
+    let region = world.get_resource::<GLRegion>();
+    if let Some(op) = region.and_then(|region| region.op) {
+      pass.set_bind_group(0, &bind_group.0[1 - front], &[]);
+      if let Some(words) = region.and_then(|region| region.copied_words(params))
+        && let Some(copy_pipeline) =
+          pipeline_cache.get_compute_pipeline(pipeline.copy_region_pipeline)
+      {
+        pass.set_pipeline(copy_pipeline);
+        pass.dispatch_workgroups(words.div_ceil(COMPUTE_WG_SIZE), 1, 1);
+      }
+      if op.kind != REGION_COPY
+        && let Some(edit_pipeline) =
+          pipeline_cache.get_compute_pipeline(pipeline.edit_region_pipeline)
+      {
+        pass.set_pipeline(edit_pipeline);
         pass.dispatch_workgroups(compute_wg, 1, 1);
       }
-      ComputeState::STEP => {
-        let Some(update_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.update_pipeline)
-        else {
-          return Ok(());
-        };
+      pass.set_bind_group(0, &bind_group.0[front], &[]);
+    }
 
-        pass.set_pipeline(update_pipeline);
-        pass.dispatch_workgroups(compute_wg, 1, 1);
+    // passes producing a generation write into `next`, which becomes `current` after the swap
+    let (generation_pass, generations) = match state {
+      ComputeState::RANDOMIZE => (Some((pipeline.randomize_pipeline, compute_wg, 1)), 1),
+      ComputeState::STEP | ComputeState::REWIND => {
+        (Some(update_pass(pipeline, params)), self.batch)
       }
-      _ => {}
+      _ => (None, 0),
+    };
+
+    let mut display_front = front;
+    let generation_pass = generation_pass.and_then(|(generation_pipeline, wg_x, wg_y)| {
+      Some((
+        pipeline_cache.get_compute_pipeline(generation_pipeline)?,
+        wg_x,
+        wg_y,
+      ))
+    });
+    if generation_pass.is_none() && generations > 0 {
+      return Ok(());
     }
+    // the ages follow every generation the update kernels compute
+    let ages_pipeline = ages
+      .filter(|_| matches!(state, ComputeState::STEP | ComputeState::REWIND))
+      .and_then(|ages| {
+        pipeline_cache
+          .get_compute_pipeline(pipeline.update_ages_pipeline)
+          .map(|ages_pipeline| (ages_pipeline, ages.workgroups))
+      });
+    let population = world.get_resource::<GLPopulation>();
+    let count_pipeline = pipeline_cache.get_compute_pipeline(pipeline.count_population_pipeline);
+    let hash_pipeline = population
+      .filter(|population| population.hashing)
+      .and_then(|_| pipeline_cache.get_compute_pipeline(pipeline.hash_generation_pipeline));
+    let timestamps = world.get_resource::<GLTimestamps>();
+    let telemetry = world.get_resource::<TelemetryReadback>();
+    let sample_at = |after: u32| {
+      let population = population?;
+      let sample = population
+        .samples
+        .iter()
+        .position(|sample| sample.after == after)?;
+      Some((population, population.staging.as_ref()?, sample))
+    };
+    let saved_at = |after: u32| {
+      history.is_some_and(|history| history.saves.iter().any(|save| save.after == after))
+    };
 
-    if let Some(display_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.display_pipeline) {
+    // the generations run in passes of their own, so the timestamps around them leave out the
+    // edits; keyframes and counts split them up since copies cannot be recorded in a pass
+    let span = match state {
+      ComputeState::RANDOMIZE => Span::Randomize,
+      _ => Span::Update,
+    };
+    let timer = timestamps
+      .filter(|_| generations > 0)
+      .map(|timestamps| timestamps.start(span));
+    let last_split = (0..generations)
+      .rev()
+      .find(|&after| saved_at(after) || sample_at(after).is_some())
+      .unwrap_or(0);
+    let mut batch_pass_open = false;
+    for after in 0..=generations {
+      let sample = sample_at(after);
+      if saved_at(after) || sample.is_some() {
+        drop(pass);
+        if let Some(count_pipeline) = count_pipeline
+          && sample.is_some()
+        {
+          let mut count_pass = render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor::default());
+          count_pass.set_pipeline(count_pipeline);
+          count_pass.set_bind_group(0, &bind_group.0[display_front], &[]);
+          count_pass.dispatch_workgroups(compute_wg, 1, 1);
+          if let Some(hash_pipeline) = hash_pipeline {
+            count_pass.set_pipeline(hash_pipeline);
+            count_pass.dispatch_workgroups(compute_wg, 1, 1);
+          }
+        }
+        save_keyframes(render_context, history, after, &buffers.0[display_front]);
+        if let Some((population, staging, sample)) = sample {
+          population.copy_sample(render_context.command_encoder(), staging, sample);
+        }
+        pass = render_context
+          .command_encoder()
+          .begin_compute_pass(&ComputePassDescriptor::default());
+        batch_pass_open = false;
+      }
+
+      let Some((generation_pipeline, wg_x, wg_y)) = generation_pass else {
+        continue;
+      };
+      if after == generations {
+        break;
+      }
+      if !batch_pass_open {
+        drop(pass);
+        let writes = timestamps
+          .zip(timer.as_ref())
+          .and_then(|(timestamps, timer)| {
+            timestamps.writes(timer, after == 0, after == last_split)
+          });
+        pass = render_context
+          .command_encoder()
+          .begin_compute_pass(&ComputePassDescriptor {
+            label: None,
+            timestamp_writes: writes,
+          });
+        batch_pass_open = true;
+      }
+      pass.set_pipeline(generation_pipeline);
+      pass.set_bind_group(0, &bind_group.0[display_front], &[]);
+      pass.dispatch_workgroups(wg_x, wg_y, 1);
+      if let Some((ages_pipeline, age_wg)) = ages_pipeline {
+        pass.set_pipeline(ages_pipeline);
+        pass.dispatch_workgroups(age_wg, 1, 1);
+      }
+      display_front = 1 - display_front;
+    }
+    if let Some((timestamps, timer)) = timestamps.zip(timer) {
+      timestamps.finish(timer, telemetry);
+    }
+
+    if self.display
+      && let Some(display_pipeline) = pipeline_cache.get_compute_pipeline(pipeline.display_pipeline)
+    {
+      drop(pass);
+      let timer = timestamps.map(|timestamps| timestamps.start(Span::Display));
+      let writes = timestamps
+        .zip(timer.as_ref())
+        .and_then(|(timestamps, timer)| timestamps.writes(timer, true, true));
+      pass = render_context
+        .command_encoder()
+        .begin_compute_pass(&ComputePassDescriptor {
+          label: None,
+          timestamp_writes: writes,
+        });
+      pass.set_bind_group(0, &bind_group.0[display_front], &[]);
       pass.set_pipeline(display_pipeline);
       pass.dispatch_workgroups(display_wg_x, display_wg_y, 1);
+      drop(pass);
+      if let Some((timestamps, timer)) = timestamps.zip(timer) {
+        timestamps.finish(timer, telemetry);
+      }
+    } else {
+      drop(pass);
+    }
+
+    // the copies see the generation produced above, the buffers are mapped after submission
+    let readbacks = world.resource::<GLReadbacks>();
+    let source = &buffers.0[display_front];
+    for readback in &readbacks.requested {
+      render_context.command_encoder().copy_buffer_to_buffer(
+        source,
+        0,
+        &readback.staging,
+        0,
+        readback.staging.size(),
+      );
+    }
+
+    if let Some(region) = region
+      && let Some(staging) = &region.staging
+    {
+      render_context.command_encoder().copy_buffer_to_buffer(
+        &region.clipboard,
+        0,
+        &staging.buffer,
+        0,
+        staging.buffer.size(),
+      );
+    }
+
+    if let Some(timestamps) = timestamps {
+      timestamps.resolve(render_context);
     }
 
     Ok(())
@@ -95,43 +399,203 @@ impl render_graph::Node for GLNode {
 
   fn update(&mut self, world: &mut World) {
     let elapsed_secs = world.resource::<Time>().elapsed_secs();
-    let randomize_pipeline = world.resource::<GLPipeline>().randomize_pipeline;
-    let randomize_pipeline_ready = world
-      .resource::<PipelineCache>()
-      .get_compute_pipeline(randomize_pipeline)
-      .is_some();
+    let pipeline = world.resource::<GLPipeline>();
+    let pipeline_cache = world.resource::<PipelineCache>();
+    // a generation pass that is skipped would swap in a stale buffer, so wait for all of them
+    let pipelines_ready = [
+      pipeline.randomize_pipeline,
+      pipeline.update_pipeline,
+      pipeline.update_generations_pipeline,
+      pipeline.update_larger_than_life_pipeline,
+      pipeline.update_ages_pipeline,
+      pipeline.apply_edits_pipeline,
+      pipeline.edit_region_pipeline,
+      pipeline.copy_region_pipeline,
+      pipeline.resize_pipeline,
+      pipeline.count_population_pipeline,
+      pipeline.hash_generation_pipeline,
+    ]
+    .into_iter()
+    .all(|id| pipeline_cache.get_compute_pipeline(id).is_some());
+
+    let controls = world
+      .get_resource::<SimulationControls>()
+      .copied()
+      .unwrap_or_default();
+    if !controls.paused {
+      // steps asked for while running are dropped rather than run after the next pause
+      self.steps_taken = controls.requested_steps;
+    }
+    let single_step = controls.requested_steps > self.steps_taken;
+
+    // the generations of the next step, none while paused and cut short at the limit
+    let generation = world.resource::<Generation>().clone();
+    let limit = world.get_resource::<GenerationLimit>().map(|limit| limit.0);
+    let remaining = limit.map_or(u64::MAX, |limit| limit.saturating_sub(generation.get()));
+    let batch = if controls.paused {
+      u32::from(single_step)
+    } else {
+      controls.generations_per_frame.max(1)
+    };
+    let batch = u64::from(batch).min(remaining) as u32;
 
+    if let Some(mut history) = world.get_resource_mut::<GLHistory>() {
+      history.saves.clear();
+      history.restore = None;
+    }
+    let from = generation.get();
+    let edited = world
+      .get_resource::<GLEdits>()
+      .is_some_and(|edits| edits.count > 0)
+      || world
+        .get_resource::<GLRegion>()
+        .and_then(|region| region.op)
+        .is_some_and(|op| op.kind != REGION_COPY);
+    // edits would land on the keyframe before it is re-simulated, so a rewind waits for a
+    // frame without them
+    let rewind = if world.get_resource::<ComputeState>() == Some(&ComputeState::WAIT) && !edited {
+      let generations = world
+        .get_resource_mut::<PendingRewind>()
+        .map_or(0, |mut pending| std::mem::take(&mut pending.0));
+      world
+        .get_resource_mut::<GLHistory>()
+        .filter(|_| generations > 0)
+        .and_then(|mut history| history.plan_rewind(from, generations))
+    } else {
+      None
+    };
+
+    let resized = world
+      .get_resource_mut::<GLResize>()
+      .map(|mut resize| !std::mem::replace(&mut resize.copied, true));
     match world.get_resource_mut::<ComputeState>() {
+      // new storage buffers were allocated for a resized universe; the generations of the
+      // last step, or the cells of the last resize, are in the old ones, so the swap still
+      // happens before the copy; a reseed in the same frame replaces them anyway
+      Some(mut state) if resized == Some(true) && *state != ComputeState::INITIAL => {
+        let swapped = match *state {
+          ComputeState::STEP | ComputeState::REWIND => self.batch % 2 == 1,
+          ComputeState::RESIZE => true,
+          _ => false,
+        };
+        if swapped {
+          self.front = 1 - self.front;
+        }
+        self.last_step_time = Some(elapsed_secs);
+        *state = ComputeState::RESIZE;
+      }
       Some(mut state) => match *state {
         ComputeState::INITIAL => {
-          if randomize_pipeline_ready {
+          if pipelines_ready {
             *state = ComputeState::RANDOMIZE;
+            generation.set(0);
           }
         }
-        ComputeState::RANDOMIZE => {
-          *state = ComputeState::STEP;
+        ComputeState::RANDOMIZE | ComputeState::RESIZE => {
+          self.front = 1 - self.front;
+          self.last_step_time = Some(elapsed_secs);
+          *state = ComputeState::WAIT;
+          if batch > 0 {
+            *state = ComputeState::STEP;
+            self.start_step(batch, single_step, &generation);
+          }
         }
-        ComputeState::STEP => {
+        ComputeState::STEP | ComputeState::REWIND => {
+          if self.batch % 2 == 1 {
+            self.front = 1 - self.front;
+          }
           self.last_step_time = Some(elapsed_secs);
           *state = ComputeState::WAIT;
         }
         ComputeState::WAIT => {
           let delta_t = elapsed_secs - self.last_step_time.unwrap();
-          if self.target_tps == 0 || delta_t > (1.0 / self.target_tps as f32) {
+          let due = controls.paused
+            || controls.target_tps == 0
+            || delta_t > (1.0 / controls.target_tps as f32);
+          if let Some((target, generations)) = rewind {
+            *state = ComputeState::REWIND;
+            self.batch = generations;
+            self.last_step_time = Some(elapsed_secs);
+            generation.set(target);
+          } else if due && batch > 0 {
             *state = ComputeState::STEP;
+            self.start_step(batch, single_step, &generation);
 
-            let Some(telemetry) = world.get_resource::<Telemetry>() else {
-              return;
-            };
-            let mut data = telemetry.ticks.lock().unwrap();
-            if data.len() == telemetry.ticks_len {
-              data.remove(0);
+            if let Some(telemetry) = world.get_resource::<TelemetryReadback>() {
+              telemetry.push(Timing {
+                span: Span::StepInterval,
+                clock: Clock::Cpu,
+                milliseconds: f64::from(delta_t) * 1000.0,
+              });
             }
-            data.push(delta_t);
           }
         }
       },
       None => world.insert_resource(ComputeState::default()),
     }
+
+    let state = world.get_resource::<ComputeState>().cloned();
+    if resized.is_some() && state != Some(ComputeState::RESIZE) {
+      world.remove_resource::<GLResize>();
+    }
+
+    // keyframes are copied and populations counted while the node computes the generations
+    // of the frame
+    if let Some(mut history) = world.get_resource_mut::<GLHistory>() {
+      match state {
+        Some(ComputeState::RANDOMIZE) => history.restart(0),
+        // keyframes of the old size cannot be restored into the new buffers, the copied
+        // cells start the history over
+        Some(ComputeState::RESIZE) => {
+          history.keyframes.clear();
+          history.plan_saves(from, 0, true);
+        }
+        Some(ComputeState::STEP) => history.plan_saves(from, self.batch, edited),
+        Some(ComputeState::WAIT) => history.plan_saves(from, 0, edited),
+        _ => {}
+      }
+    }
+
+    let counting = world
+      .get_resource::<PopulationCounting>()
+      .copied()
+      .unwrap_or_default();
+    let recount = world
+      .get_resource::<GLPopulation>()
+      .is_some_and(|population| population.recount);
+    let mut samples = Vec::new();
+    match state {
+      Some(ComputeState::RANDOMIZE) => samples.push((1, 0)),
+      Some(ComputeState::RESIZE) => samples.push((0, generation.get())),
+      Some(ComputeState::STEP | ComputeState::WAIT) => {
+        let batch = if state == Some(ComputeState::STEP) {
+          self.batch
+        } else {
+          0
+        };
+        if edited || recount {
+          samples.push((0, from));
+        }
+        samples.extend(counting.samples(from, batch));
+      }
+      // the generation rewound to, after re-simulating it
+      Some(ComputeState::REWIND) => samples.push((self.batch, generation.get())),
+      _ => {}
+    }
+    if counting.interval == 0 {
+      samples.clear();
+    }
+    let samples = samples
+      .into_iter()
+      .map(|(after, generation)| PopulationSample { after, generation })
+      .collect();
+    let hashing = world
+      .get_resource::<OscillationDetection>()
+      .is_some_and(|detection| detection.window > 0);
+    let device = world.resource::<RenderDevice>().clone();
+    if let Some(mut population) = world.get_resource_mut::<GLPopulation>() {
+      population.recount = false;
+      population.plan(samples, hashing, &device);
+    }
   }
 }
@@ -0,0 +1,208 @@
+use std::{error::Error, fmt};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Res, ResMut},
+  },
+  log::{error, info},
+  math::{IVec2, UVec2},
+  render::{Extract, renderer::RenderDevice},
+};
+
+use crate::{data_structs::Params, render_graph::COMPUTE_WG_SIZE};
+
+/// Changes the size of the universe while it runs, keeping the cells that still fit.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResizeUniverse {
+  /// New size in cells.
+  pub size: UVec2,
+  pub anchor: Anchor,
+}
+
+/// The point of the universe that stays in place when it is resized.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+  #[default]
+  Center,
+  TopLeft,
+  TopRight,
+  BottomLeft,
+  BottomRight,
+}
+
+impl Anchor {
+  /// Where the top left cell of a universe of `old` cells goes in one of `new` cells, which
+  /// is outside of it when the universe shrinks.
+  pub fn offset(&self, old: UVec2, new: UVec2) -> IVec2 {
+    let room = new.as_ivec2() - old.as_ivec2();
+    match self {
+      Anchor::Center => room / 2,
+      Anchor::TopLeft => IVec2::ZERO,
+      Anchor::TopRight => IVec2::new(room.x, 0),
+      Anchor::BottomLeft => IVec2::new(0, room.y),
+      Anchor::BottomRight => room,
+    }
+  }
+}
+
+/// A universe size the generation buffers cannot be allocated for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UniverseSizeError {
+  Empty,
+  /// A generation buffer would be larger than a storage buffer binding may be.
+  TooLarge {
+    size: UVec2,
+    bytes: u64,
+    max_bytes: u64,
+  },
+  /// The kernels working on one word per invocation would need more workgroups than a
+  /// dispatch may have.
+  TooManyWorkgroups {
+    size: UVec2,
+    workgroups: u64,
+    max_workgroups: u32,
+  },
+}
+
+impl fmt::Display for UniverseSizeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UniverseSizeError::Empty => write!(f, "the universe needs at least one cell"),
+      UniverseSizeError::TooLarge {
+        size,
+        bytes,
+        max_bytes,
+      } => write!(
+        f,
+        "a {}x{} universe takes {bytes} bytes per generation buffer, more than the \
+         {max_bytes} bytes of max_storage_buffer_binding_size",
+        size.x, size.y
+      ),
+      UniverseSizeError::TooManyWorkgroups {
+        size,
+        workgroups,
+        max_workgroups,
+      } => write!(
+        f,
+        "a {}x{} universe takes {workgroups} workgroups per generation, more than the \
+         {max_workgroups} of max_compute_workgroups_per_dimension",
+        size.x, size.y
+      ),
+    }
+  }
+}
+
+impl Error for UniverseSizeError {}
+
+/// The limits of the render device a universe has to fit in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UniverseLimits {
+  /// `max_storage_buffer_binding_size`
+  pub max_bytes: u64,
+  /// `max_compute_workgroups_per_dimension`
+  pub max_workgroups: u32,
+}
+
+impl UniverseLimits {
+  /// No limits, for the CPU backend.
+  pub const NONE: Self = Self {
+    max_bytes: u64::MAX,
+    max_workgroups: u32::MAX,
+  };
+
+  /// The limits of `device`, or none without a render device.
+  pub(crate) fn of(device: Option<&RenderDevice>) -> Self {
+    device.map_or(Self::NONE, |device| {
+      let limits = device.limits();
+      Self {
+        max_bytes: u64::from(limits.max_storage_buffer_binding_size),
+        max_workgroups: limits.max_compute_workgroups_per_dimension,
+      }
+    })
+  }
+}
+
+/// Checks that the generation buffers of a universe of `size` cells, laid out with
+/// `bits_per_cell`, fit into a storage buffer binding and that the kernels going over them
+/// a word per invocation can be dispatched within `limits`.
+pub fn check_universe_size(
+  size: UVec2,
+  bits_per_cell: u32,
+  limits: UniverseLimits,
+) -> Result<(), UniverseSizeError> {
+  if size.cmpeq(UVec2::ZERO).any() {
+    return Err(UniverseSizeError::Empty);
+  }
+  let words = u64::from(size.x.div_ceil(32 / bits_per_cell)) * u64::from(size.y);
+  let bytes = words * 4;
+  if bytes > limits.max_bytes {
+    return Err(UniverseSizeError::TooLarge {
+      size,
+      bytes,
+      max_bytes: limits.max_bytes,
+    });
+  }
+  let workgroups = words.div_ceil(u64::from(COMPUTE_WG_SIZE));
+  if workgroups > u64::from(limits.max_workgroups) {
+    return Err(UniverseSizeError::TooManyWorkgroups {
+      size,
+      workgroups,
+      max_workgroups: limits.max_workgroups,
+    });
+  }
+  Ok(())
+}
+
+/// Sent once the universe was resized, with how far its cells moved.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UniverseResized {
+  pub offset: IVec2,
+}
+
+/// How far the cells move when the render world reallocates the generation buffers, summed
+/// up over the resizes since it last did.
+#[derive(Resource, Default)]
+pub struct PendingResize(pub Option<IVec2>);
+
+/// Resizes the universe in `Params`, moving the view along with the cells.
+pub fn apply_resizes(
+  mut resizes: EventReader<ResizeUniverse>,
+  mut params: ResMut<Params>,
+  device: Option<Res<RenderDevice>>,
+  mut resized: EventWriter<UniverseResized>,
+) {
+  for resize in resizes.read() {
+    let old = UVec2::new(params.cell_count_x, params.buffer_size_y);
+    if resize.size == old {
+      continue;
+    }
+    let limits = UniverseLimits::of(device.as_deref());
+    if let Err(err) = check_universe_size(resize.size, params.bits_per_cell, limits) {
+      error!("Cannot resize the universe: {err}");
+      continue;
+    }
+
+    let offset = resize.anchor.offset(old, resize.size);
+    params.cell_count_x = resize.size.x;
+    params.buffer_size_y = resize.size.y;
+    params.buffer_size_x = resize.size.x.div_ceil(params.cells_per_word());
+    params.center_x += offset.x as f32;
+    params.center_y += offset.y as f32;
+    info!(
+      "Resizing the universe from {}x{} to {}x{} cells",
+      old.x, old.y, resize.size.x, resize.size.y
+    );
+    resized.write(UniverseResized { offset });
+  }
+}
+
+pub fn extract_resizes(
+  mut resized: Extract<EventReader<UniverseResized>>,
+  mut pending: ResMut<PendingResize>,
+) {
+  for resize in resized.read() {
+    pending.0 = Some(pending.0.unwrap_or_default() + resize.offset);
+  }
+}
@@ -0,0 +1,432 @@
+use std::{fmt, str::FromStr};
+
+use bevy::{ecs::resource::Resource, render::extract_resource::ExtractResource};
+
+/// Highest neighbor count of the Moore neighborhood.
+const MAX_NEIGHBORS: u32 = 8;
+/// Highest state count of the Generations family, limited by the 8-bit cell layout.
+const MAX_STATES: u32 = 256;
+/// Highest Larger than Life range, limited by the tile apron in the update kernel.
+pub const MAX_RANGE: u32 = 10;
+
+/// A Life-like or Generations rule, stored as bitmasks where bit `n` is set when a cell
+/// with `n` live neighbors is born (`birth`) or stays alive (`survival`).
+///
+/// `states` is 2 for Life-like rules. With more states, a live cell that does not survive
+/// steps through `states - 2` dying states before it becomes dead.
+///
+/// Larger than Life rules count neighbors over a wider neighborhood and use the count
+/// intervals in `larger_than_life` instead of the bitmasks, which are left empty.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, ExtractResource)]
+pub struct Rule {
+  pub birth: u32,
+  pub survival: u32,
+  pub states: u32,
+  pub larger_than_life: Option<LargerThanLife>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+  /// All cells within `range` in both axes.
+  Moore,
+  /// All cells within a Manhattan distance of `range`.
+  VonNeumann,
+}
+
+/// Neighborhood and count intervals of a Larger than Life rule, e.g.
+/// `R5,C0,M1,S34..58,B34..45,NM` for Bosco's rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LargerThanLife {
+  pub range: u32,
+  pub neighborhood: Neighborhood,
+  /// Whether a cell counts itself as one of its neighbors.
+  pub include_center: bool,
+  /// Inclusive interval of counts for which a dead cell is born.
+  pub birth: (u32, u32),
+  /// Inclusive interval of counts for which a live cell stays alive.
+  pub survival: (u32, u32),
+}
+
+impl LargerThanLife {
+  /// Number of cells in the neighborhood, the center cell included.
+  pub fn cell_count(&self) -> u32 {
+    let range = self.range;
+    match self.neighborhood {
+      Neighborhood::Moore => (2 * range + 1) * (2 * range + 1),
+      Neighborhood::VonNeumann => 2 * range * (range + 1) + 1,
+    }
+  }
+}
+
+impl Default for Rule {
+  fn default() -> Self {
+    // B3/S23
+    Self {
+      birth: 1 << 3,
+      survival: (1 << 2) | (1 << 3),
+      states: 2,
+      larger_than_life: None,
+    }
+  }
+}
+
+impl Rule {
+  /// Number of bits a cell occupies in the storage buffer: 1 for Life-like rules, 8 for
+  /// Generations rules.
+  pub fn bits_per_cell(&self) -> u32 {
+    if self.states > 2 { 8 } else { 1 }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleParseError {
+  Empty,
+  /// The rule does not consist of two or three `/`-separated parts.
+  PartCount(usize),
+  /// Only some parts are prefixed with `B`/`S`/`C`, a prefix is repeated, or `B` or `S` is
+  /// missing.
+  MismatchedPrefixes,
+  /// A character that is not a neighbor count, found at a byte offset of the input.
+  InvalidCharacter {
+    position: usize,
+    character: char,
+  },
+  /// A neighbor count that the Moore neighborhood cannot reach.
+  CountOutOfRange {
+    position: usize,
+    count: u32,
+  },
+  DuplicateCount {
+    position: usize,
+    count: u32,
+  },
+  /// The state count of a Generations rule is missing, below 2 or above 256.
+  InvalidStateCount {
+    position: usize,
+  },
+  /// A Larger than Life parameter that is malformed, repeated or unknown.
+  InvalidParameter {
+    position: usize,
+  },
+  MissingParameter(char),
+  /// A Larger than Life range of 0 or above `MAX_RANGE`.
+  RangeOutOfBounds {
+    position: usize,
+    range: u32,
+  },
+  /// A Larger than Life count interval that is reversed or exceeds the neighborhood size.
+  InvalidInterval {
+    position: usize,
+  },
+}
+
+impl fmt::Display for RuleParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RuleParseError::Empty => write!(f, "rule is empty"),
+      RuleParseError::PartCount(count) => {
+        write!(f, "expected 2 or 3 '/'-separated parts, found {count}")
+      }
+      RuleParseError::MismatchedPrefixes => write!(
+        f,
+        "expected one 'B', one 'S' and an optional 'C' part, or only unprefixed parts"
+      ),
+      RuleParseError::InvalidCharacter {
+        position,
+        character,
+      } => write!(
+        f,
+        "unexpected character '{character}' at position {position}"
+      ),
+      RuleParseError::CountOutOfRange { position, count } => write!(
+        f,
+        "neighbor count {count} at position {position} exceeds {MAX_NEIGHBORS}"
+      ),
+      RuleParseError::DuplicateCount { position, count } => {
+        write!(
+          f,
+          "neighbor count {count} at position {position} is repeated"
+        )
+      }
+      RuleParseError::InvalidStateCount { position } => write!(
+        f,
+        "expected a state count between 2 and {MAX_STATES} at position {position}"
+      ),
+      RuleParseError::InvalidParameter { position } => {
+        write!(f, "invalid parameter at position {position}")
+      }
+      RuleParseError::MissingParameter(parameter) => {
+        write!(f, "missing parameter '{parameter}'")
+      }
+      RuleParseError::RangeOutOfBounds { position, range } => write!(
+        f,
+        "range {range} at position {position} is not between 1 and {MAX_RANGE}"
+      ),
+      RuleParseError::InvalidInterval { position } => write!(
+        f,
+        "count interval at position {position} is reversed or exceeds the neighborhood"
+      ),
+    }
+  }
+}
+
+impl std::error::Error for RuleParseError {}
+
+impl FromStr for Rule {
+  type Err = RuleParseError;
+
+  /// Parses `B36/S23` notation (case insensitive, parts in any order) or the older
+  /// `23/36` notation, where survival comes before birth. Generations rules add a state
+  /// count, as in `B2/S/C3` or `/2/3`, and Larger than Life rules use the
+  /// `R5,C0,M1,S34..58,B34..45,NM` notation.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s.trim().is_empty() {
+      return Err(RuleParseError::Empty);
+    }
+    if s.trim_start().starts_with(['R', 'r']) {
+      return parse_larger_than_life(s);
+    }
+
+    let parts = split_parts(s);
+    if !(2..=3).contains(&parts.len()) {
+      return Err(RuleParseError::PartCount(parts.len()));
+    }
+
+    let prefixes: Vec<_> = parts
+      .iter()
+      .map(|(_, part)| part.chars().next().map(|c| c.to_ascii_uppercase()))
+      .collect();
+
+    let (birth, survival, states) = if prefixes.iter().all(|c| !is_prefix(*c)) {
+      (parts[1], parts[0], parts.get(2).copied())
+    } else {
+      if !prefixes.iter().all(|c| is_prefix(*c)) {
+        return Err(RuleParseError::MismatchedPrefixes);
+      }
+
+      let find = |prefix: char| {
+        let mut matching = parts
+          .iter()
+          .zip(&prefixes)
+          .filter(|(_, c)| **c == Some(prefix))
+          .map(|(part, _)| strip_prefix(*part));
+        match (matching.next(), matching.next()) {
+          (part, None) => Ok(part),
+          _ => Err(RuleParseError::MismatchedPrefixes),
+        }
+      };
+
+      match (find('B')?, find('S')?, find('C')?) {
+        (Some(birth), Some(survival), states) => (birth, survival, states),
+        _ => return Err(RuleParseError::MismatchedPrefixes),
+      }
+    };
+
+    Ok(Rule {
+      birth: parse_counts(birth)?,
+      survival: parse_counts(survival)?,
+      states: match states {
+        Some(states) => parse_states(states)?,
+        None => 2,
+      },
+      larger_than_life: None,
+    })
+  }
+}
+
+impl fmt::Display for Rule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if let Some(ltl) = self.larger_than_life {
+      let states = if self.states > 2 { self.states } else { 0 };
+      let neighborhood = match ltl.neighborhood {
+        Neighborhood::Moore => 'M',
+        Neighborhood::VonNeumann => 'N',
+      };
+      return write!(
+        f,
+        "R{},C{},M{},S{}..{},B{}..{},N{}",
+        ltl.range,
+        states,
+        u32::from(ltl.include_center),
+        ltl.survival.0,
+        ltl.survival.1,
+        ltl.birth.0,
+        ltl.birth.1,
+        neighborhood
+      );
+    }
+
+    write!(f, "B{}/S{}", counts(self.birth), counts(self.survival))?;
+    if self.states > 2 {
+      write!(f, "/C{}", self.states)?;
+    }
+    Ok(())
+  }
+}
+
+fn is_prefix(c: Option<char>) -> bool {
+  matches!(c, Some('B' | 'S' | 'C'))
+}
+
+/// Splits the trimmed rule on `/`, keeping the byte offset of every part for error reporting.
+fn split_parts(s: &str) -> Vec<(usize, &str)> {
+  let start = s.len() - s.trim_start().len();
+  let mut offset = start;
+  s.trim()
+    .split('/')
+    .map(|part| {
+      let item = (offset, part);
+      offset += part.len() + 1;
+      item
+    })
+    .collect()
+}
+
+fn strip_prefix((offset, part): (usize, &str)) -> (usize, &str) {
+  (offset + 1, &part[1..])
+}
+
+fn parse_counts((offset, part): (usize, &str)) -> Result<u32, RuleParseError> {
+  let mut mask = 0u32;
+  for (i, character) in part.char_indices() {
+    let position = offset + i;
+    let Some(count) = character.to_digit(10) else {
+      return Err(RuleParseError::InvalidCharacter {
+        position,
+        character,
+      });
+    };
+    if count > MAX_NEIGHBORS {
+      return Err(RuleParseError::CountOutOfRange { position, count });
+    }
+    if mask & (1 << count) != 0 {
+      return Err(RuleParseError::DuplicateCount { position, count });
+    }
+    mask |= 1 << count;
+  }
+  Ok(mask)
+}
+
+fn parse_states((offset, part): (usize, &str)) -> Result<u32, RuleParseError> {
+  match part.parse::<u32>() {
+    Ok(states) if (2..=MAX_STATES).contains(&states) => Ok(states),
+    _ => Err(RuleParseError::InvalidStateCount { position: offset }),
+  }
+}
+
+/// Parses Golly's `R5,C0,M1,S34..58,B34..45,NM` notation. Parameters may come in any order
+/// and `N` defaults to the Moore neighborhood.
+fn parse_larger_than_life(s: &str) -> Result<Rule, RuleParseError> {
+  let mut range = None;
+  let mut states = None;
+  let mut include_center = None;
+  let mut survival = None;
+  let mut birth = None;
+  let mut neighborhood = None;
+
+  let mut offset = s.len() - s.trim_start().len();
+  for part in s.trim().split(',') {
+    let position = offset;
+    offset += part.len() + 1;
+
+    let invalid = RuleParseError::InvalidParameter { position };
+    let mut chars = part.chars();
+    let key = chars.next().map(|c| c.to_ascii_uppercase());
+    let value = chars.as_str();
+    let slot_is_free = match key {
+      Some('R') => range
+        .replace((parse_value(value, position)?, position))
+        .is_none(),
+      Some('C') => states
+        .replace((parse_value(value, position)?, position))
+        .is_none(),
+      Some('M') => match value {
+        "0" | "1" => include_center.replace(value == "1").is_none(),
+        _ => return Err(invalid),
+      },
+      Some('S') => survival.replace(parse_interval(value, position)?).is_none(),
+      Some('B') => birth.replace(parse_interval(value, position)?).is_none(),
+      Some('N') => match value.to_ascii_uppercase().as_str() {
+        "M" => neighborhood.replace(Neighborhood::Moore).is_none(),
+        "N" => neighborhood.replace(Neighborhood::VonNeumann).is_none(),
+        _ => return Err(invalid),
+      },
+      _ => return Err(invalid),
+    };
+    if !slot_is_free {
+      return Err(invalid);
+    }
+  }
+
+  let (range, range_position) = range.ok_or(RuleParseError::MissingParameter('R'))?;
+  if !(1..=MAX_RANGE).contains(&range) {
+    return Err(RuleParseError::RangeOutOfBounds {
+      position: range_position,
+      range,
+    });
+  }
+  let (states, states_position) = states.ok_or(RuleParseError::MissingParameter('C'))?;
+  let states = match states {
+    0 | 2 => 2,
+    3..=MAX_STATES => states,
+    _ => {
+      return Err(RuleParseError::InvalidStateCount {
+        position: states_position,
+      });
+    }
+  };
+
+  let mut ltl = LargerThanLife {
+    range,
+    neighborhood: neighborhood.unwrap_or(Neighborhood::Moore),
+    include_center: include_center.ok_or(RuleParseError::MissingParameter('M'))?,
+    birth: (0, 0),
+    survival: (0, 0),
+  };
+  let cell_count = ltl.cell_count();
+  for (interval, slot, parameter) in [
+    (birth, &mut ltl.birth, 'B'),
+    (survival, &mut ltl.survival, 'S'),
+  ] {
+    let (min, max, position) = interval.ok_or(RuleParseError::MissingParameter(parameter))?;
+    if max > cell_count {
+      return Err(RuleParseError::InvalidInterval { position });
+    }
+    *slot = (min, max);
+  }
+
+  Ok(Rule {
+    birth: 0,
+    survival: 0,
+    states,
+    larger_than_life: Some(ltl),
+  })
+}
+
+fn parse_value(value: &str, position: usize) -> Result<u32, RuleParseError> {
+  value
+    .parse()
+    .map_err(|_| RuleParseError::InvalidParameter { position })
+}
+
+/// Parses `min..max`, or a single count as a one-element interval.
+fn parse_interval(value: &str, position: usize) -> Result<(u32, u32, usize), RuleParseError> {
+  let (min, max) = match value.split_once("..") {
+    Some((min, max)) => (parse_value(min, position)?, parse_value(max, position)?),
+    None => {
+      let count = parse_value(value, position)?;
+      (count, count)
+    }
+  };
+  if min > max {
+    return Err(RuleParseError::InvalidInterval { position });
+  }
+  Ok((min, max, position))
+}
+
+fn counts(mask: u32) -> String {
+  (0..=MAX_NEIGHBORS)
+    .filter(|count| mask & (1 << count) != 0)
+    .map(|count| char::from_digit(count, 10).unwrap())
+    .collect()
+}
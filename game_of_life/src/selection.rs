@@ -0,0 +1,474 @@
+use std::{
+  collections::VecDeque,
+  mem,
+  path::PathBuf,
+  sync::{Arc, Mutex},
+};
+
+use bevy::{
+  ecs::{
+    event::{Event, EventReader, EventWriter},
+    resource::Resource,
+    system::{Local, Res, ResMut, Single},
+    world::{FromWorld, World},
+  },
+  input::{ButtonInput, keyboard::KeyCode, mouse::MouseButton},
+  log::{error, info, warn},
+  math::{IVec2, URect, UVec2},
+  render::{
+    Extract,
+    extract_resource::ExtractResource,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages, MapMode},
+    renderer::{RenderDevice, RenderQueue},
+  },
+  window::Window,
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+  bind_group::GLBufferLayout,
+  data_structs::{ComputeState, Params},
+  export::write_pattern_file,
+  paint::cursor_cell,
+  pattern::Pattern,
+  rule::Rule,
+};
+
+/// Most words the clipboard buffer holds, 128 million cells of a Life-like rule.
+const MAX_CLIPBOARD_WORDS: u32 = 1 << 22;
+
+// must match the `REGION_` constants in `game_of_life.wgsl`
+const REGION_CLEAR: u32 = 1;
+const REGION_CLEAR_OUTSIDE: u32 = 2;
+const REGION_FILL: u32 = 3;
+pub(crate) const REGION_COPY: u32 = 4;
+pub(crate) const REGION_CUT: u32 = 5;
+const REGION_PASTE: u32 = 6;
+
+/// The rectangle of cells selected by dragging with Shift and the left button, and the
+/// density it is filled with.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+  pub rect: Option<URect>,
+  /// Chance of every cell to be alive after a `RegionEdit::Fill`.
+  pub density: f32,
+}
+
+impl Default for Selection {
+  fn default() -> Self {
+    Self {
+      rect: None,
+      density: 0.5,
+    }
+  }
+}
+
+/// Cells last copied or cut from the universe, with the rule they were copied under.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct Clipboard(pub Option<Arc<Pattern>>);
+
+/// An edit of a rectangle of the latest generation. They run as compute passes over the
+/// storage buffer, one per frame in the order they were sent.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub enum RegionEdit {
+  /// Kills the cells inside the rectangle.
+  Clear(URect),
+  /// Kills the cells outside the rectangle.
+  ClearOutside(URect),
+  /// Makes every cell inside the rectangle alive with a chance of `density`, and kills the
+  /// others.
+  Fill {
+    rect: URect,
+    density: f32,
+    seed: u32,
+  },
+  /// Copies the cells inside the rectangle to the `Clipboard`, which has them a few frames
+  /// later once they are read back.
+  Copy(URect),
+  /// Copies the cells inside the rectangle, then kills them.
+  Cut(URect),
+  /// Writes the clipboard with its top left corner at the cell, replacing the cells under
+  /// it.
+  Paste(IVec2),
+}
+
+/// Writes the clipboard to `path`, in the format its extension asks for like an
+/// `ExportPattern`.
+#[derive(Event, Clone, Debug)]
+pub struct ExportClipboard {
+  pub path: PathBuf,
+}
+
+/// A `RegionEdit` as the `edit_region` and `copy_region` kernels read it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+pub(crate) struct RegionOp {
+  pub kind: u32,
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  pub target_x: i32,
+  pub target_y: i32,
+  pub threshold: u32,
+  pub seed: u32,
+}
+
+impl RegionOp {
+  fn rect(kind: u32, rect: URect) -> Self {
+    let size = rect.size();
+    Self {
+      kind,
+      x: rect.min.x,
+      y: rect.min.y,
+      width: size.x,
+      height: size.y,
+      ..Self::default()
+    }
+  }
+}
+
+/// `density` scaled to the 24 bits of the hash the `edit_region` kernel compares it with,
+/// which survive the conversion exactly.
+pub(crate) fn fill_threshold(density: f32) -> u32 {
+  (density.clamp(0.0, 1.0) * (1 << 24) as f32).round() as u32
+}
+
+/// Words of a rectangle of cells packed in rows of whole words, like the clipboard.
+pub(crate) fn packed_words(size: UVec2, bits_per_cell: u32) -> u32 {
+  size.x.div_ceil(32 / bits_per_cell) * size.y
+}
+
+/// Drags out the selection with Shift and the left button and runs the region edits:
+/// Ctrl+C copies, Ctrl+X cuts, Ctrl+V pastes centered on the cursor, Delete clears the
+/// selection (with Shift the outside of it), N fills it and Escape drops it. Ctrl+E writes
+/// the clipboard to `clipboard.rle`.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_selection(
+  window: Single<&Window>,
+  buttons: Res<ButtonInput<MouseButton>>,
+  keys: Res<ButtonInput<KeyCode>>,
+  clipboard: Res<Clipboard>,
+  mut selection: ResMut<Selection>,
+  mut params: ResMut<Params>,
+  mut anchor: Local<Option<IVec2>>,
+  mut edits: EventWriter<RegionEdit>,
+  mut exports: EventWriter<ExportClipboard>,
+) {
+  let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+  let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+  let cursor = cursor_cell(&window, &params);
+  let last_cell = IVec2::new(params.cell_count_x as i32, params.buffer_size_y as i32) - 1;
+
+  if buttons.just_pressed(MouseButton::Left) && shift && !ctrl {
+    *anchor = cursor.map(|cell| cell.clamp(IVec2::ZERO, last_cell));
+  }
+  if let Some(from) = *anchor
+    && let Some(to) = cursor
+    && buttons.pressed(MouseButton::Left)
+  {
+    let to = to.clamp(IVec2::ZERO, last_cell);
+    let min = from.min(to).as_uvec2();
+    selection.rect = Some(URect::from_corners(min, from.max(to).as_uvec2() + 1));
+  }
+  if !buttons.pressed(MouseButton::Left) {
+    *anchor = None;
+  }
+  if keys.just_pressed(KeyCode::Escape) {
+    selection.rect = None;
+  }
+
+  if let Some(rect) = selection.rect {
+    if ctrl && keys.just_pressed(KeyCode::KeyC) {
+      edits.write(RegionEdit::Copy(rect));
+    }
+    if ctrl && keys.just_pressed(KeyCode::KeyX) {
+      edits.write(RegionEdit::Cut(rect));
+    }
+    if keys.just_pressed(KeyCode::Delete) {
+      edits.write(if shift {
+        RegionEdit::ClearOutside(rect)
+      } else {
+        RegionEdit::Clear(rect)
+      });
+    }
+    if keys.just_pressed(KeyCode::KeyN) {
+      edits.write(RegionEdit::Fill {
+        rect,
+        density: selection.density,
+        seed: rand::random(),
+      });
+    }
+  }
+  if ctrl && keys.just_pressed(KeyCode::KeyV) {
+    match (&clipboard.0, cursor) {
+      (Some(pattern), Some(cell)) => {
+        let size = UVec2::new(pattern.width, pattern.height).as_ivec2();
+        edits.write(RegionEdit::Paste(cell - size / 2));
+      }
+      (None, _) => warn!("Nothing to paste, the clipboard is empty"),
+      (Some(_), None) => {}
+    }
+  }
+  if ctrl && keys.just_pressed(KeyCode::KeyE) {
+    exports.write(ExportClipboard {
+      path: "clipboard.rle".into(),
+    });
+  }
+
+  let rect = selection.rect.unwrap_or_default();
+  params.selection_x = rect.min.x;
+  params.selection_y = rect.min.y;
+  params.selection_width = rect.width();
+  params.selection_height = rect.height();
+}
+
+/// Copies arriving from the GPU, pushed by the buffer mapping callbacks like `Readback`.
+#[derive(Resource, Clone, ExtractResource, Default)]
+pub struct ClipboardReadback(Arc<Mutex<Vec<Pattern>>>);
+
+pub fn receive_clipboard(
+  readback: Res<ClipboardReadback>,
+  rule: Res<Rule>,
+  mut clipboard: ResMut<Clipboard>,
+) {
+  let Some(mut pattern) = mem::take(&mut *readback.0.lock().unwrap()).pop() else {
+    return;
+  };
+  info!("Copied {}x{} cells", pattern.width, pattern.height);
+  pattern.rule = Some(*rule);
+  clipboard.0 = Some(Arc::new(pattern));
+}
+
+pub fn export_clipboard(mut exports: EventReader<ExportClipboard>, clipboard: Res<Clipboard>) {
+  for export in exports.read() {
+    let Some(pattern) = &clipboard.0 else {
+      warn!("Nothing to export, the clipboard is empty");
+      continue;
+    };
+    match write_pattern_file(&export.path, pattern) {
+      Ok(()) => info!("Exported the clipboard to {}", export.path.display()),
+      Err(err) => error!("Failed to write {}: {err}", export.path.display()),
+    }
+  }
+}
+
+/// Region edits extracted to the render world, waiting for buffers that hold a generation.
+#[derive(Resource, Default)]
+pub struct PendingRegionEdits(VecDeque<RegionEdit>);
+
+pub struct StagingClipboard {
+  pub buffer: Buffer,
+  origin: UVec2,
+  size: UVec2,
+  bits_per_cell: u32,
+}
+
+/// The buffers of the region kernels and the edit the render graph node runs this frame.
+#[derive(Resource)]
+pub struct GLRegion {
+  pub uniform: Buffer,
+  pub clipboard: Buffer,
+  pub(crate) op: Option<RegionOp>,
+  /// Filled with the copied cells by the node, mapped once the frame is submitted.
+  pub staging: Option<StagingClipboard>,
+  /// The clipboard pattern and cell layout the clipboard buffer was packed from.
+  packed: Option<(Arc<Pattern>, u32)>,
+}
+
+impl GLRegion {
+  /// Words of the clipboard the `copy_region` kernel fills this frame.
+  pub(crate) fn copied_words(&self, params: &Params) -> Option<u32> {
+    let op = self
+      .op
+      .filter(|op| matches!(op.kind, REGION_COPY | REGION_CUT))?;
+    Some(packed_words(
+      UVec2::new(op.width, op.height),
+      params.bits_per_cell,
+    ))
+  }
+}
+
+impl FromWorld for GLRegion {
+  fn from_world(world: &mut World) -> Self {
+    let device = world.resource::<RenderDevice>();
+    let uniform = device.create_buffer(&BufferDescriptor {
+      label: None,
+      size: size_of::<RegionOp>() as u64,
+      usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+      mapped_at_creation: false,
+    });
+    let clipboard = device.create_buffer(&BufferDescriptor {
+      label: None,
+      size: u64::from(MAX_CLIPBOARD_WORDS) * 4,
+      usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC | BufferUsages::STORAGE,
+      mapped_at_creation: false,
+    });
+    Self {
+      uniform,
+      clipboard,
+      op: None,
+      staging: None,
+      packed: None,
+    }
+  }
+}
+
+pub fn extract_region_edits(
+  mut edits: Extract<EventReader<RegionEdit>>,
+  mut pending: ResMut<PendingRegionEdits>,
+) {
+  pending.0.extend(edits.read().copied());
+}
+
+/// Hands the next pending edit to the render graph node, packing the clipboard into its
+/// buffer for pastes and allocating a staging buffer for copies.
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_region_edit(
+  mut pending: ResMut<PendingRegionEdits>,
+  region: Option<ResMut<GLRegion>>,
+  clipboard: Option<Res<Clipboard>>,
+  state: Option<Res<ComputeState>>,
+  params: Res<Params>,
+  layout: Option<Res<GLBufferLayout>>,
+  device: Res<RenderDevice>,
+  queue: Res<RenderQueue>,
+) {
+  let Some(mut region) = region else {
+    return;
+  };
+  region.op = None;
+
+  // like painting, edits of the initial random soup would be overwritten
+  let ready = matches!(
+    state.as_deref(),
+    Some(ComputeState::STEP | ComputeState::WAIT)
+  ) && layout.is_some_and(|layout| layout.matches(&params));
+  if !ready {
+    return;
+  }
+  let Some(edit) = pending.0.pop_front() else {
+    return;
+  };
+
+  let universe = URect::from_corners(
+    UVec2::ZERO,
+    UVec2::new(params.cell_count_x, params.buffer_size_y),
+  );
+  let op = match edit {
+    RegionEdit::Clear(rect) => RegionOp::rect(REGION_CLEAR, rect.intersect(universe)),
+    RegionEdit::ClearOutside(rect) => {
+      RegionOp::rect(REGION_CLEAR_OUTSIDE, rect.intersect(universe))
+    }
+    RegionEdit::Fill {
+      rect,
+      density,
+      seed,
+    } => RegionOp {
+      threshold: fill_threshold(density),
+      seed,
+      ..RegionOp::rect(REGION_FILL, rect.intersect(universe))
+    },
+    RegionEdit::Copy(rect) | RegionEdit::Cut(rect) => {
+      let rect = rect.intersect(universe);
+      let words = packed_words(rect.size(), params.bits_per_cell);
+      if rect.is_empty() || words > MAX_CLIPBOARD_WORDS {
+        warn!(
+          "Cannot copy {}x{} cells to the clipboard",
+          rect.width(),
+          rect.height()
+        );
+        return;
+      }
+
+      region.staging = Some(StagingClipboard {
+        buffer: device.create_buffer(&BufferDescriptor {
+          label: None,
+          size: u64::from(words) * 4,
+          usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+          mapped_at_creation: false,
+        }),
+        origin: rect.min,
+        size: rect.size(),
+        bits_per_cell: params.bits_per_cell,
+      });
+      // the clipboard buffer no longer holds the clipboard pattern until it comes back
+      region.packed = None;
+      let kind = match edit {
+        RegionEdit::Cut(_) => REGION_CUT,
+        _ => REGION_COPY,
+      };
+      RegionOp::rect(kind, rect)
+    }
+    RegionEdit::Paste(offset) => {
+      let Some(pattern) = clipboard.and_then(|clipboard| clipboard.0.clone()) else {
+        return;
+      };
+      let size = UVec2::new(pattern.width, pattern.height);
+      if packed_words(size, params.bits_per_cell) > MAX_CLIPBOARD_WORDS {
+        warn!("The clipboard is too large to paste");
+        return;
+      }
+
+      let packed = region
+        .packed
+        .as_ref()
+        .is_some_and(|(packed, bits_per_cell)| {
+          Arc::ptr_eq(packed, &pattern) && *bits_per_cell == params.bits_per_cell
+        });
+      if !packed {
+        let (_, rows) = pattern.pack_rows(0, params.bits_per_cell);
+        queue.write_buffer(&region.clipboard, 0, bytemuck::cast_slice(&rows.concat()));
+        region.packed = Some((pattern, params.bits_per_cell));
+      }
+      RegionOp {
+        kind: REGION_PASTE,
+        width: size.x,
+        height: size.y,
+        target_x: offset.x,
+        target_y: offset.y,
+        ..RegionOp::default()
+      }
+    }
+  };
+
+  queue.write_buffer(&region.uniform, 0, bytemuck::bytes_of(&op));
+  region.op = Some(op);
+}
+
+/// Maps the clipboard copy made this frame, like `map_readbacks`.
+pub fn map_clipboard(mut region: ResMut<GLRegion>, readback: Res<ClipboardReadback>) {
+  let Some(StagingClipboard {
+    buffer,
+    origin,
+    size,
+    bits_per_cell,
+  }) = region.staging.take()
+  else {
+    return;
+  };
+  let completed = readback.0.clone();
+  let mapped = buffer.clone();
+
+  buffer.slice(..).map_async(MapMode::Read, move |result| {
+    if let Err(err) = result {
+      error!("Failed to map the clipboard buffer: {err}");
+      return;
+    }
+    let words: Vec<u32> = bytemuck::cast_slice(&mapped.slice(..).get_mapped_range()).to_vec();
+    mapped.unmap();
+
+    let cells_per_word = 32 / bits_per_cell;
+    let row_words = size.x.div_ceil(cells_per_word);
+    let mut pattern = Pattern::new(size.x, size.y);
+    for y in 0..size.y {
+      for x in 0..size.x {
+        let word = words[(x / cells_per_word + y * row_words) as usize];
+        let shift = (cells_per_word - 1 - x % cells_per_word) * bits_per_cell;
+        pattern.set(x, y, ((word >> shift) & ((1 << bits_per_cell) - 1)) as u8);
+      }
+    }
+    pattern.position = Some(origin.as_ivec2());
+    completed.lock().unwrap().push(pattern);
+  });
+}
@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use bevy::{
+  ecs::{
+    event::EventWriter,
+    resource::Resource,
+    system::{Local, Res, ResMut, Single},
+    world::{FromWorld, World},
+  },
+  input::{ButtonInput, keyboard::KeyCode, mouse::MouseButton},
+  math::{IVec2, UVec2, Vec2},
+  render::{
+    extract_resource::ExtractResource,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages},
+    renderer::{RenderDevice, RenderQueue},
+  },
+  window::Window,
+};
+
+use crate::{
+  data_structs::Params,
+  paint::{PaintCells, cursor_cell},
+  pattern::Pattern,
+};
+
+/// Most words of the preview the ghost buffer holds, larger patterns are stamped without
+/// one.
+const MAX_GHOST_WORDS: u32 = 1 << 20;
+
+/// How far the cursor may move between pressing and releasing the button for it to count
+/// as a click that stamps, rather than a drag that pans.
+const CLICK_SLOP: f32 = 4.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StampMode {
+  /// Adds the live cells of the pattern, the cells under its dead ones are left alone.
+  #[default]
+  Or,
+  /// Overwrites the whole rectangle of the pattern, dead cells included.
+  Replace,
+}
+
+/// The pattern attached to the cursor, already rotated and flipped, and how it is stamped.
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub struct StampTool {
+  pub pattern: Option<Arc<Pattern>>,
+  pub mode: StampMode,
+}
+
+impl StampTool {
+  /// Replaces the attached pattern with `transform` of it.
+  pub fn transform(&mut self, transform: impl FnOnce(&Pattern) -> Pattern) {
+    if let Some(pattern) = &mut self.pattern {
+      *pattern = Arc::new(transform(pattern));
+    }
+  }
+}
+
+/// The cell changes of stamping `pattern` with its top left corner at `offset`, one event
+/// per state. Cells outside a universe of `universe` cells are dropped, and like in
+/// `Pattern::pack_rows` only state 1 is alive in the 1-bit layout.
+pub fn stamp_cells(
+  pattern: &Pattern,
+  offset: IVec2,
+  mode: StampMode,
+  universe: UVec2,
+  bits_per_cell: u32,
+) -> Vec<PaintCells> {
+  let mut states: BTreeMap<u8, Vec<UVec2>> = BTreeMap::new();
+  for y in 0..pattern.height {
+    for x in 0..pattern.width {
+      let state = match (pattern.get(x, y), bits_per_cell) {
+        (state, 1) => u8::from(state == 1),
+        (state, _) => state,
+      };
+      let cell = offset + UVec2::new(x, y).as_ivec2();
+      let inside = cell.cmpge(IVec2::ZERO).all() && cell.cmplt(universe.as_ivec2()).all();
+      if inside && (state != 0 || mode == StampMode::Replace) {
+        states.entry(state).or_default().push(cell.as_uvec2());
+      }
+    }
+  }
+
+  states
+    .into_iter()
+    .map(|(state, cells)| PaintCells { cells, state })
+    .collect()
+}
+
+/// Words the preview of `pattern` takes up in the ghost buffer.
+fn ghost_words(pattern: &Pattern, bits_per_cell: u32) -> u32 {
+  pattern.width.div_ceil(32 / bits_per_cell) * pattern.height
+}
+
+/// Moves the attached pattern with the cursor, turns and flips it with R and F (with Shift
+/// for the other direction), switches the mode with M and drops it with Escape. A left
+/// click stamps it.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_stamping(
+  window: Single<&Window>,
+  buttons: Res<ButtonInput<MouseButton>>,
+  keys: Res<ButtonInput<KeyCode>>,
+  mut tool: ResMut<StampTool>,
+  mut params: ResMut<Params>,
+  mut press: Local<Option<Vec2>>,
+  mut paints: EventWriter<PaintCells>,
+) {
+  let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+  if keys.just_pressed(KeyCode::Escape) {
+    tool.pattern = None;
+  }
+  if keys.just_pressed(KeyCode::KeyR) {
+    tool.transform(if shift {
+      Pattern::rotated_counterclockwise
+    } else {
+      Pattern::rotated_clockwise
+    });
+  }
+  if keys.just_pressed(KeyCode::KeyF) {
+    tool.transform(if shift {
+      Pattern::flipped_vertically
+    } else {
+      Pattern::flipped_horizontally
+    });
+  }
+  if keys.just_pressed(KeyCode::KeyM) {
+    tool.mode = match tool.mode {
+      StampMode::Or => StampMode::Replace,
+      StampMode::Replace => StampMode::Or,
+    };
+  }
+
+  let (Some(pattern), Some(cell)) = (&tool.pattern, cursor_cell(&window, &params)) else {
+    params.ghost_width = 0;
+    *press = None;
+    return;
+  };
+
+  // the pattern hangs centered under the cursor
+  let origin = cell - UVec2::new(pattern.width, pattern.height).as_ivec2() / 2;
+  let shown = ghost_words(pattern, params.bits_per_cell) <= MAX_GHOST_WORDS;
+  params.ghost_x = origin.x;
+  params.ghost_y = origin.y;
+  params.ghost_width = if shown { pattern.width } else { 0 };
+  params.ghost_height = pattern.height;
+  params.ghost_replace = (tool.mode == StampMode::Replace).into();
+
+  // Ctrl and the left button paint instead, Shift selects
+  let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+  if buttons.just_pressed(MouseButton::Left) && !ctrl && !shift {
+    *press = window.cursor_position();
+  }
+  if !buttons.just_released(MouseButton::Left) {
+    return;
+  }
+  let clicked = press
+    .take()
+    .zip(window.cursor_position())
+    .is_some_and(|(from, to)| from.distance(to) <= CLICK_SLOP);
+  if clicked {
+    let universe = UVec2::new(params.cell_count_x, params.buffer_size_y);
+    paints.write_batch(stamp_cells(
+      pattern,
+      origin,
+      tool.mode,
+      universe,
+      params.bits_per_cell,
+    ));
+  }
+}
+
+/// The preview the `display` kernel draws, and the pattern and cell layout it was packed
+/// for.
+#[derive(Resource)]
+pub struct GLGhost {
+  pub buffer: Buffer,
+  packed: Option<(Arc<Pattern>, u32)>,
+}
+
+impl FromWorld for GLGhost {
+  fn from_world(world: &mut World) -> Self {
+    let device = world.resource::<RenderDevice>();
+    let buffer = device.create_buffer(&BufferDescriptor {
+      label: None,
+      size: u64::from(MAX_GHOST_WORDS) * 4,
+      usage: BufferUsages::COPY_DST | BufferUsages::STORAGE,
+      mapped_at_creation: false,
+    });
+    Self {
+      buffer,
+      packed: None,
+    }
+  }
+}
+
+/// Packs the attached pattern into the ghost buffer whenever it or the cell layout changes.
+pub fn prepare_ghost(
+  tool: Option<Res<StampTool>>,
+  params: Res<Params>,
+  ghost: Option<ResMut<GLGhost>>,
+  queue: Res<RenderQueue>,
+) {
+  let (Some(pattern), Some(mut ghost)) = (tool.and_then(|tool| tool.pattern.clone()), ghost) else {
+    return;
+  };
+  let packed = ghost
+    .packed
+    .as_ref()
+    .is_some_and(|(packed, bits_per_cell)| {
+      Arc::ptr_eq(packed, &pattern) && *bits_per_cell == params.bits_per_cell
+    });
+  if packed || ghost_words(&pattern, params.bits_per_cell) > MAX_GHOST_WORDS {
+    return;
+  }
+
+  let (_, rows) = pattern.pack_rows(0, params.bits_per_cell);
+  queue.write_buffer(&ghost.buffer, 0, bytemuck::cast_slice(&rows.concat()));
+  ghost.packed = Some((pattern, params.bits_per_cell));
+}
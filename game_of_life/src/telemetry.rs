@@ -0,0 +1,404 @@
+use std::{
+  mem,
+  ops::Range,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+
+use bevy::{
+  diagnostic::{Diagnostic, DiagnosticMeasurement, DiagnosticPath, DiagnosticsStore},
+  ecs::{
+    resource::Resource,
+    system::{Res, ResMut},
+    world::{FromWorld, World},
+  },
+  log::{error, info},
+  render::{
+    extract_resource::ExtractResource,
+    render_resource::{Buffer, BufferDescriptor, BufferUsages, MapMode, WgpuFeatures},
+    renderer::{RenderContext, RenderDevice, RenderQueue},
+  },
+};
+use wgpu::{ComputePassTimestampWrites, QuerySet, QuerySetDescriptor, QueryType};
+
+/// Timings kept per span for the statistics.
+pub const TIMING_SAMPLES: usize = 1000;
+
+/// Resolved queries must start at multiples of 256 bytes of the resolve buffer.
+const QUERY_RESOLVE_STRIDE: u64 = 256;
+
+/// What the render graph node times.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Span {
+  /// The pass writing the initial random soup.
+  Randomize,
+  /// The update passes of the generations computed in one frame, together with the keyframe
+  /// copies and population counts that interrupt them.
+  Update,
+  Display,
+  /// Wall-clock time between two steps, which includes the frame pacing.
+  StepInterval,
+}
+
+impl Span {
+  pub const ALL: [Span; 4] = [
+    Span::Randomize,
+    Span::Update,
+    Span::Display,
+    Span::StepInterval,
+  ];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Span::Randomize => "randomize",
+      Span::Update => "update",
+      Span::Display => "display",
+      Span::StepInterval => "step_interval",
+    }
+  }
+
+  fn index(&self) -> usize {
+    *self as usize
+  }
+}
+
+/// Where a timing comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Clock {
+  /// Timestamp queries around the passes on the GPU.
+  Gpu,
+  /// The CPU, which for passes only sees how long they took to record.
+  #[default]
+  Cpu,
+}
+
+impl Clock {
+  fn field(&self) -> &'static str {
+    match self {
+      Clock::Gpu => "elapsed_gpu",
+      Clock::Cpu => "elapsed_cpu",
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+  pub span: Span,
+  pub clock: Clock,
+  pub milliseconds: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimingSummary {
+  pub min: f64,
+  pub avg: f64,
+  pub p95: f64,
+  pub max: f64,
+}
+
+/// The latest timings of a span, overwriting the oldest once full.
+#[derive(Clone, Debug)]
+pub struct TimingRing {
+  samples: Vec<f64>,
+  capacity: usize,
+  next: usize,
+}
+
+impl TimingRing {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      samples: Vec::with_capacity(capacity),
+      capacity,
+      next: 0,
+    }
+  }
+
+  pub fn push(&mut self, sample: f64) {
+    if self.samples.len() < self.capacity {
+      self.samples.push(sample);
+    } else if self.capacity > 0 {
+      self.samples[self.next] = sample;
+    }
+    self.next = (self.next + 1) % self.capacity.max(1);
+  }
+
+  pub fn len(&self) -> usize {
+    self.samples.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.samples.is_empty()
+  }
+
+  /// `None` until there is a sample.
+  pub fn summary(&self) -> Option<TimingSummary> {
+    let mut sorted = self.samples.clone();
+    sorted.sort_by(f64::total_cmp);
+    let (&min, &max) = (sorted.first()?, sorted.last()?);
+    let p95 = sorted[(sorted.len() * 95).div_ceil(100) - 1];
+    Some(TimingSummary {
+      min,
+      avg: sorted.iter().sum::<f64>() / sorted.len() as f64,
+      p95,
+      max,
+    })
+  }
+}
+
+/// The timings of every span, and the clock they were last taken with.
+#[derive(Resource, Clone, Debug)]
+pub struct Telemetry {
+  rings: [TimingRing; Span::ALL.len()],
+  clocks: [Clock; Span::ALL.len()],
+}
+
+impl Default for Telemetry {
+  fn default() -> Self {
+    Self {
+      rings: Span::ALL.map(|_| TimingRing::new(TIMING_SAMPLES)),
+      clocks: [Clock::Cpu; Span::ALL.len()],
+    }
+  }
+}
+
+impl Telemetry {
+  pub fn timings(&self, span: Span) -> &TimingRing {
+    &self.rings[span.index()]
+  }
+
+  pub fn clock(&self, span: Span) -> Clock {
+    self.clocks[span.index()]
+  }
+
+  pub fn record(&mut self, timing: Timing) {
+    self.rings[timing.span.index()].push(timing.milliseconds);
+    self.clocks[timing.span.index()] = timing.clock;
+  }
+}
+
+/// The diagnostic the timings of `span` are reported to, `game_of_life/<span>/elapsed_gpu`
+/// or `elapsed_cpu` like Bevy's own render diagnostics.
+pub fn diagnostic_path(span: Span, clock: Clock) -> DiagnosticPath {
+  DiagnosticPath::from_components(["game_of_life", span.name(), clock.field()])
+}
+
+/// Shared with the render world, which pushes the timings into it as they come in.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct TelemetryReadback(Arc<Mutex<Vec<Timing>>>);
+
+impl TelemetryReadback {
+  pub fn push(&self, timing: Timing) {
+    self.0.lock().unwrap().push(timing);
+  }
+}
+
+pub fn receive_telemetry(
+  readback: Res<TelemetryReadback>,
+  mut telemetry: ResMut<Telemetry>,
+  store: Option<ResMut<DiagnosticsStore>>,
+) {
+  let timings = mem::take(&mut *readback.0.lock().unwrap());
+  let mut store = store;
+  let time = Instant::now();
+  for timing in timings {
+    telemetry.record(timing);
+
+    let Some(store) = store.as_deref_mut() else {
+      continue;
+    };
+    let path = diagnostic_path(timing.span, timing.clock);
+    if store.get(&path).is_none() {
+      store.add(Diagnostic::new(path.clone()).with_suffix("ms"));
+    }
+    if let Some(diagnostic) = store.get_mut(&path) {
+      diagnostic.add_measurement(DiagnosticMeasurement {
+        time,
+        value: timing.milliseconds,
+      });
+    }
+  }
+}
+
+pub fn print_telemetry(telemetry: Res<Telemetry>) {
+  for span in Span::ALL {
+    let Some(summary) = telemetry.timings(span).summary() else {
+      continue;
+    };
+    info!(
+      "{} ({:?}): min {:.3} ms, avg {:.3} ms, p95 {:.3} ms, max {:.3} ms",
+      span.name(),
+      telemetry.clock(span),
+      summary.min,
+      summary.avg,
+      summary.p95,
+      summary.max
+    );
+  }
+}
+
+/// Spans started during this frame's node run, for the queries to resolve.
+#[derive(Default)]
+struct TimestampFrame {
+  spans: Vec<Span>,
+  staging: Option<Buffer>,
+}
+
+/// Timestamp queries around the passes of the render graph node. Without the
+/// `TIMESTAMP_QUERY` feature the passes are timed on the CPU instead.
+#[derive(Resource)]
+pub struct GLTimestamps {
+  queries: Option<(QuerySet, Buffer)>,
+  /// Nanoseconds per timestamp tick.
+  period: f32,
+  frame: Mutex<TimestampFrame>,
+}
+
+/// A span being recorded, timed on the CPU when there are no timestamp queries.
+pub struct SpanTimer {
+  span: Span,
+  start: Instant,
+}
+
+impl FromWorld for GLTimestamps {
+  fn from_world(world: &mut World) -> Self {
+    let device = world.resource::<RenderDevice>();
+    let span_count = Span::ALL.len() as u64;
+    let queries = device
+      .features()
+      .contains(WgpuFeatures::TIMESTAMP_QUERY)
+      .then(|| {
+        let query_set = device.wgpu_device().create_query_set(&QuerySetDescriptor {
+          label: Some("timestamps"),
+          ty: QueryType::Timestamp,
+          count: Span::ALL.len() as u32 * 2,
+        });
+        let resolve = device.create_buffer(&BufferDescriptor {
+          label: None,
+          size: span_count * QUERY_RESOLVE_STRIDE,
+          usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+          mapped_at_creation: false,
+        });
+        (query_set, resolve)
+      });
+    if queries.is_none() {
+      info!("Timestamp queries are not supported, timing the passes on the CPU");
+    }
+
+    Self {
+      queries,
+      period: world.resource::<RenderQueue>().get_timestamp_period(),
+      frame: Mutex::new(TimestampFrame::default()),
+    }
+  }
+}
+
+impl GLTimestamps {
+  fn queries(&self, span: Span) -> Range<u32> {
+    let first = span.index() as u32 * 2;
+    first..first + 2
+  }
+
+  pub fn start(&self, span: Span) -> SpanTimer {
+    SpanTimer {
+      span,
+      start: Instant::now(),
+    }
+  }
+
+  /// Timestamp writes for a compute pass of `timer`'s span, at the beginning of the span's
+  /// first pass and the end of its last one.
+  pub fn writes(
+    &self,
+    timer: &SpanTimer,
+    first: bool,
+    last: bool,
+  ) -> Option<ComputePassTimestampWrites<'_>> {
+    let (query_set, _) = self.queries.as_ref()?;
+    let queries = self.queries(timer.span);
+    if first {
+      self.frame.lock().unwrap().spans.push(timer.span);
+    }
+    Some(ComputePassTimestampWrites {
+      query_set,
+      beginning_of_pass_write_index: first.then_some(queries.start),
+      end_of_pass_write_index: last.then_some(queries.end - 1),
+    })
+  }
+
+  /// Ends the span, reporting its CPU time when the GPU cannot be asked.
+  pub fn finish(&self, timer: SpanTimer, readback: Option<&TelemetryReadback>) {
+    if self.queries.is_some() {
+      return;
+    }
+    if let Some(readback) = readback {
+      readback.push(Timing {
+        span: timer.span,
+        clock: Clock::Cpu,
+        milliseconds: timer.start.elapsed().as_secs_f64() * 1000.0,
+      });
+    }
+  }
+
+  /// Resolves the queries written this frame into a staging buffer for `map_timestamps`.
+  pub fn resolve(&self, render_context: &mut RenderContext) {
+    let Some((query_set, resolve)) = &self.queries else {
+      return;
+    };
+    let mut frame = self.frame.lock().unwrap();
+    if frame.spans.is_empty() {
+      return;
+    }
+
+    let staging = render_context
+      .render_device()
+      .create_buffer(&BufferDescriptor {
+        label: None,
+        size: resolve.size(),
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+      });
+    let encoder = render_context.command_encoder();
+    for span in &frame.spans {
+      let offset = span.index() as u64 * QUERY_RESOLVE_STRIDE;
+      encoder.resolve_query_set(query_set, self.queries(*span), resolve, offset);
+    }
+    encoder.copy_buffer_to_buffer(resolve, 0, &staging, 0, resolve.size());
+    frame.staging = Some(staging);
+  }
+}
+
+/// Maps the timestamps resolved by the render graph node, without waiting for the GPU.
+pub fn map_timestamps(timestamps: Res<GLTimestamps>, readback: Res<TelemetryReadback>) {
+  let frame = mem::take(&mut *timestamps.frame.lock().unwrap());
+  let Some(staging) = frame.staging else {
+    return;
+  };
+  let spans = frame.spans;
+  let period = f64::from(timestamps.period);
+  let readback = readback.clone();
+  let mapped = staging.clone();
+
+  staging.slice(..).map_async(MapMode::Read, move |result| {
+    if let Err(err) = result {
+      error!("Failed to map the timestamp buffer: {err}");
+      return;
+    }
+    let ticks: Vec<u64> = bytemuck::cast_slice(&mapped.slice(..).get_mapped_range()).to_vec();
+    mapped.unmap();
+
+    let stride = (QUERY_RESOLVE_STRIDE / 8) as usize;
+    for span in spans {
+      let start = ticks[span.index() * stride];
+      let end = ticks[span.index() * stride + 1];
+      // some drivers let the counter wrap or reset between passes
+      if end < start {
+        continue;
+      }
+      readback.push(Timing {
+        span,
+        clock: Clock::Gpu,
+        milliseconds: (end - start) as f64 * period / 1e6,
+      });
+    }
+  });
+}
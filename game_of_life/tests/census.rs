@@ -0,0 +1,123 @@
+use std::fs;
+
+use bevy::{
+  MinimalPlugins,
+  app::{App, AppExit},
+  math::UVec2,
+};
+use game_of_life::{
+  CensusRun, GameOfLifePlugin, ObjectKind, Rule, classify, pattern::parse_rle, wechsler,
+};
+
+fn classified(rle: &str) -> (String, ObjectKind) {
+  let object = classify(&parse_rle(rle).unwrap(), &Rule::default());
+  (object.apgcode, object.kind)
+}
+
+#[test]
+fn names_objects_like_apgsearch() {
+  assert_eq!(
+    classified("x = 2, y = 2\n2o$2o!"),
+    ("xs4_33".to_string(), ObjectKind::StillLife)
+  );
+  assert_eq!(
+    classified("x = 4, y = 3\nb2o$o2bo$b2o!"),
+    ("xs6_696".to_string(), ObjectKind::StillLife)
+  );
+  assert_eq!(
+    classified("x = 3, y = 1\n3o!"),
+    ("xp2_7".to_string(), ObjectKind::Oscillator { period: 2 })
+  );
+  // every phase and orientation of the glider has the same name
+  for glider in ["x = 3, y = 3\nbo$2bo$3o!", "x = 3, y = 3\no$b2o$2o!"] {
+    assert_eq!(
+      classified(glider),
+      (
+        "xq4_153".to_string(),
+        ObjectKind::Spaceship {
+          period: 4,
+          dx: 1,
+          dy: 1
+        }
+      )
+    );
+  }
+  assert_eq!(
+    classified("x = 5, y = 4\nbo2bo$o4b$o3bo$4o!"),
+    (
+      "xq4_6frc".to_string(),
+      ObjectKind::Spaceship {
+        period: 4,
+        dx: 2,
+        dy: 0
+      }
+    )
+  );
+}
+
+#[test]
+fn describes_the_kinds_of_objects() {
+  assert_eq!(ObjectKind::StillLife.to_string(), "still life");
+  assert_eq!(
+    ObjectKind::Oscillator { period: 15 }.to_string(),
+    "oscillator p15"
+  );
+  let glider = ObjectKind::Spaceship {
+    period: 4,
+    dx: 1,
+    dy: 1,
+  };
+  assert_eq!(glider.to_string(), "spaceship c/4 diagonal");
+  let lwss = ObjectKind::Spaceship {
+    period: 4,
+    dx: 2,
+    dy: 0,
+  };
+  assert_eq!(lwss.to_string(), "spaceship c/2 orthogonal");
+}
+
+#[test]
+fn encodes_empty_columns_and_strips() {
+  let pattern = parse_rle("x = 9, y = 6\no7bo$9b$9b$9b$9b$o!").unwrap();
+  assert_eq!(wechsler(&pattern), "1y31z1");
+  let pattern = parse_rle("x = 4, y = 1\no2bo!").unwrap();
+  assert_eq!(wechsler(&pattern), "1w1");
+}
+
+#[test]
+fn writes_a_census_of_the_soups() {
+  let directory = std::env::temp_dir().join(format!("census-{}", std::process::id()));
+  fs::create_dir_all(&directory).unwrap();
+
+  let run = CensusRun {
+    seeds: 0..2,
+    soup_size: 8,
+    size: UVec2::splat(64),
+    max_generations: 2000,
+    report: directory.join("census.csv"),
+  };
+  let exit = App::new()
+    .add_plugins(MinimalPlugins)
+    .add_plugins(GameOfLifePlugin {
+      census: Some(run.clone()),
+      ..Default::default()
+    })
+    .run();
+  assert_eq!(exit, AppExit::Success);
+
+  let report = fs::read_to_string(&run.report).unwrap();
+  let mut lines = report.lines();
+  assert_eq!(
+    lines.next(),
+    Some("# 2 soups of 8x8 cells in a 64x64 universe, seeds 0..2, rule B3/S23")
+  );
+  assert!(lines.next().unwrap().starts_with("# "));
+  assert_eq!(lines.next(), Some("apgcode,kind,count"));
+  // counted from the most common object down
+  let counts: Vec<u64> = lines
+    .map(|line| line.rsplit(',').next().unwrap().parse().unwrap())
+    .collect();
+  assert!(counts.is_sorted_by(|a, b| a >= b));
+
+  fs::remove_dir_all(directory).unwrap();
+}
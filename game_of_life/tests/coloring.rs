@@ -0,0 +1,128 @@
+use bevy::math::{UVec2, Vec2, Vec4};
+use game_of_life::{
+  AgeGradient, CellColoring, CpuUniverse, Rule, Topology, Viewport, pattern::parse_rle,
+};
+
+const BLOCK: &str = "x = 2, y = 2\n2o$2o!";
+
+fn with_pattern(rle: &str, x: u32, y: u32) -> CpuUniverse {
+  let mut universe = CpuUniverse::new(UVec2::new(40, 8), &Rule::default(), Topology::DeadEdges);
+  universe.load(&parse_rle(rle).unwrap(), UVec2::new(x, y));
+  universe
+}
+
+fn any_aged(universe: &CpuUniverse) -> bool {
+  universe.ages().iter().any(|&word| word != 0)
+}
+
+#[test]
+fn the_key_cycles_through_every_coloring() {
+  let mut coloring = CellColoring::default();
+  let mut seen = vec![coloring];
+  loop {
+    coloring = coloring.next();
+    if coloring == CellColoring::Plain {
+      break;
+    }
+    seen.push(coloring);
+  }
+  assert_eq!(
+    seen,
+    [
+      CellColoring::Plain,
+      CellColoring::Age(AgeGradient::Heat),
+      CellColoring::Age(AgeGradient::Viridis),
+      CellColoring::Age(AgeGradient::Ice),
+    ]
+  );
+
+  // only the plain coloring gets by without ages, and the shader tells them all apart
+  let ids: Vec<u32> = seen.iter().map(CellColoring::shader_id).collect();
+  assert_eq!(ids, [0, 1, 2, 3]);
+  assert!(!CellColoring::Plain.tracks_ages());
+  assert!(seen[1..].iter().all(CellColoring::tracks_ages));
+}
+
+#[test]
+fn cells_age_every_generation_they_live() {
+  // a blinker whose middle cell survives and whose ends are born and die in turn
+  let mut universe = with_pattern("x = 3, y = 1\n3o!", 30, 3);
+  assert_eq!(universe.age(31, 3), 0);
+
+  universe.step();
+  assert_eq!(universe.age(31, 3), 1);
+  assert_eq!(universe.age(31, 2), 1);
+  assert_eq!(universe.age(30, 3), 0);
+  universe.step();
+  assert_eq!(universe.age(31, 3), 2);
+  assert_eq!(universe.age(30, 3), 1);
+  assert_eq!(universe.age(31, 2), 0);
+
+  // a block lives on and its ages saturate
+  let mut universe = with_pattern(BLOCK, 3, 3);
+  for generation in 1..=300 {
+    universe.step();
+    assert_eq!(universe.age(4, 4), generation.min(255));
+  }
+  assert_eq!(universe.age(5, 4), 0);
+}
+
+#[test]
+fn zoomed_out_pixels_average_the_live_cells_they_cover() {
+  let mut universe = with_pattern(BLOCK, 0, 0);
+  for _ in 0..10 {
+    universe.step();
+  }
+  // a painted cell has not aged yet and counts as newly born
+  universe.set(3, 3, 1);
+
+  assert_eq!(universe.average_age(Vec2::ZERO, 1.0), 10.0);
+  assert_eq!(universe.average_age(Vec2::new(3.0, 3.0), 2.0), 1.0);
+  assert_eq!(universe.average_age(Vec2::new(2.0, 0.0), 1.0), 0.0);
+  // four pixels per cell cover 4x4 cells, the block and the painted cell
+  assert_eq!(universe.average_age(Vec2::ZERO, 0.25), 41.0 / 5.0);
+  // far out only every fourth cell along each axis is sampled
+  assert_eq!(universe.average_age(Vec2::ZERO, 1.0 / 64.0), 10.0);
+
+  universe.set_coloring(CellColoring::Age(AgeGradient::Heat));
+  let viewport = Viewport {
+    resolution: UVec2::new(8, 8),
+    center: Vec2::new(4.0, 4.0),
+    zoom: 1.0,
+    tile_display: false,
+  };
+  // the newest cells take the start of the gradient, dead cells stay black
+  assert_eq!(
+    universe.pixel_color(&viewport, UVec2::new(3, 3)),
+    Vec4::new(1.0, 1.0, 0.6, 1.0)
+  );
+  assert_eq!(
+    universe.pixel_color(&viewport, UVec2::new(2, 0)),
+    Vec4::new(0.0, 0.0, 0.0, 1.0)
+  );
+  assert_ne!(
+    universe.pixel_color(&viewport, UVec2::ZERO),
+    universe.pixel_color(&viewport, UVec2::new(3, 3))
+  );
+}
+
+#[test]
+fn reseeding_rewinding_and_loading_start_the_ages_over() {
+  let mut universe = with_pattern(BLOCK, 3, 3);
+  let keyframe = universe.words().to_vec();
+  universe.step();
+  assert!(any_aged(&universe));
+
+  universe.restore(&keyframe);
+  assert!(!any_aged(&universe));
+  universe.step();
+  assert_eq!(universe.age(3, 3), 1);
+
+  universe.randomize(7);
+  assert!(!any_aged(&universe));
+  universe.step();
+  assert!(any_aged(&universe));
+
+  universe.load(&parse_rle(BLOCK).unwrap(), UVec2::new(3, 3));
+  assert!(!any_aged(&universe));
+}
@@ -0,0 +1,39 @@
+use game_of_life::{MAX_GENERATIONS_PER_FRAME, SimulationControl, SimulationControls};
+
+#[test]
+fn controls_pause_and_change_the_speed() {
+  let mut controls = SimulationControls::default();
+  assert!(!controls.paused);
+  assert_eq!(
+    (controls.target_tps, controls.generations_per_frame),
+    (10, 1)
+  );
+
+  controls.apply(SimulationControl::TogglePause);
+  assert!(controls.paused);
+  controls.apply(SimulationControl::Pause);
+  assert!(controls.paused);
+  controls.apply(SimulationControl::TogglePause);
+  assert!(!controls.paused);
+  controls.apply(SimulationControl::Pause);
+  controls.apply(SimulationControl::Resume);
+  assert!(!controls.paused);
+
+  // stepping only asks for a generation, it never changes the settings
+  let before = controls;
+  controls.apply(SimulationControl::Step);
+  assert_ne!(controls, before);
+  assert_eq!(
+    (controls.paused, controls.target_tps),
+    (before.paused, before.target_tps)
+  );
+
+  controls.apply(SimulationControl::SetTargetTps(0));
+  assert_eq!(controls.target_tps, 0);
+  controls.apply(SimulationControl::SetGenerationsPerFrame(0));
+  assert_eq!(controls.generations_per_frame, 1);
+  controls.apply(SimulationControl::SetGenerationsPerFrame(64));
+  assert_eq!(controls.generations_per_frame, 64);
+  controls.apply(SimulationControl::SetGenerationsPerFrame(u32::MAX));
+  assert_eq!(controls.generations_per_frame, MAX_GENERATIONS_PER_FRAME);
+}
@@ -0,0 +1,298 @@
+use std::{fs, time::Duration};
+
+use bevy::{
+  DefaultPlugins, MinimalPlugins,
+  app::{App, AppExit, PluginGroup, ScheduleRunnerPlugin},
+  log::LogPlugin,
+  math::{UVec2, Vec2, Vec4},
+  window::{ExitCondition, WindowPlugin},
+  winit::WinitPlugin,
+};
+use game_of_life::{
+  Axis, CpuUniverse, GameOfLifePlugin, HeadlessBackend, HeadlessRun, HeadlessStart, Rule, Topology,
+  Viewport,
+  pattern::{Pattern, parse_rle},
+};
+
+const GLIDER: &str = "x = 3, y = 3\nbob$2bo$3o!";
+
+fn life(width: u32, height: u32, topology: Topology) -> CpuUniverse {
+  CpuUniverse::new(UVec2::new(width, height), &Rule::default(), topology)
+}
+
+fn with_pattern(mut universe: CpuUniverse, rle: &str, x: u32, y: u32) -> CpuUniverse {
+  universe.load(&parse_rle(rle).unwrap(), UVec2::new(x, y));
+  universe
+}
+
+fn live_cells(universe: &CpuUniverse) -> Vec<(u32, u32)> {
+  let size = universe.size();
+  (0..size.y)
+    .flat_map(|y| (0..size.x).map(move |x| (x, y)))
+    .filter(|&(x, y)| universe.get(x, y) == 1)
+    .collect()
+}
+
+fn shifted(cells: &[(u32, u32)], dx: u32, dy: u32) -> Vec<(u32, u32)> {
+  cells.iter().map(|&(x, y)| (x + dx, y + dy)).collect()
+}
+
+#[test]
+fn blinker_oscillates_with_period_two() {
+  let mut universe = with_pattern(life(8, 8, Topology::DeadEdges), "x = 3, y = 1\n3o!", 2, 3);
+  let horizontal = live_cells(&universe);
+
+  universe.step();
+  assert_eq!(live_cells(&universe), vec![(3, 2), (3, 3), (3, 4)]);
+  universe.step();
+  assert_eq!(live_cells(&universe), horizontal);
+}
+
+#[test]
+fn still_lifes_do_not_change() {
+  for rle in [
+    "x = 2, y = 2\n2o$2o!",
+    "x = 4, y = 3\nb2o$o2bo$b2o!",
+    "x = 4, y = 4\nb2o$o2bo$bobo$2bo!",
+  ] {
+    let mut universe = with_pattern(life(10, 10, Topology::DeadEdges), rle, 3, 3);
+    let words = universe.words().to_vec();
+    universe.step();
+    assert_eq!(universe.words(), words, "{rle}");
+  }
+}
+
+#[test]
+fn glider_moves_diagonally_across_word_boundaries() {
+  let mut universe = with_pattern(life(64, 8, Topology::DeadEdges), GLIDER, 28, 1);
+  let start = live_cells(&universe);
+
+  for _ in 0..4 {
+    universe.step();
+  }
+  assert_eq!(live_cells(&universe), shifted(&start, 1, 1));
+}
+
+#[test]
+fn glider_wraps_around_a_torus() {
+  let mut universe = with_pattern(life(16, 16, Topology::Torus), GLIDER, 0, 0);
+  let start = universe.words().to_vec();
+
+  // a glider moves one cell diagonally every four generations
+  for _ in 0..4 * 16 {
+    universe.step();
+  }
+  assert_eq!(universe.words(), start);
+}
+
+#[test]
+fn dead_edges_stop_a_glider() {
+  let mut universe = with_pattern(life(8, 8, Topology::DeadEdges), GLIDER, 5, 5);
+  for _ in 0..8 {
+    universe.step();
+  }
+  // the glider turns into a block in the corner
+  assert_eq!(live_cells(&universe), vec![(6, 6), (7, 6), (6, 7), (7, 7)]);
+}
+
+#[test]
+fn alive_edges_give_birth_along_the_border() {
+  let mut universe = life(8, 8, Topology::AliveEdges);
+  universe.step();
+
+  // edge cells see three live cells past the edge, corners see five
+  let border = |x: u32, y: u32| (x == 0 || x == 7) != (y == 0 || y == 7);
+  for y in 0..8 {
+    for x in 0..8 {
+      assert_eq!(universe.get(x, y) == 1, border(x, y), "({x}, {y})");
+    }
+  }
+}
+
+#[test]
+fn padding_past_the_width_stays_dead() {
+  let mut universe = life(40, 4, Topology::AliveEdges);
+  universe.randomize(7);
+  for _ in 0..3 {
+    universe.step();
+    for row in universe.words().chunks(universe.buffer_size_x() as usize) {
+      assert_eq!(row[1] & 0x00FF_FFFF, 0);
+    }
+  }
+}
+
+#[test]
+fn randomize_matches_the_shader_hash() {
+  let mut universe = life(64, 2, Topology::DeadEdges);
+  universe.randomize(0);
+  assert_eq!(universe.words()[..2], [0x67b2772f, 0x08fcaab9]);
+
+  let mut generations = CpuUniverse::new(
+    UVec2::new(8, 1),
+    &"B2/S/C3".parse().unwrap(),
+    Topology::DeadEdges,
+  );
+  generations.randomize(0);
+  assert_eq!(
+    generations.words(),
+    [0x67b2772f & 0x01010101, 0x08fcaab9 & 0x01010101]
+  );
+}
+
+#[test]
+fn generations_cells_die_through_their_dying_states() {
+  let rule = "B2/S/C3".parse().unwrap();
+  let mut universe = CpuUniverse::new(UVec2::new(6, 6), &rule, Topology::DeadEdges);
+  universe.set(2, 2, 1);
+
+  universe.step();
+  assert_eq!(universe.get(2, 2), 2);
+  universe.step();
+  assert_eq!(universe.get(2, 2), 0);
+}
+
+#[test]
+fn larger_than_life_with_range_one_is_life() {
+  let ltl = "R1,C0,M0,S2..3,B3..3,NM".parse().unwrap();
+  let mut life = life(48, 24, Topology::Torus);
+  life.randomize(99);
+  let mut larger = CpuUniverse::from_words(
+    UVec2::new(48, 24),
+    &ltl,
+    Topology::Torus,
+    life.words().to_vec(),
+  );
+
+  for _ in 0..10 {
+    life.step();
+    larger.step();
+    assert_eq!(life.words(), larger.words());
+  }
+}
+
+#[test]
+fn twisted_edges_mirror_the_other_axis() {
+  let mut universe = life(
+    8,
+    6,
+    Topology::KleinBottle {
+      twisted: Axis::Horizontal,
+    },
+  );
+  universe.set(1, 5, 1);
+  assert_eq!(universe.cell_state(6, -1), 1);
+  assert_eq!(universe.cell_state(1, -1), 0);
+
+  let mut sphere = life(6, 6, Topology::Sphere);
+  sphere.set(4, 0, 1);
+  // the left edge is glued to the top edge
+  assert_eq!(sphere.cell_state(-1, 4), 1);
+}
+
+#[test]
+fn display_maps_pixels_to_cells() {
+  let mut universe = life(10, 10, Topology::Torus);
+  universe.set(5, 5, 1);
+  let mut viewport = Viewport {
+    resolution: UVec2::new(100, 100),
+    center: Vec2::new(5.0, 5.0),
+    zoom: 10.0,
+    tile_display: false,
+  };
+
+  assert_eq!(
+    CpuUniverse::pixel_position(&viewport, UVec2::new(55, 40)),
+    Vec2::new(5.5, 4.0)
+  );
+  assert_eq!(
+    universe.pixel_color(&viewport, UVec2::new(50, 50)),
+    Vec4::ONE
+  );
+  assert_eq!(universe.pixel_color(&viewport, UVec2::new(49, 50)), Vec4::W);
+
+  viewport.center = Vec2::ZERO;
+  let outside = UVec2::new(0, 0);
+  assert_eq!(
+    universe.pixel_color(&viewport, outside),
+    Vec4::new(1.0, 0.0, 0.0, 1.0)
+  );
+  viewport.tile_display = true;
+  assert_eq!(universe.pixel_color(&viewport, outside), Vec4::ONE);
+}
+
+/// Runs a soup of `seed` for `generations` generations as a headless app on `backend`
+/// and reads back the live cells it wrote.
+fn run_headless(
+  backend: HeadlessBackend,
+  rule: &str,
+  topology: Topology,
+  size: UVec2,
+  generations: u64,
+) -> Pattern {
+  let directory = std::env::temp_dir().join(format!(
+    "gpu-vs-cpu-{}-{backend:?}-{generations}",
+    std::process::id()
+  ));
+  fs::create_dir_all(&directory).unwrap();
+  let run = HeadlessRun {
+    backend,
+    start: HeadlessStart::Seed(7),
+    generations,
+    size,
+    output: directory.join("final.rle"),
+    statistics: directory.join("population.csv"),
+    sample_interval: generations.max(1),
+  };
+
+  let mut app = App::new();
+  match backend {
+    HeadlessBackend::Cpu => app.add_plugins(MinimalPlugins),
+    HeadlessBackend::Gpu => app.add_plugins((
+      DefaultPlugins
+        .set(WindowPlugin {
+          primary_window: None,
+          exit_condition: ExitCondition::DontExit,
+          close_when_requested: false,
+        })
+        .disable::<WinitPlugin>()
+        .disable::<LogPlugin>(),
+      ScheduleRunnerPlugin::run_loop(Duration::ZERO),
+    )),
+  };
+  let exit = app
+    .insert_resource(rule.parse::<Rule>().unwrap())
+    .insert_resource(topology)
+    .add_plugins(GameOfLifePlugin {
+      headless: Some(run.clone()),
+      ..Default::default()
+    })
+    .run();
+  assert_eq!(exit, AppExit::Success);
+
+  let pattern = parse_rle(&fs::read_to_string(&run.output).unwrap()).unwrap();
+  fs::remove_dir_all(directory).unwrap();
+  pattern
+}
+
+/// Checks the `randomize` and `update` kernels against `CpuUniverse`: the soup both
+/// backends start from and the generations after it have to hold the same cells.
+fn assert_backends_match(rule: &str, topology: Topology, size: UVec2) {
+  for generations in [0, 64] {
+    let gpu = run_headless(HeadlessBackend::Gpu, rule, topology, size, generations);
+    let cpu = run_headless(HeadlessBackend::Cpu, rule, topology, size, generations);
+    assert_eq!(gpu, cpu, "{rule} after {generations} generations");
+  }
+}
+
+#[test]
+#[ignore = "needs a GPU"]
+fn the_shaders_match_the_cpu_universe_for_life_like_rules() {
+  // 100 cells leave 28 cells of padding in the last word of a row
+  assert_backends_match("B3/S23", Topology::DeadEdges, UVec2::new(100, 70));
+}
+
+#[test]
+#[ignore = "needs a GPU"]
+fn the_shaders_match_the_cpu_universe_for_generations_rules() {
+  assert_backends_match("B2/S345/C4", Topology::Torus, UVec2::new(90, 60));
+}
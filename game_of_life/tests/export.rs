@@ -0,0 +1,102 @@
+use bevy::math::{IVec2, URect, UVec2};
+use game_of_life::{
+  CpuUniverse, ExportBounds, Rule, Topology,
+  pattern::{Pattern, parse_plaintext, parse_rle, write_plaintext, write_rle},
+};
+
+fn rule(rulestring: &str) -> Rule {
+  rulestring.parse().unwrap()
+}
+
+/// The storage buffer after loading `pattern` into an empty universe, the way a dropped
+/// export file is loaded.
+fn reloaded(pattern: &Pattern, size: UVec2) -> Vec<u32> {
+  let mut universe = CpuUniverse::new(size, &pattern.rule.unwrap(), Topology::DeadEdges);
+  universe.load(pattern, pattern.position.unwrap().as_uvec2());
+  universe.words().to_vec()
+}
+
+#[test]
+fn exports_load_back_to_the_same_cells() {
+  // 40 cells take two words per row, the last 24 bits of the second one are padding
+  let mut universe = CpuUniverse::new(UVec2::new(40, 6), &rule("B36/S23"), Topology::DeadEdges);
+  universe.load(
+    &parse_rle("x = 3, y = 3\nbob$2bo$3o!").unwrap(),
+    UVec2::new(37, 1),
+  );
+  universe.load(&parse_rle("x = 3, y = 1\n3o!").unwrap(), UVec2::new(2, 4));
+  let snapshot = universe.snapshot(12);
+  assert_eq!(snapshot.live_bounds(), Some(URect::new(2, 1, 40, 5)));
+
+  let live = snapshot.export_pattern(ExportBounds::LiveCells);
+  assert_eq!((live.width, live.height), (38, 4));
+  assert_eq!(live.cells.iter().filter(|&&state| state == 1).count(), 8);
+  // the rightmost cell of the universe, next to the padding
+  assert_eq!(live.get(37, 1), 1);
+  let rle = write_rle(&live);
+  assert!(rle.starts_with("#CXRLE Pos=2,1 Gen=12\nx = 38, y = 4, rule = B36/S23\n"));
+  let parsed = parse_rle(&rle).unwrap();
+  assert_eq!(parsed, live);
+  assert_eq!(reloaded(&parsed, universe.size()), universe.words());
+
+  let whole = snapshot.export_pattern(ExportBounds::Universe);
+  assert_eq!((whole.width, whole.height), (40, 6));
+  assert_eq!(whole.position, Some(IVec2::ZERO));
+  let parsed = parse_rle(&write_rle(&whole)).unwrap();
+  assert_eq!(parsed, whole);
+  assert_eq!(reloaded(&parsed, universe.size()), universe.words());
+
+  // plaintext has no position, everything else comes back
+  for pattern in [live, whole] {
+    let parsed = parse_plaintext(&write_plaintext(&pattern)).unwrap();
+    assert_eq!(
+      (parsed.width, parsed.height),
+      (pattern.width, pattern.height)
+    );
+    assert_eq!(parsed.cells, pattern.cells);
+    assert_eq!(parsed.rule, Some(rule("B36/S23")));
+    assert_eq!((parsed.generation, parsed.position), (Some(12), None));
+  }
+}
+
+#[test]
+fn exports_keep_the_dying_states_of_generations_rules() {
+  // 18 cells of 8 bits take five words per row, the last two cells are padding
+  let mut universe = CpuUniverse::new(UVec2::new(18, 5), &rule("B2/S345/C4"), Topology::DeadEdges);
+  for (x, y, state) in [(1, 1, 1), (2, 1, 2), (3, 1, 3), (16, 3, 3), (17, 3, 1)] {
+    universe.set(x, y, state);
+  }
+  let snapshot = universe.snapshot(7);
+  assert_eq!(snapshot.get(2, 1), 2);
+  assert_eq!(snapshot.get(17, 3), 1);
+  assert_eq!(snapshot.live_bounds(), Some(URect::new(1, 1, 18, 4)));
+
+  let live = snapshot.export_pattern(ExportBounds::LiveCells);
+  assert_eq!((live.width, live.height), (17, 3));
+  assert_eq!([live.get(0, 0), live.get(1, 0), live.get(2, 0)], [1, 2, 3]);
+  assert_eq!([live.get(15, 2), live.get(16, 2)], [3, 1]);
+  let rle = write_rle(&live);
+  assert!(rle.starts_with("#CXRLE Pos=1,1 Gen=7\nx = 17, y = 3, rule = B2/S345/C4\n"));
+  let parsed = parse_rle(&rle).unwrap();
+  assert_eq!(parsed, live);
+  assert_eq!(reloaded(&parsed, universe.size()), universe.words());
+
+  let whole = snapshot.export_pattern(ExportBounds::Universe);
+  assert_eq!((whole.width, whole.height), (18, 5));
+  let parsed = parse_rle(&write_rle(&whole)).unwrap();
+  assert_eq!(parsed, whole);
+  assert_eq!(reloaded(&parsed, universe.size()), universe.words());
+
+  // plaintext only knows two states, so the dying cells come back alive
+  for pattern in [live, whole] {
+    let parsed = parse_plaintext(&write_plaintext(&pattern)).unwrap();
+    let alive: Vec<u8> = pattern
+      .cells
+      .iter()
+      .map(|&state| u8::from(state != 0))
+      .collect();
+    assert_eq!(parsed.cells, alive);
+    assert_eq!(parsed.rule, Some(rule("B2/S345/C4")));
+    assert_eq!(parsed.generation, Some(7));
+  }
+}
@@ -0,0 +1,105 @@
+use bevy::math::{I64Vec2, UVec2};
+use game_of_life::{CpuUniverse, HashLife, Rule, Topology, UnsupportedRule, pattern::parse_rle};
+
+const GLIDER: &str = "x = 3, y = 3\nbob$2bo$3o!";
+
+fn hashlife_with(rle: &str, x: i64, y: i64) -> HashLife {
+  let pattern = parse_rle(rle).unwrap();
+  let mut life = HashLife::new(&Rule::default()).unwrap();
+  for py in 0..pattern.height {
+    for px in 0..pattern.width {
+      if pattern.get(px, py) == 1 {
+        life.set(I64Vec2::new(x + px as i64, y + py as i64), true);
+      }
+    }
+  }
+  life
+}
+
+#[test]
+fn matches_the_reference_engine() {
+  let size = UVec2::new(256, 256);
+  let mut soup = CpuUniverse::new(UVec2::new(32, 32), &Rule::default(), Topology::DeadEdges);
+  soup.randomize(1234);
+
+  // keep the soup far enough from the edges that nothing reaches them in time
+  let mut cpu = CpuUniverse::new(size, &Rule::default(), Topology::DeadEdges);
+  for y in 0..32 {
+    for x in 0..32 {
+      cpu.set(112 + x, 112 + y, soup.get(x, y));
+    }
+  }
+  let mut life =
+    HashLife::from_words(&Rule::default(), size, cpu.buffer_size_x(), cpu.words()).unwrap();
+  let mut stepped = HashLife::from_words(&Rule::default(), size, 8, cpu.words()).unwrap();
+
+  for generation in 1..=64 {
+    cpu.step();
+    stepped.step_pow2(0);
+    assert_eq!(
+      stepped.to_words(I64Vec2::ZERO, size),
+      cpu.words(),
+      "generation {generation}"
+    );
+  }
+  life.advance(64);
+  assert_eq!(life.generation(), 64);
+  assert_eq!(life.to_words(I64Vec2::ZERO, size), cpu.words());
+}
+
+#[test]
+fn glider_travels_across_huge_jumps() {
+  let mut life = hashlife_with(GLIDER, 0, 0);
+  life.step_pow2(40);
+
+  assert_eq!(life.generation(), 1 << 40);
+  assert_eq!(life.population(), 5);
+  let moved = 1i64 << 38;
+  assert_eq!(
+    life.live_bounds(),
+    Some((I64Vec2::splat(moved), UVec2::new(3, 3)))
+  );
+  assert!(life.get(I64Vec2::new(moved + 1, moved)));
+}
+
+#[test]
+fn oscillators_return_after_any_even_jump() {
+  let mut life = hashlife_with("x = 3, y = 1\n3o!", -1, 0);
+  life.advance(1_000_000_000_002);
+
+  assert_eq!(life.population(), 3);
+  let words = life.to_words(I64Vec2::new(-1, 0), UVec2::new(3, 1));
+  assert_eq!(words, [0b111 << 29]);
+}
+
+#[test]
+fn empty_universes_have_no_bounds() {
+  let mut life = hashlife_with("x = 2, y = 2\no$bo!", 10, 10);
+  life.step_pow2(0);
+  assert_eq!(life.population(), 0);
+  assert_eq!(life.live_bounds(), None);
+}
+
+#[test]
+fn exports_regions_as_patterns() {
+  let life = hashlife_with(GLIDER, -5, 7);
+  let (origin, size) = life.live_bounds().unwrap();
+  let pattern = life.to_pattern(origin, size);
+
+  let mut expected = parse_rle(GLIDER).unwrap();
+  expected.position = Some((-5, 7).into());
+  expected.generation = Some(0);
+  assert_eq!(pattern, expected);
+}
+
+#[test]
+fn rejects_rules_it_cannot_run() {
+  let rule = |rule: &str| HashLife::new(&rule.parse().unwrap()).err();
+  assert_eq!(rule("B3/S23"), None);
+  assert_eq!(rule("B2/S/C3"), Some(UnsupportedRule::Generations));
+  assert_eq!(
+    rule("R5,C0,M1,S34..58,B34..45,NM"),
+    Some(UnsupportedRule::LargerThanLife)
+  );
+  assert_eq!(rule("B013/S23"), Some(UnsupportedRule::BirthOnZero));
+}
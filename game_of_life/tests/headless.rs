@@ -0,0 +1,51 @@
+use std::fs;
+
+use bevy::{
+  MinimalPlugins,
+  app::{App, AppExit},
+  math::UVec2,
+};
+use game_of_life::{
+  GameOfLifePlugin, HeadlessBackend, HeadlessRun, HeadlessStart, pattern::parse_rle,
+};
+
+#[test]
+fn runs_a_pattern_on_the_cpu() {
+  let directory = std::env::temp_dir().join(format!("headless-{}", std::process::id()));
+  fs::create_dir_all(&directory).unwrap();
+  let start = directory.join("glider.rle");
+  fs::write(&start, "x = 3, y = 3, rule = B3/S23\nbob$2bo$3o!").unwrap();
+
+  let run = HeadlessRun {
+    backend: HeadlessBackend::Cpu,
+    start: HeadlessStart::Pattern(start),
+    generations: 8,
+    size: UVec2::new(64, 48),
+    output: directory.join("final.rle"),
+    statistics: directory.join("population.csv"),
+    sample_interval: 4,
+  };
+  let exit = App::new()
+    .add_plugins(MinimalPlugins)
+    .add_plugins(GameOfLifePlugin {
+      headless: Some(run.clone()),
+      ..Default::default()
+    })
+    .run();
+  assert_eq!(exit, AppExit::Success);
+
+  // centered at (30, 22), the glider moves two cells down and right in 8 generations
+  let output = fs::read_to_string(&run.output).unwrap();
+  let pattern = parse_rle(&output).unwrap();
+  assert_eq!(pattern.cells, parse_rle(GLIDER).unwrap().cells);
+  assert_eq!(pattern.position.unwrap().to_array(), [32, 24]);
+  assert_eq!(pattern.generation, Some(8));
+  assert_eq!(
+    fs::read_to_string(&run.statistics).unwrap(),
+    "generation,population\n0,5\n4,5\n8,5\n"
+  );
+
+  fs::remove_dir_all(directory).unwrap();
+}
+
+const GLIDER: &str = "x = 3, y = 3\nbob$2bo$3o!";
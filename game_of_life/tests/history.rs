@@ -0,0 +1,58 @@
+use game_of_life::{Keyframe, Keyframes};
+
+fn generations(keyframes: &Keyframes) -> Vec<u64> {
+  keyframes
+    .iter()
+    .map(|keyframe| keyframe.generation)
+    .collect()
+}
+
+#[test]
+fn keyframes_drop_the_oldest_once_full() {
+  let mut keyframes = Keyframes::new(3);
+  let slots: Vec<u32> = (0..3).filter_map(|g| keyframes.save(g * 4)).collect();
+  assert_eq!(slots, [0, 1, 2]);
+  assert_eq!(generations(&keyframes), [0, 4, 8]);
+  assert_eq!(keyframes.capacity(), 3);
+
+  // the slot of the oldest keyframe is reused
+  assert_eq!(keyframes.save(12), Some(0));
+  assert_eq!(keyframes.len(), 3);
+  assert_eq!(
+    keyframes.oldest(),
+    Some(Keyframe {
+      generation: 4,
+      slot: 1
+    })
+  );
+  assert_eq!(generations(&keyframes), [4, 8, 12]);
+
+  assert_eq!(Keyframes::new(0).save(0), None);
+}
+
+#[test]
+fn rewinding_forgets_the_later_keyframes() {
+  let mut keyframes = Keyframes::new(10);
+  for generation in (0..=20).step_by(5) {
+    keyframes.save(generation);
+  }
+
+  // generation 12 is re-simulated from the keyframe at 10
+  let keyframe = keyframes.rewind_to(12).unwrap();
+  assert_eq!(keyframe.generation, 10);
+  assert_eq!(generations(&keyframes), [0, 5, 10]);
+
+  // the slots of the forgotten keyframes are free again
+  let slot = keyframes.save(15).unwrap();
+  assert!(slot >= 3, "slot {slot}");
+  assert_eq!(generations(&keyframes), [0, 5, 10, 15]);
+
+  // an edit saves over the keyframes at and after its generation
+  keyframes.save(5);
+  assert_eq!(generations(&keyframes), [0, 5]);
+
+  assert_eq!(keyframes.rewind_to(3).map(|k| k.generation), Some(0));
+  keyframes.clear();
+  assert!(keyframes.is_empty());
+  assert_eq!(keyframes.rewind_to(3), None);
+}
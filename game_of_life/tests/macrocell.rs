@@ -0,0 +1,165 @@
+use bevy::math::{I64Vec2, U64Vec2};
+use game_of_life::{
+  HashLife, Rule, UnsupportedRule,
+  pattern::{
+    Macrocell, ParseErrorKind, Pattern, PatternFormat, parse_macrocell, parse_pattern, parse_rle,
+    write_macrocell,
+  },
+};
+
+const GLIDER: &str = "[M2] (golly 4.2)\n#R B3/S23\n#G 100\n.*$..*$***$\n4 1 0 0 0\n";
+
+#[test]
+fn parses_two_state_leaves() {
+  let pattern = parse_macrocell(GLIDER).unwrap();
+  assert_eq!(
+    pattern.cells,
+    parse_rle("x = 3, y = 3\nbob$2bo$3o!").unwrap().cells
+  );
+  assert_eq!((pattern.width, pattern.height), (3, 3));
+  assert_eq!(pattern.rule, Some(Rule::default()));
+  assert_eq!(pattern.generation, Some(100));
+}
+
+#[test]
+fn parses_multi_state_nodes() {
+  let source = "[M2] (golly 4.2)\n#R 345/2/4\n1 0 1 2 3\n1 3 0 0 0\n2 1 2 0 1\n";
+  let pattern = parse_macrocell(source).unwrap();
+  assert_eq!((pattern.width, pattern.height), (4, 4));
+  assert_eq!(pattern.rule.unwrap().states, 4);
+  assert_eq!(
+    pattern.cells,
+    [0, 1, 3, 0, 2, 3, 0, 0, 0, 0, 0, 1, 0, 0, 2, 3]
+  );
+}
+
+#[test]
+fn round_trips_two_state_patterns() {
+  let mut pattern = parse_rle("x = 20, y = 12, rule = B36/S23\n3o$obo9$o$19bo!").unwrap();
+  pattern.generation = Some(7);
+  pattern.comments = vec!["two gliders".to_string()];
+
+  let written = write_macrocell(&pattern);
+  assert!(written.starts_with("[M2]"));
+  assert!(written.contains("#R B36/S23\n#G 7\n#C two gliders\n"));
+  assert_eq!(parse_macrocell(&written).unwrap(), pattern);
+}
+
+#[test]
+fn round_trips_multi_state_patterns() {
+  let pattern = parse_rle("x = 5, y = 2, rule = B2/S345/C4\nA.BC$3.2A!").unwrap();
+  let written = write_macrocell(&pattern);
+  assert!(written.lines().any(|line| line.starts_with("1 ")));
+  assert_eq!(parse_macrocell(&written).unwrap(), pattern);
+}
+
+#[test]
+fn stores_repeated_squares_once() {
+  // a 1024x1024 checkerboard of blocks
+  let mut pattern = Pattern::new(1024, 1024);
+  for y in 0..1024 {
+    for x in 0..1024 {
+      pattern.set(x, y, u8::from((x / 4 + y / 4) % 2 == 0));
+    }
+  }
+
+  let written = write_macrocell(&pattern);
+  // one leaf and one node per level from 4 to 10
+  assert_eq!(
+    written
+      .lines()
+      .filter(|line| !line.starts_with(['[', '#']))
+      .count(),
+    8
+  );
+  assert_eq!(parse_macrocell(&written).unwrap().cells, pattern.cells);
+}
+
+#[test]
+fn measures_huge_patterns_before_expanding_them() {
+  // two cells in opposite corners of a square of 2^41 cells
+  let mut source = "[M2]\n*$\n$$$$$$$.......*$\n4 1 0 0 0\n4 0 0 0 2\n".to_string();
+  // the chains of north west and south east corners, each node refers to the pair below
+  for level in 5..=41 {
+    let (nw, se) = (2 * level - 7, 2 * level - 6);
+    match level {
+      41 => source += &format!("41 {nw} 0 0 {se}\n"),
+      _ => source += &format!("{level} {nw} 0 0 0\n{level} 0 0 0 {se}\n"),
+    }
+  }
+
+  let macrocell = Macrocell::parse(&source).unwrap();
+  assert_eq!(macrocell.side(), 1 << 41);
+  assert_eq!(
+    macrocell.live_bounds(),
+    Some((U64Vec2::ZERO, U64Vec2::splat(1 << 41)))
+  );
+  assert_eq!(macrocell.to_pattern(), None);
+  assert_eq!(
+    parse_macrocell(&source).unwrap_err().kind,
+    ParseErrorKind::OutOfBounds
+  );
+}
+
+#[test]
+fn converts_to_and_from_hashlife() {
+  let macrocell = Macrocell::parse(GLIDER).unwrap();
+  let mut life = HashLife::from_macrocell(&macrocell, &Rule::default()).unwrap();
+  assert_eq!(life.generation(), 100);
+  assert_eq!(life.population(), 5);
+  // the root of 16 cells is centered on the origin
+  assert!(life.get(I64Vec2::new(-7, -8)));
+
+  life.advance(4);
+  let moved = life.to_macrocell();
+  assert_eq!(moved.generation, Some(104));
+  assert_eq!(moved.rule, Some(Rule::default()));
+  let pattern = parse_macrocell(&moved.to_string()).unwrap();
+  assert_eq!(
+    pattern.cells,
+    parse_rle("x = 3, y = 3\nbob$2bo$3o!").unwrap().cells
+  );
+
+  let multi_state = Macrocell::parse("[M2]\n1 2 0 0 1\n").unwrap();
+  assert_eq!(
+    HashLife::from_macrocell(&multi_state, &Rule::default()).err(),
+    Some(UnsupportedRule::Generations)
+  );
+}
+
+#[test]
+fn rejects_malformed_files() {
+  let error = |source: &str| Macrocell::parse(source).unwrap_err();
+  assert_eq!(error("4 0 0 0 0\n").kind, ParseErrorKind::MissingHeader);
+
+  let forward = error("[M2]\n.*$\n4 1 0 0 2\n");
+  assert_eq!(
+    (forward.line, forward.kind),
+    (3, ParseErrorKind::InvalidNode)
+  );
+  let wrong_level = error("[M2]\n.*$\n5 1 0 0 0\n");
+  assert_eq!(wrong_level.kind, ParseErrorKind::InvalidNode);
+  let short = error("[M2]\n.*$\n4 1 0 0\n");
+  assert_eq!(short.kind, ParseErrorKind::InvalidNode);
+
+  let wide = error("[M2]\n.........*$\n");
+  assert_eq!((wide.line, wide.column), (2, 9));
+  assert_eq!(
+    error("[M2]\n.o$\n").kind,
+    ParseErrorKind::UnexpectedCharacter('o')
+  );
+  assert_eq!(error("[M2]\n#C empty\n").kind, ParseErrorKind::InvalidNode);
+}
+
+#[test]
+fn detects_macrocell_files() {
+  assert_eq!(
+    PatternFormat::detect(GLIDER, None),
+    Some(PatternFormat::Macrocell)
+  );
+  assert_eq!(
+    PatternFormat::detect("", Some("MC")),
+    Some(PatternFormat::Macrocell)
+  );
+  assert_eq!(parse_pattern(GLIDER, None).unwrap().width, 3);
+}
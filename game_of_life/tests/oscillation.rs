@@ -0,0 +1,89 @@
+use game_of_life::{Fingerprint, Fingerprints, Settled};
+
+fn observe(fingerprints: &mut Fingerprints, generation: u64, hash: u64) -> Option<Settled> {
+  fingerprints.observe(Fingerprint { generation, hash }, 8, 1)
+}
+
+#[test]
+fn reports_each_way_of_settling_once() {
+  let mut fingerprints = Fingerprints::default();
+  assert_eq!(observe(&mut fingerprints, 0, 10), None);
+  assert_eq!(observe(&mut fingerprints, 1, 11), None);
+  // a blinker flips between two generations
+  assert_eq!(observe(&mut fingerprints, 2, 12), None);
+  assert_eq!(observe(&mut fingerprints, 3, 13), None);
+  assert_eq!(
+    observe(&mut fingerprints, 4, 12),
+    Some(Settled::Oscillating {
+      generation: 4,
+      period: 2
+    })
+  );
+  assert_eq!(observe(&mut fingerprints, 5, 13), None);
+  assert_eq!(observe(&mut fingerprints, 6, 12), None);
+
+  // it gets hit and turns into a block
+  assert_eq!(observe(&mut fingerprints, 7, 20), None);
+  assert_eq!(
+    observe(&mut fingerprints, 8, 20),
+    Some(Settled::StillLife { generation: 8 })
+  );
+  assert_eq!(observe(&mut fingerprints, 9, 20), None);
+
+  assert_eq!(
+    observe(&mut fingerprints, 10, 0),
+    Some(Settled::Extinct { generation: 10 })
+  );
+  assert_eq!(observe(&mut fingerprints, 11, 0), None);
+}
+
+#[test]
+fn forgets_the_window_when_the_generations_do_not_follow() {
+  let mut fingerprints = Fingerprints::default();
+  for generation in 0..4 {
+    observe(&mut fingerprints, generation, 30 + generation % 2);
+  }
+
+  // rewinding, or an edit in the same generation, starts over
+  assert_eq!(observe(&mut fingerprints, 3, 31), None);
+  assert_eq!(observe(&mut fingerprints, 2, 30), None);
+  // so does a jump, even onto a generation that looks like one seen before
+  assert_eq!(observe(&mut fingerprints, 100, 30), None);
+
+  // periods longer than the window are not found
+  let mut fingerprints = Fingerprints::default();
+  for generation in 0..20 {
+    assert_eq!(
+      observe(&mut fingerprints, generation, 40 + generation % 10),
+      None
+    );
+  }
+
+  // with a window of 0 nothing is reported, not even extinction
+  assert_eq!(
+    fingerprints.observe(
+      Fingerprint {
+        generation: 20,
+        hash: 0
+      },
+      0,
+      1
+    ),
+    None
+  );
+}
+
+#[test]
+fn periods_are_found_in_steps_of_the_counting_interval() {
+  let mut fingerprints = Fingerprints::default();
+  let mut observe = |generation, hash| fingerprints.observe(Fingerprint { generation, hash }, 8, 5);
+  assert_eq!(observe(0, 1), None);
+  assert_eq!(observe(5, 2), None);
+  assert_eq!(
+    observe(10, 1),
+    Some(Settled::Oscillating {
+      generation: 10,
+      period: 10
+    })
+  );
+}
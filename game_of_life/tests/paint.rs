@@ -0,0 +1,91 @@
+use bevy::math::{IVec2, UVec2};
+use game_of_life::{Brush, BrushShape, CellEdits, CpuUniverse, Rule, Topology, bresenham_line};
+
+#[test]
+fn lines_join_their_ends_without_gaps() {
+  let ends = [
+    (IVec2::new(0, 0), IVec2::new(5, 0)),
+    (IVec2::new(3, 3), IVec2::new(-2, -2)),
+    (IVec2::new(1, -4), IVec2::new(3, 7)),
+    (IVec2::new(10, 2), IVec2::new(-3, 5)),
+    (IVec2::new(4, 4), IVec2::new(4, 4)),
+  ];
+
+  for (from, to) in ends {
+    let cells = bresenham_line(from, to);
+    assert_eq!(cells.first(), Some(&from));
+    assert_eq!(cells.last(), Some(&to));
+    assert_eq!(cells.len() as i32, (to - from).abs().max_element() + 1);
+    for pair in cells.windows(2) {
+      assert_eq!((pair[1] - pair[0]).abs().max_element(), 1);
+    }
+  }
+
+  assert_eq!(
+    bresenham_line(IVec2::ZERO, IVec2::new(4, 2)),
+    [(0, 0), (1, 0), (2, 1), (3, 1), (4, 2)].map(IVec2::from)
+  );
+}
+
+#[test]
+fn brushes_cover_squares_and_discs() {
+  let brush = |shape, radius| Brush { shape, radius };
+  let center = IVec2::new(10, -3);
+
+  assert_eq!(
+    brush(BrushShape::Square, 0)
+      .cells(center)
+      .collect::<Vec<_>>(),
+    [center]
+  );
+  assert_eq!(
+    brush(BrushShape::Round, 0)
+      .cells(center)
+      .collect::<Vec<_>>(),
+    [center]
+  );
+  assert_eq!(brush(BrushShape::Square, 1).cells(center).count(), 9);
+
+  let disc: Vec<IVec2> = brush(BrushShape::Round, 2).cells(center).collect();
+  assert_eq!(disc.len(), 21);
+  assert!(disc.contains(&(center + IVec2::new(2, 1))));
+  assert!(!disc.contains(&(center + IVec2::new(2, 2))));
+}
+
+#[test]
+fn edits_change_only_the_painted_cells() {
+  for rule in ["B3/S23", "B2/S345/C4"] {
+    let rule: Rule = rule.parse().unwrap();
+    let size = UVec2::new(70, 20);
+    let mut expected = CpuUniverse::new(size, &rule, Topology::Torus);
+    expected.randomize(7);
+    let mut painted = expected.clone();
+
+    let mut edits = CellEdits::new(expected.buffer_size_x(), expected.bits_per_cell());
+    let strokes = [
+      (IVec2::new(0, 0), IVec2::new(69, 19), 1),
+      (IVec2::new(40, 2), IVec2::new(30, 15), 0),
+      (IVec2::new(31, 0), IVec2::new(33, 0), 1),
+    ];
+    for (from, to, state) in strokes {
+      for cell in bresenham_line(from, to) {
+        let cell = cell.as_uvec2();
+        edits.set(cell, state);
+        expected.set(cell.x, cell.y, u32::from(state));
+      }
+    }
+
+    // the strokes cross, so some words are painted and erased in the same batch
+    let count = edits.len();
+    assert!(count < 70 + 14 + 3);
+    let first = edits.drain(10);
+    let rest = edits.drain(usize::MAX);
+    assert_eq!(first.len() + rest.len(), count);
+    assert!(first.windows(2).all(|pair| pair[0].index < pair[1].index));
+    assert!(edits.is_empty());
+
+    painted.apply_edits(&first);
+    painted.apply_edits(&rest);
+    assert_eq!(painted.words(), expected.words(), "rule {rule}");
+  }
+}
@@ -0,0 +1,259 @@
+use bevy::math::IVec2;
+use game_of_life::{
+  Rule, RuleParseError,
+  pattern::{
+    ParseError, ParseErrorKind, parse_life_105, parse_life_106, parse_plaintext, parse_rle,
+    write_plaintext, write_rle,
+  },
+};
+
+fn rule(rulestring: &str) -> Rule {
+  rulestring.parse().unwrap()
+}
+
+fn error_at(error: ParseError) -> (usize, usize, ParseErrorKind) {
+  (error.line, error.column, error.kind)
+}
+
+#[test]
+fn reads_the_header_and_comments() {
+  let pattern = parse_rle("#N Glider\n#C slow\nx = 3, y = 3, rule = B36/S23\nbob$2bo$3o!").unwrap();
+  assert_eq!((pattern.width, pattern.height), (3, 3));
+  assert_eq!(pattern.cells, [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+  assert_eq!(pattern.rule, Some(rule("B36/S23")));
+  assert_eq!(pattern.comments, ["Glider", "slow"]);
+  assert_eq!((pattern.position, pattern.generation), (None, None));
+
+  // the old `#r` line, overridden by a rule in the header
+  assert_eq!(
+    parse_rle("#r 23/36\nx = 1, y = 1\no!").unwrap().rule,
+    Some(rule("B36/S23"))
+  );
+  assert_eq!(
+    parse_rle("#r 23/36\nx = 1, y = 1, rule = B3/S23\no!")
+      .unwrap()
+      .rule,
+    Some(Rule::default())
+  );
+  // Golly's topology suffix and Larger than Life rules with their commas
+  assert_eq!(
+    parse_rle("x = 1, y = 1, rule = B3/S23:T100,100\no!")
+      .unwrap()
+      .rule,
+    Some(Rule::default())
+  );
+  assert_eq!(
+    parse_rle("x = 1, y = 1, rule = R5,C0,M1,S34..58,B34..45,NM\no!")
+      .unwrap()
+      .rule,
+    Some(rule("R5,C0,M1,S34..58,B34..45,NM"))
+  );
+}
+
+#[test]
+fn reads_the_extended_header() {
+  let pattern = parse_rle("#CXRLE Pos=-5,7 Gen=42\nx = 2, y = 1\n2o!").unwrap();
+  assert_eq!(pattern.position, Some(IVec2::new(-5, 7)));
+  assert_eq!(pattern.generation, Some(42));
+  assert!(pattern.comments.is_empty());
+
+  // malformed attributes are skipped
+  let pattern = parse_rle("#CXRLE Pos=1 Gen=x Foo=2\nx = 2, y = 1\n2o!").unwrap();
+  assert_eq!((pattern.position, pattern.generation), (None, None));
+
+  let written = write_rle(&parse_rle("#CXRLE Pos=-5,7 Gen=42\nx = 2, y = 1\n2o!").unwrap());
+  assert!(written.starts_with("#CXRLE Pos=-5,7 Gen=42\nx = 2, y = 1\n"));
+}
+
+#[test]
+fn reads_multi_state_cells() {
+  let pattern = parse_rle("x = 5, y = 2, rule = B2/S/C256\n.ApBX$yO3.A!").unwrap();
+  assert_eq!(pattern.cells, [0, 1, 26, 24, 0, 255, 0, 0, 0, 1]);
+  assert_eq!(parse_rle(&write_rle(&pattern)).unwrap(), pattern);
+
+  // `yP` would be state 256
+  assert_eq!(
+    error_at(parse_rle("x = 1, y = 1\nyP!").unwrap_err()),
+    (2, 2, ParseErrorKind::InvalidState)
+  );
+  // a prefix has to be followed by a state letter
+  assert_eq!(
+    error_at(parse_rle("x = 1, y = 1\npo!").unwrap_err()),
+    (2, 2, ParseErrorKind::UnexpectedCharacter('o'))
+  );
+}
+
+#[test]
+fn reports_where_patterns_are_malformed() {
+  let error = |source| error_at(parse_rle(source).unwrap_err());
+  assert_eq!(error(""), (1, 1, ParseErrorKind::MissingHeader));
+  assert_eq!(
+    error("#C only\n\n#C comments"),
+    (3, 1, ParseErrorKind::MissingHeader)
+  );
+  assert_eq!(error("x = 3\no!"), (1, 1, ParseErrorKind::MissingHeader));
+  assert_eq!(
+    error("x = 3, z = 2\no!"),
+    (1, 8, ParseErrorKind::InvalidHeader)
+  );
+  assert_eq!(
+    error("x = a, y = 1\no!"),
+    (1, 1, ParseErrorKind::InvalidHeader)
+  );
+  assert_eq!(
+    error("x = 1, y = 1, rule = B9/S23\no!"),
+    (
+      1,
+      22,
+      ParseErrorKind::InvalidRule(RuleParseError::CountOutOfRange {
+        position: 1,
+        count: 9
+      })
+    )
+  );
+  assert_eq!(
+    error("x = 2, y = 1\n\noz!"),
+    (3, 2, ParseErrorKind::UnexpectedCharacter('z'))
+  );
+  assert_eq!(
+    error("x = 2, y = 1\n3o!"),
+    (2, 2, ParseErrorKind::OutOfBounds)
+  );
+  assert_eq!(
+    error("x = 1, y = 1\n$o!"),
+    (2, 2, ParseErrorKind::OutOfBounds)
+  );
+  assert_eq!(
+    error("x = 100000, y = 100000\n!"),
+    (1, 1, ParseErrorKind::OutOfBounds)
+  );
+  assert_eq!(
+    error("x = 2, y = 1\no\n2b"),
+    (3, 2, ParseErrorKind::MissingTerminator)
+  );
+  assert_eq!(
+    error("x = 2, y = 1"),
+    (1, 13, ParseErrorKind::MissingTerminator)
+  );
+}
+
+#[test]
+fn rejects_runs_that_overflow_the_position() {
+  let error = parse_rle("x = 4, y = 4\n4294967295bo!").unwrap_err();
+  assert_eq!(
+    (error.line, error.column, error.kind),
+    (2, 12, ParseErrorKind::OutOfBounds)
+  );
+
+  let error = parse_rle("x = 4, y = 4\n4294967295$4294967295$o!").unwrap_err();
+  assert_eq!(
+    (error.line, error.column, error.kind),
+    (2, 22, ParseErrorKind::OutOfBounds)
+  );
+}
+
+#[test]
+fn reads_plaintext_patterns() {
+  let pattern = parse_plaintext("!Name: Glider\n!\n\n.O\n..O\nOOO\n\n").unwrap();
+  assert_eq!((pattern.width, pattern.height), (3, 3));
+  assert_eq!(pattern.cells, [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+  assert_eq!(pattern.comments, ["Name: Glider", ""]);
+  assert_eq!(pattern.rule, None);
+
+  // short rows and empty lines inside the pattern are dead cells
+  let pattern = parse_plaintext("!Rule: B36/S23\n!Generation: 7\n*\n\n.*.").unwrap();
+  assert_eq!((pattern.width, pattern.height), (3, 3));
+  assert_eq!(pattern.cells, [1, 0, 0, 0, 0, 0, 0, 1, 0]);
+  assert_eq!(pattern.rule, Some(rule("B36/S23")));
+  assert_eq!(pattern.generation, Some(7));
+  assert_eq!(
+    parse_plaintext(&write_plaintext(&pattern)).unwrap(),
+    pattern
+  );
+
+  let error = |source| error_at(parse_plaintext(source).unwrap_err());
+  assert_eq!(
+    error("!Glider\n.O\n.x"),
+    (3, 2, ParseErrorKind::UnexpectedCharacter('x'))
+  );
+  assert_eq!(
+    error("!Rule: B9/S23\nO"),
+    (
+      1,
+      8,
+      ParseErrorKind::InvalidRule(RuleParseError::CountOutOfRange {
+        position: 1,
+        count: 9
+      })
+    )
+  );
+}
+
+#[test]
+fn places_life_105_blocks_at_their_offsets() {
+  let source = "#Life 1.05\n#D two blocks\n#R 23/3\n#P -1 -1\n.*\n*\n#P 2 0\n***\n";
+  let pattern = parse_life_105(source).unwrap();
+  // the cells span from (-1, -1) to (4, 0)
+  assert_eq!((pattern.width, pattern.height), (6, 2));
+  assert_eq!(pattern.cells, [0, 1, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1]);
+  assert_eq!(pattern.rule, Some(Rule::default()));
+  assert_eq!(pattern.comments, ["two blocks"]);
+
+  assert_eq!(
+    parse_life_105("#Life 1.05\n#N\n*").unwrap().rule,
+    Some(Rule::default())
+  );
+
+  let error = |source| error_at(parse_life_105(source).unwrap_err());
+  assert_eq!(error("#P 0 0\n*"), (1, 1, ParseErrorKind::MissingHeader));
+  assert_eq!(
+    error("#Life 1.05\n.*\n.o"),
+    (3, 2, ParseErrorKind::UnexpectedCharacter('o'))
+  );
+  assert_eq!(
+    error("#Life 1.05\n#P 1\n*"),
+    (2, 2, ParseErrorKind::InvalidCoordinates)
+  );
+  assert_eq!(
+    error("#Life 1.05\n#R 9/3"),
+    (
+      2,
+      4,
+      ParseErrorKind::InvalidRule(RuleParseError::CountOutOfRange {
+        position: 0,
+        count: 9
+      })
+    )
+  );
+}
+
+#[test]
+fn reads_negative_life_106_coordinates() {
+  let pattern = parse_life_106("#Life 1.06\n#comment\n-2 -3\n1 0\n\n0 -3\n").unwrap();
+  // the cells span from (-2, -3) to (1, 0)
+  assert_eq!((pattern.width, pattern.height), (4, 4));
+  assert_eq!(pattern.get(0, 0), 1);
+  assert_eq!(pattern.get(2, 0), 1);
+  assert_eq!(pattern.get(3, 3), 1);
+  assert_eq!(pattern.cells.iter().filter(|&&state| state == 1).count(), 3);
+  assert_eq!(pattern.comments, ["comment"]);
+
+  let empty = parse_life_106("#Life 1.06\n").unwrap();
+  assert_eq!((empty.width, empty.height), (0, 0));
+
+  let error = |source| error_at(parse_life_106(source).unwrap_err());
+  assert_eq!(error("0 0\n"), (1, 1, ParseErrorKind::MissingHeader));
+  assert_eq!(
+    error("#Life 1.06\n0 0\n  1 x\n"),
+    (3, 3, ParseErrorKind::InvalidCoordinates)
+  );
+  assert_eq!(
+    error("#Life 1.06\n1 2 3\n"),
+    (2, 1, ParseErrorKind::InvalidCoordinates)
+  );
+  // the bounding box of two cells far apart is too large to allocate
+  assert_eq!(
+    error("#Life 1.06\n0 0\n100000 100000\n"),
+    (3, 1, ParseErrorKind::OutOfBounds)
+  );
+}
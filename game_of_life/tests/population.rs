@@ -0,0 +1,19 @@
+use game_of_life::PopulationCounting;
+
+#[test]
+fn counts_every_interval_generations_of_a_batch() {
+  let every = PopulationCounting { interval: 1 };
+  assert_eq!(
+    every.samples(10, 3).collect::<Vec<_>>(),
+    [(1, 11), (2, 12), (3, 13)]
+  );
+  assert_eq!(every.samples(10, 0).count(), 0);
+
+  // the generations are counted wherever they fall in the batch
+  let sparse = PopulationCounting { interval: 4 };
+  assert_eq!(sparse.samples(6, 8).collect::<Vec<_>>(), [(2, 8), (6, 12)]);
+  assert_eq!(sparse.samples(0, 3).count(), 0);
+
+  let off = PopulationCounting { interval: 0 };
+  assert_eq!(off.samples(0, 100).count(), 0);
+}
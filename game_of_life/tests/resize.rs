@@ -0,0 +1,65 @@
+use bevy::math::{IVec2, UVec2};
+use game_of_life::{Anchor, UniverseLimits, UniverseSizeError, check_universe_size};
+
+#[test]
+fn anchors_keep_their_point_in_place() {
+  let old = UVec2::new(100, 50);
+  let new = UVec2::new(120, 80);
+  assert_eq!(Anchor::Center.offset(old, new), IVec2::new(10, 15));
+  assert_eq!(Anchor::TopLeft.offset(old, new), IVec2::ZERO);
+  assert_eq!(Anchor::TopRight.offset(old, new), IVec2::new(20, 0));
+  assert_eq!(Anchor::BottomLeft.offset(old, new), IVec2::new(0, 30));
+  assert_eq!(Anchor::BottomRight.offset(old, new), IVec2::new(20, 30));
+
+  // shrinking moves the cells up and to the left, out of the universe
+  assert_eq!(Anchor::Center.offset(new, old), IVec2::new(-10, -15));
+  assert_eq!(Anchor::BottomRight.offset(new, old), IVec2::new(-20, -30));
+}
+
+fn limits(max_bytes: u64, max_workgroups: u32) -> UniverseLimits {
+  UniverseLimits {
+    max_bytes,
+    max_workgroups,
+  }
+}
+
+#[test]
+fn sizes_are_checked_against_the_binding_limit() {
+  // 10000 cells take 313 words per row with one bit per cell
+  let bytes = 313 * 10000 * 4;
+  let size = UVec2::splat(10000);
+  assert_eq!(check_universe_size(size, 1, limits(bytes, 65535)), Ok(()));
+  assert_eq!(
+    check_universe_size(size, 1, limits(bytes - 1, 65535)),
+    Err(UniverseSizeError::TooLarge {
+      size,
+      bytes,
+      max_bytes: bytes - 1,
+    })
+  );
+  // Generations rules take eight times as much
+  assert!(check_universe_size(size, 8, limits(bytes, 65535)).is_err());
+  assert_eq!(
+    check_universe_size(UVec2::new(0, 10), 1, UniverseLimits::NONE),
+    Err(UniverseSizeError::Empty)
+  );
+}
+
+#[test]
+fn sizes_are_checked_against_the_workgroup_limit() {
+  // 32768 cells take 1024 words per row, so every row is one workgroup
+  let size = UVec2::new(32768, 65535);
+  assert_eq!(
+    check_universe_size(size, 1, limits(u64::MAX, 65535)),
+    Ok(())
+  );
+  assert_eq!(
+    check_universe_size(UVec2::new(32768, 65536), 1, limits(u64::MAX, 65535)),
+    Err(UniverseSizeError::TooManyWorkgroups {
+      size: UVec2::new(32768, 65536),
+      workgroups: 65536,
+      max_workgroups: 65535,
+    })
+  );
+  assert_eq!(check_universe_size(size, 1, UniverseLimits::NONE), Ok(()));
+}
@@ -0,0 +1,238 @@
+use bevy::math::UVec2;
+use game_of_life::{
+  CpuUniverse, LargerThanLife, Neighborhood, Rule, RuleParseError, Topology, pattern::parse_rle,
+};
+
+fn rule(rulestring: &str) -> Rule {
+  rulestring.parse().unwrap()
+}
+
+fn error(rulestring: &str) -> (RuleParseError, String) {
+  let error = rulestring.parse::<Rule>().unwrap_err();
+  let message = error.to_string();
+  (error, message)
+}
+
+#[test]
+fn parses_both_notations() {
+  let highlife = rule("B36/S23");
+  assert_eq!(highlife.birth, (1 << 3) | (1 << 6));
+  assert_eq!(highlife.survival, (1 << 2) | (1 << 3));
+  assert_eq!(highlife.states, 2);
+  assert_eq!(highlife.bits_per_cell(), 1);
+
+  // survival comes first without prefixes
+  assert_eq!(rule("23/36"), highlife);
+  assert_eq!(rule("s23/b36"), highlife);
+  assert_eq!(rule(" B3/S23 "), Rule::default());
+  assert_eq!(rule("B/S0").birth, 0);
+  assert_eq!(rule("B/S0").survival, 1);
+
+  assert_eq!(highlife.to_string(), "B36/S23");
+  assert_eq!(rule(&highlife.to_string()), highlife);
+}
+
+#[test]
+fn reports_where_rules_are_malformed() {
+  assert_eq!(
+    error(""),
+    (RuleParseError::Empty, "rule is empty".to_string())
+  );
+  assert_eq!(
+    error("B3"),
+    (
+      RuleParseError::PartCount(1),
+      "expected 2 or 3 '/'-separated parts, found 1".to_string()
+    )
+  );
+  assert_eq!(error("1/2/3/4").0, RuleParseError::PartCount(4));
+  assert_eq!(
+    error("B3/23"),
+    (
+      RuleParseError::MismatchedPrefixes,
+      "expected one 'B', one 'S' and an optional 'C' part, or only unprefixed parts".to_string()
+    )
+  );
+  assert_eq!(error("B3/B6").0, RuleParseError::MismatchedPrefixes);
+  assert_eq!(
+    error("B3x/S23"),
+    (
+      RuleParseError::InvalidCharacter {
+        position: 2,
+        character: 'x'
+      },
+      "unexpected character 'x' at position 2".to_string()
+    )
+  );
+  assert_eq!(
+    error("B9/S23"),
+    (
+      RuleParseError::CountOutOfRange {
+        position: 1,
+        count: 9
+      },
+      "neighbor count 9 at position 1 exceeds 8".to_string()
+    )
+  );
+  assert_eq!(
+    error("B3/S233"),
+    (
+      RuleParseError::DuplicateCount {
+        position: 6,
+        count: 3
+      },
+      "neighbor count 3 at position 6 is repeated".to_string()
+    )
+  );
+}
+
+#[test]
+fn the_masks_decide_births_and_survivals() {
+  // six live cells around the middle one
+  let pattern = parse_rle("x = 3, y = 3\n3o$o$2o!").unwrap();
+  let step = |rule: &Rule| {
+    let mut universe = CpuUniverse::new(UVec2::splat(8), rule, Topology::DeadEdges);
+    universe.load(&pattern, UVec2::splat(2));
+    universe.step();
+    universe
+  };
+
+  assert_eq!(step(&rule("B36/S23")).get(3, 3), 1);
+  assert_eq!(step(&Rule::default()).get(3, 3), 0);
+  // the corner with two neighbors only survives with 2 in the survival mask
+  assert_eq!(step(&rule("B36/S23")).get(2, 4), 1);
+  assert_eq!(step(&rule("B36/S3")).get(2, 4), 0);
+}
+
+#[test]
+fn parses_generations_rules() {
+  let brians_brain = rule("B2/S/C3");
+  assert_eq!(brians_brain.birth, 1 << 2);
+  assert_eq!(brians_brain.survival, 0);
+  assert_eq!(brians_brain.states, 3);
+  assert_eq!(brians_brain.bits_per_cell(), 8);
+  assert_eq!(rule("/2/3"), brians_brain);
+  assert_eq!(rule("c3/b2/s"), brians_brain);
+  assert_eq!(rule("345/2/4"), rule("B2/S345/C4"));
+  assert_eq!(rule("B2/S/C256").states, 256);
+  // two states are a Life-like rule
+  assert_eq!(rule("B3/S23/C2"), Rule::default());
+
+  for rulestring in ["B2/S/C3", "B2/S345/C4", "B2/S/C256"] {
+    assert_eq!(rule(rulestring).to_string(), rulestring);
+    assert_eq!(rule(&rule(rulestring).to_string()), rule(rulestring));
+  }
+}
+
+#[test]
+fn rejects_invalid_state_counts() {
+  assert_eq!(
+    error("B2/S/C1"),
+    (
+      RuleParseError::InvalidStateCount { position: 6 },
+      "expected a state count between 2 and 256 at position 6".to_string()
+    )
+  );
+  for rulestring in ["B2/S/C257", "B2/S/C", "B2/S/Cx", "/2/0"] {
+    assert!(
+      matches!(
+        error(rulestring).0,
+        RuleParseError::InvalidStateCount { .. }
+      ),
+      "{rulestring}"
+    );
+  }
+  assert_eq!(error("B2/S/C3/C4").0, RuleParseError::PartCount(4));
+  assert_eq!(error("B2/C3/C4").0, RuleParseError::MismatchedPrefixes);
+}
+
+#[test]
+fn parses_larger_than_life_rules() {
+  let bosco = rule("R5,C0,M1,S34..58,B34..45,NM");
+  assert_eq!(
+    bosco.larger_than_life,
+    Some(LargerThanLife {
+      range: 5,
+      neighborhood: Neighborhood::Moore,
+      include_center: true,
+      birth: (34, 45),
+      survival: (34, 58),
+    })
+  );
+  assert_eq!((bosco.birth, bosco.survival, bosco.states), (0, 0, 2));
+  assert_eq!(bosco.larger_than_life.unwrap().cell_count(), 121);
+  // parameters come in any order and the neighborhood defaults to Moore
+  assert_eq!(rule("r5,b34..45,s34..58,m1,c0"), bosco);
+  assert_eq!(bosco.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+
+  let von_neumann = rule("R2,C3,M0,S1..3,B2,NN");
+  let ltl = von_neumann.larger_than_life.unwrap();
+  assert_eq!(ltl.neighborhood, Neighborhood::VonNeumann);
+  assert_eq!(ltl.cell_count(), 13);
+  assert_eq!(ltl.birth, (2, 2));
+  assert_eq!(von_neumann.states, 3);
+  assert_eq!(von_neumann.to_string(), "R2,C3,M0,S1..3,B2..2,NN");
+  assert_eq!(rule(&von_neumann.to_string()), von_neumann);
+
+  assert_eq!(rule("R10,C0,M0,S1,B1").larger_than_life.unwrap().range, 10);
+}
+
+#[test]
+fn rejects_malformed_larger_than_life_rules() {
+  assert_eq!(
+    error("R11,C0,M0,S1,B1"),
+    (
+      RuleParseError::RangeOutOfBounds {
+        position: 0,
+        range: 11
+      },
+      "range 11 at position 0 is not between 1 and 10".to_string()
+    )
+  );
+  assert!(matches!(
+    error("R0,C0,M0,S1,B1").0,
+    RuleParseError::RangeOutOfBounds { range: 0, .. }
+  ));
+  assert_eq!(
+    error("R5,C1,M1,S34..58,B34..45").0,
+    RuleParseError::InvalidStateCount { position: 3 }
+  );
+  assert_eq!(
+    error("R5,C257,M1,S34..58,B34..45").0,
+    RuleParseError::InvalidStateCount { position: 3 }
+  );
+  assert_eq!(
+    error("R5,C0,S34..58,B34..45"),
+    (
+      RuleParseError::MissingParameter('M'),
+      "missing parameter 'M'".to_string()
+    )
+  );
+  assert_eq!(
+    error("R5,C0,M1,S34..58").0,
+    RuleParseError::MissingParameter('B')
+  );
+  assert_eq!(
+    error("R5,C0,M2,S34..58,B34..45"),
+    (
+      RuleParseError::InvalidParameter { position: 6 },
+      "invalid parameter at position 6".to_string()
+    )
+  );
+  assert_eq!(
+    error("R5,R6,C0,M1,S34..58,B34..45").0,
+    RuleParseError::InvalidParameter { position: 3 }
+  );
+  assert_eq!(
+    error("R1,C0,M0,S3..2,B3"),
+    (
+      RuleParseError::InvalidInterval { position: 9 },
+      "count interval at position 9 is reversed or exceeds the neighborhood".to_string()
+    )
+  );
+  // the Moore neighborhood of range 1 has 9 cells
+  assert_eq!(
+    error("R1,C0,M0,S2..3,B10").0,
+    RuleParseError::InvalidInterval { position: 15 }
+  );
+}
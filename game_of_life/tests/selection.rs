@@ -0,0 +1,150 @@
+use bevy::math::{IVec2, URect, UVec2};
+use game_of_life::{CpuUniverse, RegionEdit, Rule, Topology, pattern::parse_rle};
+
+const GLIDER: &str = "x = 3, y = 3\nbob$2bo$3o!";
+
+fn soup(size: UVec2, rule: &str) -> CpuUniverse {
+  let mut universe = CpuUniverse::new(size, &rule.parse().unwrap(), Topology::Torus);
+  universe.randomize(3);
+  universe
+}
+
+fn contains(rect: URect, x: u32, y: u32) -> bool {
+  x >= rect.min.x && x < rect.max.x && y >= rect.min.y && y < rect.max.y
+}
+
+/// Checks every cell against `expected`, given the old universe and whether the cell is in
+/// `rect`.
+fn assert_cells(
+  before: &CpuUniverse,
+  after: &CpuUniverse,
+  rect: URect,
+  expected: impl Fn(u32, bool) -> u32,
+) {
+  let size = before.size();
+  for y in 0..size.y {
+    for x in 0..size.x {
+      let state = expected(before.get(x, y), contains(rect, x, y));
+      assert_eq!(after.get(x, y), state, "cell ({x}, {y})");
+    }
+  }
+}
+
+#[test]
+fn clears_inside_or_outside_the_selection() {
+  for rule in ["B3/S23", "B2/S345/C4"] {
+    let before = soup(UVec2::new(70, 20), rule);
+    let rect = URect::new(5, 3, 41, 17);
+
+    let mut cleared = before.clone();
+    assert_eq!(cleared.edit_region(&RegionEdit::Clear(rect), None), None);
+    assert_cells(
+      &before,
+      &cleared,
+      rect,
+      |state, inside| if inside { 0 } else { state },
+    );
+
+    let mut cleared = before.clone();
+    cleared.edit_region(&RegionEdit::ClearOutside(rect), None);
+    assert_cells(
+      &before,
+      &cleared,
+      rect,
+      |state, inside| if inside { state } else { 0 },
+    );
+  }
+}
+
+#[test]
+fn fills_the_selection_with_the_given_density() {
+  let before = soup(UVec2::new(200, 100), "B3/S23");
+  let rect = URect::new(10, 10, 110, 90);
+  let fill = |density, seed| {
+    let mut universe = before.clone();
+    universe.edit_region(
+      &RegionEdit::Fill {
+        rect,
+        density,
+        seed,
+      },
+      None,
+    );
+    universe
+  };
+
+  assert_cells(
+    &before,
+    &fill(0.0, 1),
+    rect,
+    |state, inside| if inside { 0 } else { state },
+  );
+  assert_cells(
+    &before,
+    &fill(1.0, 1),
+    rect,
+    |state, inside| if inside { 1 } else { state },
+  );
+
+  let filled = fill(0.3, 1);
+  let alive = (rect.min.y..rect.max.y)
+    .flat_map(|y| (rect.min.x..rect.max.x).map(move |x| (x, y)))
+    .filter(|&(x, y)| filled.get(x, y) == 1)
+    .count() as f32;
+  let density = alive / (rect.width() * rect.height()) as f32;
+  assert!((density - 0.3).abs() < 0.02, "density {density}");
+  assert_eq!(filled.words(), fill(0.3, 1).words());
+  assert_ne!(filled.words(), fill(0.3, 2).words());
+
+  // dying states are never filled in
+  let mut generations = soup(UVec2::new(40, 40), "B2/S345/C4");
+  generations.edit_region(
+    &RegionEdit::Fill {
+      rect,
+      density: 0.5,
+      seed: 1,
+    },
+    None,
+  );
+  assert!((10..40).all(|y| (10..40).all(|x| generations.get(x, y) <= 1)));
+}
+
+#[test]
+fn cut_and_paste_move_the_cells() {
+  let rule = Rule::default();
+  let mut universe = CpuUniverse::new(UVec2::new(64, 32), &rule, Topology::DeadEdges);
+  let glider = parse_rle(GLIDER).unwrap();
+  universe.load(&glider, UVec2::new(30, 4));
+  let before = universe.clone();
+
+  let rect = URect::new(29, 3, 34, 8);
+  let copied = universe.edit_region(&RegionEdit::Copy(rect), None).unwrap();
+  assert_eq!(universe.words(), before.words());
+  assert_eq!((copied.width, copied.height), (5, 5));
+  assert_eq!(copied.position, Some(IVec2::new(29, 3)));
+  assert_eq!(copied.get(2, 1), 1);
+
+  let cut = universe.edit_region(&RegionEdit::Cut(rect), None).unwrap();
+  assert_eq!(cut, copied);
+  assert!(universe.words().iter().all(|&word| word == 0));
+
+  // pasting replaces the cells under the clipboard and clips it to the universe
+  universe.edit_region(&RegionEdit::Paste(IVec2::new(60, -1)), Some(&cut));
+  universe.edit_region(&RegionEdit::Paste(IVec2::new(2, 20)), Some(&cut));
+  let mut expected = CpuUniverse::new(universe.size(), &rule, Topology::DeadEdges);
+  expected.load(&glider, UVec2::new(3, 21));
+  for (x, y) in [(62, 0), (63, 1), (61, 2), (62, 2), (63, 2)] {
+    expected.set(x, y, 1);
+  }
+  assert_eq!(universe.words(), expected.words());
+
+  // copies are clipped to the universe as well
+  let corner = universe
+    .edit_region(&RegionEdit::Copy(URect::new(60, 30, 80, 40)), None)
+    .unwrap();
+  assert_eq!((corner.width, corner.height), (4, 2));
+  assert_eq!(
+    universe.edit_region(&RegionEdit::Copy(URect::new(70, 0, 80, 5)), None),
+    None
+  );
+}
@@ -0,0 +1,139 @@
+use bevy::math::{IVec2, UVec2};
+use game_of_life::{
+  CellEdits, CpuUniverse, Rule, StampMode, Topology,
+  pattern::{Pattern, parse_rle},
+  stamp_cells,
+};
+
+const GLIDER: &str = "x = 3, y = 3\nbob$2bo$3o!";
+
+fn rle(source: &str) -> Pattern {
+  parse_rle(source).unwrap()
+}
+
+#[test]
+fn patterns_rotate_and_flip() {
+  let glider = rle(GLIDER);
+  let clockwise = glider.rotated_clockwise();
+  assert_eq!(clockwise.cells, rle("x = 3, y = 3\no$obo$2o!").cells);
+  assert_eq!(
+    glider.rotated_counterclockwise(),
+    clockwise.rotated_clockwise().rotated_clockwise()
+  );
+  assert_eq!(clockwise.rotated_counterclockwise(), glider);
+  assert_eq!(
+    glider.flipped_horizontally().cells,
+    rle("x = 3, y = 3\nbo$o$3o!").cells
+  );
+  assert_eq!(
+    glider.flipped_vertically().cells,
+    rle("x = 3, y = 3\n3o$2bo$bo!").cells
+  );
+
+  // non-square patterns swap their sides
+  let mut wide = rle("x = 4, y = 2\no2bo$2bo!");
+  wide.position = Some(IVec2::new(5, 5));
+  let turned = wide.rotated_clockwise();
+  assert_eq!((turned.width, turned.height), (2, 4));
+  assert_eq!(turned.cells, rle("x = 2, y = 4\nbo2$o$bo!").cells);
+  assert_eq!(turned.position, None);
+  assert_eq!(
+    wide.flipped_horizontally().flipped_horizontally().cells,
+    wide.cells
+  );
+}
+
+fn stamped(
+  universe: &CpuUniverse,
+  pattern: &Pattern,
+  offset: IVec2,
+  mode: StampMode,
+) -> CpuUniverse {
+  let mut edits = CellEdits::new(universe.buffer_size_x(), universe.bits_per_cell());
+  for paint in stamp_cells(
+    pattern,
+    offset,
+    mode,
+    universe.size(),
+    universe.bits_per_cell(),
+  ) {
+    for cell in paint.cells {
+      edits.set(cell, paint.state);
+    }
+  }
+  let mut universe = universe.clone();
+  universe.apply_edits(&edits.drain(usize::MAX));
+  universe
+}
+
+#[test]
+fn stamps_add_to_or_replace_the_cells_under_them() {
+  let mut universe = CpuUniverse::new(UVec2::new(40, 10), &Rule::default(), Topology::DeadEdges);
+  universe.load(&rle("x = 3, y = 3\n3o$3o$3o!"), UVec2::new(30, 2));
+  let glider = rle(GLIDER);
+
+  let added = stamped(&universe, &glider, IVec2::new(30, 2), StampMode::Or);
+  assert_eq!(added.words(), universe.words());
+
+  let replaced = stamped(&universe, &glider, IVec2::new(30, 2), StampMode::Replace);
+  let mut expected = universe.clone();
+  expected.load(&glider, UVec2::new(30, 2));
+  assert_eq!(replaced.words(), expected.words());
+
+  let mut empty = CpuUniverse::new(universe.size(), &Rule::default(), Topology::DeadEdges);
+  let added = stamped(&empty, &glider, IVec2::new(1, 1), StampMode::Or);
+  empty.load(&glider, UVec2::new(1, 1));
+  assert_eq!(added.words(), empty.words());
+}
+
+#[test]
+fn stamps_are_clipped_to_the_universe() {
+  let size = UVec2::new(8, 8);
+  let block = rle("x = 3, y = 3\n3o$3o$3o!");
+  let cells = |offset, mode| -> Vec<UVec2> {
+    stamp_cells(&block, offset, mode, size, 1)
+      .into_iter()
+      .flat_map(|paint| paint.cells)
+      .collect()
+  };
+
+  assert_eq!(cells(IVec2::new(-2, -2), StampMode::Or), [UVec2::new(0, 0)]);
+  assert_eq!(cells(IVec2::new(6, 7), StampMode::Replace).len(), 2);
+  assert!(cells(IVec2::new(8, 0), StampMode::Or).is_empty());
+}
+
+#[test]
+fn stamps_keep_the_states_of_multi_state_patterns() {
+  let rule: Rule = "B2/S345/C4".parse().unwrap();
+  let pattern = rle("x = 3, y = 1, rule = B2/S345/C4\nABC!");
+
+  let paints = stamp_cells(
+    &pattern,
+    IVec2::ZERO,
+    StampMode::Replace,
+    UVec2::new(3, 1),
+    8,
+  );
+  let states: Vec<u8> = paints.iter().map(|paint| paint.state).collect();
+  assert_eq!(states, [1, 2, 3]);
+
+  // the 1-bit layout has no dying states, like when the pattern is loaded
+  let paints = stamp_cells(
+    &pattern,
+    IVec2::ZERO,
+    StampMode::Replace,
+    UVec2::new(3, 1),
+    1,
+  );
+  let states: Vec<(u8, usize)> = paints
+    .iter()
+    .map(|paint| (paint.state, paint.cells.len()))
+    .collect();
+  assert_eq!(states, [(0, 2), (1, 1)]);
+
+  let universe = CpuUniverse::new(UVec2::new(12, 3), &rule, Topology::DeadEdges);
+  let result = stamped(&universe, &pattern, IVec2::new(5, 1), StampMode::Or);
+  let mut expected = universe.clone();
+  expected.load(&pattern, UVec2::new(5, 1));
+  assert_eq!(result.words(), expected.words());
+}
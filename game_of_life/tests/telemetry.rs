@@ -0,0 +1,47 @@
+use game_of_life::{Clock, Span, Telemetry, Timing, TimingRing, TimingSummary, diagnostic_path};
+
+#[test]
+fn summarizes_the_latest_timings() {
+  let mut ring = TimingRing::new(20);
+  assert_eq!(ring.summary(), None);
+
+  for sample in 1..=20 {
+    ring.push(f64::from(sample));
+  }
+  assert_eq!(
+    ring.summary(),
+    Some(TimingSummary {
+      min: 1.0,
+      avg: 10.5,
+      p95: 19.0,
+      max: 20.0,
+    })
+  );
+
+  // the oldest timings are overwritten once the ring is full
+  for _ in 0..10 {
+    ring.push(100.0);
+  }
+  assert_eq!(ring.len(), 20);
+  let summary = ring.summary().unwrap();
+  assert_eq!((summary.min, summary.max), (11.0, 100.0));
+  assert_eq!(summary.avg, 57.75);
+}
+
+#[test]
+fn keeps_timings_per_span() {
+  let mut telemetry = Telemetry::default();
+  telemetry.record(Timing {
+    span: Span::Update,
+    clock: Clock::Gpu,
+    milliseconds: 2.0,
+  });
+  assert_eq!(telemetry.timings(Span::Update).len(), 1);
+  assert_eq!(telemetry.clock(Span::Update), Clock::Gpu);
+  assert!(telemetry.timings(Span::Display).is_empty());
+
+  assert_eq!(
+    diagnostic_path(Span::Display, Clock::Gpu).as_str(),
+    "game_of_life/display/elapsed_gpu"
+  );
+}
@@ -1,27 +1,189 @@
+use std::{env, process::ExitCode, time::Duration};
+
 use bevy::app::PluginGroup;
 use bevy::{
-  DefaultPlugins,
-  app::App,
+  DefaultPlugins, MinimalPlugins,
+  app::{App, AppExit, ScheduleRunnerPlugin},
+  log::LogPlugin,
+  math::UVec2,
   render::texture::ImagePlugin,
   utils::default,
-  window::{Window, WindowPlugin},
+  window::{ExitCondition, Window, WindowPlugin},
+  winit::WinitPlugin,
+};
+
+use game_of_life::{
+  CensusRun, GameOfLifePlugin, HeadlessBackend, HeadlessRun, HeadlessStart, Rule,
 };
 
-use cellular_automata::CellularAutomataPlugin;
-use game_of_life::GameOfLifePlugin;
+const USAGE: &str = "\
+usage: shaders [--headless [options] | --census [census options]]
+
+headless options:
+  --cpu                  run on the CPU instead of a headless render device
+  --generations <n>      generations to run (default 1000)
+  --seed <n>             start from the random soup of this seed (default 0)
+  --pattern <file>       start from a pattern file instead
+  --rule <rule>          rule to run, unless the pattern has one (default B3/S23)
+  --size <w>x<h>         universe size in cells (default 1024x1024)
+  --output <file>        final state, .rle, .cells or .mc (default final.rle)
+  --stats <file>         population CSV (default population.csv)
+  --sample-interval <n>  generations between population samples (default 100)
+  --resolution <w>x<h>   size of the unused display texture (default 1280x720)
 
-fn main() {
-  App::new()
-    .add_plugins((DefaultPlugins
-      .set(WindowPlugin {
-        primary_window: Some(Window {
-          resizable: false,
-          present_mode: bevy::window::PresentMode::AutoNoVsync,
+census options:
+  --soups <n>            soups to run (default 100)
+  --seed <n>             seed of the first soup (default 0)
+  --soup-size <n>        side of the square soups (default 16)
+  --size <w>x<h>         universe size in cells (default 256x256)
+  --generations <n>      generations a soup may take to settle (default 10000)
+  --rule <rule>          rule to run (default B3/S23)
+  --report <file>        object counts (default census.csv)";
+
+fn main() -> ExitCode {
+  let args: Vec<String> = env::args().skip(1).collect();
+  if args.is_empty() {
+    App::new()
+      .add_plugins((DefaultPlugins
+        .set(WindowPlugin {
+          primary_window: Some(Window {
+            present_mode: bevy::window::PresentMode::AutoNoVsync,
+            ..default()
+          }),
           ..default()
-        }),
-        ..default()
-      })
-      .set(ImagePlugin::default_nearest()),))
-    .add_plugins(GameOfLifePlugin)
-    .run();
+        })
+        .set(ImagePlugin::default_nearest()),))
+      .add_plugins(GameOfLifePlugin::default())
+      .run();
+    return ExitCode::SUCCESS;
+  }
+
+  let (plugin, rule) = match parse_args(&args) {
+    Ok(parsed) => parsed,
+    Err(err) => {
+      eprintln!("{err}\n\n{USAGE}");
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let mut app = App::new();
+  match plugin.headless.as_ref().map(|run| run.backend) {
+    _ if plugin.census.is_some() => {
+      app.add_plugins((MinimalPlugins, LogPlugin::default()));
+    }
+    Some(HeadlessBackend::Cpu) => {
+      app.add_plugins((MinimalPlugins, LogPlugin::default()));
+    }
+    _ => {
+      // no window and no event loop, so it runs on machines without a display
+      app.add_plugins((
+        DefaultPlugins
+          .set(WindowPlugin {
+            primary_window: None,
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+          })
+          .disable::<WinitPlugin>(),
+        ScheduleRunnerPlugin::run_loop(Duration::ZERO),
+      ));
+    }
+  }
+  app.insert_resource(rule).add_plugins(plugin);
+
+  match app.run() {
+    AppExit::Success => ExitCode::SUCCESS,
+    AppExit::Error(code) => ExitCode::from(code.get()),
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<(GameOfLifePlugin, Rule), String> {
+  let mut plugin = GameOfLifePlugin::default();
+  let mut run = HeadlessRun::default();
+  let mut census = CensusRun::default();
+  let mut soups = census.seeds.len() as u32;
+  let mut rule = Rule::default();
+  let mut headless = false;
+  let mut census_mode = false;
+
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let mut value = || {
+      args
+        .next()
+        .map(String::as_str)
+        .ok_or_else(|| format!("missing value for {arg}"))
+    };
+    let invalid = |value: &str| format!("invalid value '{value}' for {arg}");
+
+    match arg.as_str() {
+      "--headless" => headless = true,
+      "--census" => census_mode = true,
+      "--cpu" => run.backend = HeadlessBackend::Cpu,
+      "--generations" => {
+        let value = value()?;
+        run.generations = value.parse().map_err(|_| invalid(value))?;
+        census.max_generations = run.generations;
+      }
+      "--seed" => {
+        let value = value()?;
+        let seed = value.parse().map_err(|_| invalid(value))?;
+        run.start = HeadlessStart::Seed(seed);
+        census.seeds.start = seed;
+      }
+      "--pattern" => run.start = HeadlessStart::Pattern(value()?.into()),
+      "--rule" => {
+        let value = value()?;
+        rule = value
+          .parse()
+          .map_err(|err| format!("{}: {err}", invalid(value)))?;
+      }
+      "--size" => {
+        let value = value()?;
+        run.size = parse_size(value).ok_or_else(|| invalid(value))?;
+        census.size = run.size;
+      }
+      "--output" => run.output = value()?.into(),
+      "--stats" => run.statistics = value()?.into(),
+      "--sample-interval" => {
+        let value = value()?;
+        run.sample_interval = value.parse().map_err(|_| invalid(value))?;
+      }
+      "--soups" => {
+        let value = value()?;
+        soups = value.parse().map_err(|_| invalid(value))?;
+      }
+      "--soup-size" => {
+        let value = value()?;
+        census.soup_size = value
+          .parse()
+          .ok()
+          .filter(|&size| size > 0)
+          .ok_or_else(|| invalid(value))?;
+      }
+      "--report" => census.report = value()?.into(),
+      "--resolution" => {
+        let value = value()?;
+        plugin.resolution = parse_size(value).ok_or_else(|| invalid(value))?;
+      }
+      _ => return Err(format!("unknown argument {arg}")),
+    }
+  }
+
+  if census_mode {
+    census.seeds = census.seeds.start..census.seeds.start.saturating_add(soups);
+    plugin.census = Some(census);
+    return Ok((plugin, rule));
+  }
+  if !headless {
+    return Err("options are only supported with --headless or --census".to_string());
+  }
+  plugin.headless = Some(run);
+  Ok((plugin, rule))
+}
+
+/// Parses `<w>x<h>`, both non-zero.
+fn parse_size(value: &str) -> Option<UVec2> {
+  let (x, y) = value.split_once('x')?;
+  let size = UVec2::new(x.parse().ok()?, y.parse().ok()?);
+  size.cmpgt(UVec2::ZERO).all().then_some(size)
 }